@@ -0,0 +1,94 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::{Filter, RouterConfig};
+
+/// The items a `DELETE /{prefix}` request deletes.
+#[derive(Debug, PartialEq)]
+pub(crate) enum DeleteTarget<PrimaryKeyType> {
+    Ids(Vec<PrimaryKeyType>),
+    /// The items matching the filters, all items without filters.
+    Matching(Vec<Filter>),
+}
+
+/// Reads the comma separated `ids` parameter of the batch routes, `None` when it is missing.
+pub(crate) fn parse_ids<PrimaryKeyType: DeserializeOwned>(query: &[(String, String)]) -> Result<Option<Vec<PrimaryKeyType>>, String> {
+    let Some((_, ids)) = query.iter().find(|(key, _)| key == "ids") else {
+        return Ok(None);
+    };
+
+    ids.split(',')
+        .map(|id| {
+            let id = id.trim();
+            // numbers are parsed as JSON, anything else is taken as a string
+            serde_json::from_str(id)
+                .or_else(|_| serde_json::from_value(Value::String(id.to_string())))
+                .map_err(|_| format!("`{}` is not a valid id", id))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+impl RouterConfig {
+    /// Picks the items to delete by `ids` or by the filters of the list route,
+    /// deleting all items has to be confirmed with `confirm=true`.
+    pub(crate) fn delete_target<PrimaryKeyType: DeserializeOwned>(&self, query: &[(String, String)]) -> Result<DeleteTarget<PrimaryKeyType>, String> {
+        let filters = self.parse_filters(query)?;
+        let confirmed = query.iter().any(|(key, value)| key == "confirm" && value == "true");
+
+        match parse_ids(query)? {
+            Some(_) if !self.delete_items => Err(String::from("deleting items by `ids` is disabled")),
+            Some(_) if !filters.is_empty() => Err(String::from("`ids` can't be combined with filters")),
+            Some(ids) => Ok(DeleteTarget::Ids(ids)),
+            None if !self.delete_all_items => Err(String::from("`ids` is required")),
+            None if filters.is_empty() && !confirmed => Err(String::from("deleting all items has to be confirmed with `confirm=true`")),
+            None => Ok(DeleteTarget::Matching(filters)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FieldType, FilterCondition, FilterValue};
+
+    fn query(ids: &str) -> Vec<(String, String)> {
+        vec![(String::from("ids"), String::from(ids))]
+    }
+
+    fn config(delete_items: bool, delete_all_items: bool) -> RouterConfig {
+        RouterConfig {
+            filter_fields: vec![(String::from("published"), FieldType::Bool)],
+            delete_items,
+            delete_all_items,
+            ..RouterConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_ids() {
+        assert_eq!(parse_ids::<i64>(&[]).unwrap(), None);
+        assert_eq!(parse_ids::<i64>(&query("1,2, 3")).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(parse_ids::<String>(&query("a,12")).unwrap(), Some(vec![String::from("a"), String::from("12")]));
+        assert!(parse_ids::<i64>(&query("1,a")).is_err());
+        assert!(parse_ids::<i64>(&query("")).is_err());
+        assert!(parse_ids::<i64>(&query("1,,2")).is_err());
+    }
+
+    #[test]
+    fn test_delete_target() {
+        let published = |value: &str| (String::from("published"), String::from(value));
+        let confirm = |value: &str| (String::from("confirm"), String::from(value));
+
+        assert_eq!(config(true, true).delete_target::<i64>(&query("1,2")).unwrap(), DeleteTarget::Ids(vec![1, 2]));
+        assert_eq!(config(true, true).delete_target::<i64>(&[published("true")]).unwrap(), DeleteTarget::Matching(vec![
+            Filter { field: String::from("published"), condition: FilterCondition::Eq(FilterValue::Bool(true)) },
+        ]));
+        assert_eq!(config(true, true).delete_target::<i64>(&[confirm("true")]).unwrap(), DeleteTarget::Matching(Vec::new()));
+        assert!(config(true, true).delete_target::<i64>(&[]).is_err());
+        assert!(config(true, true).delete_target::<i64>(&[confirm("false")]).is_err());
+        assert!(config(true, true).delete_target::<i64>(&[query("1").remove(0), published("true")]).is_err());
+        assert!(config(false, true).delete_target::<i64>(&query("1")).is_err());
+        assert!(config(true, false).delete_target::<i64>(&[confirm("true")]).is_err());
+        assert!(config(true, false).delete_target::<i64>(&[published("maybe")]).is_err());
+    }
+}
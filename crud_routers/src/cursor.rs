@@ -0,0 +1,292 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::{FieldType, Filter, FilterCondition, FilterValue, ReadDeleteRepository, RouterConfig, Sort};
+use crate::servers::ErrorResponse;
+
+/// How the list route splits the items into pages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PaginationMode {
+    /// `skip` and `limit`, the response is a JSON array.
+    #[default]
+    Offset,
+    /// An opaque `cursor` and `limit`, the response is a [CursorPage].
+    Cursor,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct CursorPagination{
+    /// `next_cursor` of the previous page, omitted for the first page
+    pub(crate) cursor: Option<String>,
+    pub(crate) limit: Option<u64>,
+}
+
+/// Response of the list route in [PaginationMode::Cursor].
+#[derive(Serialize)]
+pub struct CursorPage<Schema> {
+    pub items: Vec<Schema>,
+    /// Requests the next page, `None` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// Position of the last item of a page: its sort keys followed by its primary key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    /// `None` for a null sort key.
+    pub sort_values: Vec<Option<FilterValue>>,
+    pub primary_key: FilterValue,
+}
+
+fn json_value(value: &FilterValue) -> Value {
+    match value {
+        FilterValue::Bool(value) => Value::from(*value),
+        FilterValue::Integer(value) => Value::from(*value),
+        FilterValue::Float(value) => Value::from(*value),
+        FilterValue::String(value) => Value::from(value.as_str()),
+    }
+}
+
+fn has_type(value: &FilterValue, field_type: FieldType) -> bool {
+    matches!(
+        (value, field_type),
+        (FilterValue::Bool(_), FieldType::Bool)
+            | (FilterValue::Integer(_), FieldType::Integer | FieldType::Float)
+            | (FilterValue::Float(_), FieldType::Float)
+            | (FilterValue::String(_), FieldType::String)
+    )
+}
+
+impl Cursor {
+    /// Reads the cursor of `item` from its JSON representation, fields have to be
+    /// named like their columns.
+    pub(crate) fn after(item: &impl Serialize, sort: &[Sort], primary_key: &str) -> Result<Self, String> {
+        let item = serde_json::to_value(item).map_err(|e| e.to_string())?;
+        let field = |field: &str| match item.get(field) {
+            Some(Value::Null) => Ok(None),
+            value => value.and_then(FilterValue::from_json)
+                .map(Some)
+                .ok_or_else(|| format!("`{}` can't be used in a cursor, it has to be a string, number or boolean", field)),
+        };
+
+        Ok(Self {
+            sort_values: sort.iter().map(|sort| field(&sort.field)).collect::<Result<_, _>>()?,
+            primary_key: field(primary_key)?
+                .ok_or_else(|| format!("`{}` can't be used in a cursor, it is null", primary_key))?,
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        let values: Vec<Value> = self.sort_values.iter()
+            .map(|value| value.as_ref().map_or(Value::Null, json_value))
+            .chain(std::iter::once(json_value(&self.primary_key)))
+            .collect();
+        URL_SAFE_NO_PAD.encode(Value::Array(values).to_string())
+    }
+
+    /// Parses a token made by [Cursor::encode] for a list sorted by keys of `sort_types`,
+    /// the values have to be of these types and the primary key a `PrimaryKeyType`.
+    pub fn decode<PrimaryKeyType: DeserializeOwned>(token: &str, sort_types: &[FieldType]) -> Result<Self, String> {
+        let invalid = || format!("`{}` is not a valid cursor", token);
+
+        let json = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+        let Ok(Value::Array(values)) = serde_json::from_slice(&json) else {
+            return Err(invalid());
+        };
+        if values.len() != sort_types.len() + 1 {
+            return Err(invalid());
+        }
+
+        let mut values = values.iter()
+            .map(|value| match value {
+                Value::Null => Some(None),
+                value => FilterValue::from_json(value).map(Some),
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        let primary_key = values.pop().flatten().ok_or_else(invalid)?;
+        let typed = |(value, field_type): (&Option<FilterValue>, &FieldType)| match value {
+            Some(value) => has_type(value, *field_type),
+            None => true,
+        };
+        if !values.iter().zip(sort_types).all(typed)
+            || serde_json::from_value::<PrimaryKeyType>(json_value(&primary_key)).is_err() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            sort_values: values,
+            primary_key,
+        })
+    }
+
+    /// The condition selecting the items after the cursor, as alternatives of filters
+    /// that all have to match: `a > 1 OR (a = 1 AND id > 7)` for `sort=a`.
+    ///
+    /// Nulls sort before all values, so they come first in ascending and last in descending order.
+    pub fn keyset(&self, sort: &[Sort], primary_key: &str) -> Vec<Vec<Filter>> {
+        let mut keyset = Vec::new();
+        let mut equal = Vec::new();
+
+        for (sort, value) in sort.iter().zip(&self.sort_values) {
+            let filter = |condition| Filter {
+                field: sort.field.clone(),
+                condition,
+            };
+            let after = match (value, sort.descending) {
+                (Some(value), false) => vec![FilterCondition::Gt(value.clone())],
+                (Some(value), true) => vec![FilterCondition::Lt(value.clone()), FilterCondition::IsNull(true)],
+                (None, false) => vec![FilterCondition::IsNull(false)],
+                (None, true) => vec![],
+            };
+            for condition in after {
+                let mut after = equal.clone();
+                after.push(filter(condition));
+                keyset.push(after);
+            }
+            equal.push(filter(match value {
+                Some(value) => FilterCondition::Eq(value.clone()),
+                None => FilterCondition::IsNull(true),
+            }));
+        }
+
+        equal.push(Filter {
+            field: primary_key.to_string(),
+            condition: FilterCondition::Gt(self.primary_key.clone()),
+        });
+        keyset.push(equal);
+        keyset
+    }
+}
+
+impl RouterConfig {
+    /// The types of the sort keys, to check the values of cursors with.
+    pub(crate) fn sort_types(&self, sort: &[Sort]) -> Vec<FieldType> {
+        sort.iter()
+            .filter_map(|sort| self.sort_fields.iter().find(|(field, _)| *field == sort.field))
+            .map(|(_, field_type)| *field_type)
+            .collect()
+    }
+}
+
+impl<Schema: Serialize> CursorPage<Schema> {
+    /// Loads one more item than the limit to find out whether there is a next page.
+    ///
+    /// A sort key of the last item that can't be put into the cursor, like an object, is answered with 400.
+    pub(crate) async fn load<Repo, PrimaryKeyType>(config: &RouterConfig, repository: &Repo, cursor: Option<Cursor>, limit: Option<u64>, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Self, ErrorResponse>
+    where
+        Repo: ReadDeleteRepository<Schema, PrimaryKeyType>,
+    {
+        let mut items = repository.list_items_after(cursor, limit.map(|limit| limit.saturating_add(1)), filters, sort.clone()).await
+            .map_err(|e| config.error(e))?;
+
+        let next_cursor = match limit {
+            Some(limit) if items.len() as u64 > limit => {
+                items.truncate(limit as usize);
+                match items.last() {
+                    Some(item) => Some(Cursor::after(item, &sort, &repository.get_primary_key_name())
+                        .map_err(|e| config.error_with_status(400, e))?
+                        .encode()),
+                    None => None,
+                }
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            items,
+            next_cursor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort() -> Vec<Sort> {
+        vec![Sort { field: String::from("title"), descending: true }]
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let cursor = Cursor {
+            sort_values: vec![Some(FilterValue::String(String::from("Post")))],
+            primary_key: FilterValue::Integer(7),
+        };
+
+        assert_eq!(Cursor::decode::<i32>(&cursor.encode(), &[FieldType::String]).unwrap(), cursor);
+        assert!(Cursor::decode::<i32>(&cursor.encode(), &[]).is_err());
+        assert!(Cursor::decode::<i32>("not a cursor", &[FieldType::String]).is_err());
+
+        // values of other types than the sort keys and the primary key
+        assert!(Cursor::decode::<i32>(&cursor.encode(), &[FieldType::Integer]).is_err());
+        assert!(Cursor::decode::<bool>(&cursor.encode(), &[FieldType::String]).is_err());
+
+        let cursor = Cursor {
+            sort_values: vec![None],
+            primary_key: FilterValue::Integer(7),
+        };
+        assert_eq!(Cursor::decode::<i32>(&cursor.encode(), &[FieldType::String]).unwrap(), cursor);
+        // `[1,null]`
+        assert!(Cursor::decode::<i32>("WzEsbnVsbF0", &[FieldType::Integer]).is_err());
+    }
+
+    #[test]
+    fn test_cursor_after() {
+        let item = serde_json::json!({"id": 7, "title": "Post", "body": null});
+
+        assert_eq!(Cursor::after(&item, &sort(), "id").unwrap(), Cursor {
+            sort_values: vec![Some(FilterValue::String(String::from("Post")))],
+            primary_key: FilterValue::Integer(7),
+        });
+        assert_eq!(Cursor::after(&item, &[Sort { field: String::from("body"), descending: false }], "id").unwrap(), Cursor {
+            sort_values: vec![None],
+            primary_key: FilterValue::Integer(7),
+        });
+        assert!(Cursor::after(&item, &[Sort { field: String::from("tags"), descending: false }], "id").is_err());
+        assert!(Cursor::after(&item, &sort(), "body").is_err());
+    }
+
+    #[test]
+    fn test_keyset() {
+        let cursor = Cursor {
+            sort_values: vec![Some(FilterValue::String(String::from("Post")))],
+            primary_key: FilterValue::Integer(7),
+        };
+
+        assert_eq!(cursor.keyset(&sort(), "id"), vec![
+            vec![
+                Filter { field: String::from("title"), condition: FilterCondition::Lt(FilterValue::String(String::from("Post"))) },
+            ],
+            vec![
+                Filter { field: String::from("title"), condition: FilterCondition::IsNull(true) },
+            ],
+            vec![
+                Filter { field: String::from("title"), condition: FilterCondition::Eq(FilterValue::String(String::from("Post"))) },
+                Filter { field: String::from("id"), condition: FilterCondition::Gt(FilterValue::Integer(7)) },
+            ],
+        ]);
+    }
+
+    #[test]
+    fn test_null_keyset() {
+        let cursor = Cursor {
+            sort_values: vec![None],
+            primary_key: FilterValue::Integer(7),
+        };
+        let title = |condition| Filter { field: String::from("title"), condition };
+        let id = Filter { field: String::from("id"), condition: FilterCondition::Gt(FilterValue::Integer(7)) };
+
+        // nulls come first in ascending order, the items after them are the non-null ones
+        assert_eq!(cursor.keyset(&[Sort { field: String::from("title"), descending: false }], "id"), vec![
+            vec![title(FilterCondition::IsNull(false))],
+            vec![title(FilterCondition::IsNull(true)), id.clone()],
+        ]);
+        // and last in descending order
+        assert_eq!(cursor.keyset(&sort(), "id"), vec![
+            vec![title(FilterCondition::IsNull(true)), id],
+        ]);
+    }
+}
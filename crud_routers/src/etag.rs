@@ -0,0 +1,141 @@
+use serde::Serialize;
+use serde_json::Value;
+use crate::{CRUDRepository, CrudError, ReadDeleteRepository, RouterConfig};
+use crate::servers::ErrorResponse;
+
+impl RouterConfig {
+    /// The field holding the version of the items: the version column of the repository, or the
+    /// `version_field` of the router for repositories without one. A `version_field` naming another
+    /// column is answered with 500, as its ETags would not be checked by the writes.
+    pub(crate) fn version_field<'a>(&'a self, repository: &impl CRUDRepository) -> Result<Option<&'a str>, ErrorResponse> {
+        match (self.version_field.as_deref(), repository.get_version_column()) {
+            (Some(field), Some(column)) if field != column => Err(self.error(CrudError::Backend(
+                format!("the version field `{}` is not the version column `{}` of the repository", field, column)
+            ))),
+            (Some(field), _) => Ok(Some(field)),
+            (None, column) => Ok(column),
+        }
+    }
+
+    /// The strong ETag of an item, `None` when ETags are disabled. It is the version field
+    /// of the item if there is one, otherwise a hash of the serialized item.
+    pub(crate) fn etag(&self, version_field: Option<&str>, item: &impl Serialize) -> Option<String> {
+        if !self.etags {
+            return None;
+        }
+        let item = serde_json::to_value(item).ok()?;
+        let version = version_field.and_then(|field| item.get(field));
+
+        match version {
+            Some(Value::String(version)) => Some(format!("\"{}\"", version)),
+            Some(version) if !version.is_null() => Some(format!("\"{}\"", version)),
+            _ => Some(format!("\"{:016x}\"", fnv1a(&serde_json::to_vec(&item).ok()?))),
+        }
+    }
+
+    /// Checks the `If-Match` header of a PUT, PATCH or DELETE request against the stored item,
+    /// a stale or missing item is answered with 412 Precondition Failed.
+    ///
+    /// Returns the version of the matched item if the repository has a version column. The caller
+    /// writes with that version, so that an update slipping in after this check fails the version
+    /// check of the repository with 409 Conflict. Hash ETags are only checked here.
+    pub(crate) async fn if_match<Repo, Schema, PrimaryKeyType>(&self, repository: &Repo, id: PrimaryKeyType, if_match: Option<&str>) -> Result<Option<i64>, ErrorResponse>
+    where
+        Repo: ReadDeleteRepository<Schema, PrimaryKeyType>,
+        Schema: Serialize,
+    {
+        let Some(if_match) = if_match.filter(|_| self.etags) else {
+            return Ok(None);
+        };
+        let version_field = self.version_field(repository)?;
+        let item = repository.get_item(id).await.map_err(|e| self.error(e))?
+            .and_then(|item| serde_json::to_value(item).ok());
+        match item.as_ref().and_then(|item| self.etag(version_field, item)) {
+            Some(etag) if if_match.split(',').any(|tag| tag.trim() == "*" || tag.trim() == etag) => Ok(repository.get_version_column()
+                .and_then(|column| item.as_ref()?.get(column)?.as_i64())),
+            _ => Err(self.error_with_status(412, "the item has been modified")),
+        }
+    }
+}
+
+/// Sets the version column of an update to the version its `If-Match` header was checked against.
+pub(crate) fn pin_version(item: &mut Value, version_column: Option<&str>, version: Option<i64>) {
+    if let (Value::Object(fields), Some(column), Some(version)) = (item, version_column, version) {
+        fields.insert(column.to_string(), Value::from(version));
+    }
+}
+
+/// Whether the `If-None-Match` header matches the ETag, so that a GET request can be answered with 304 Not Modified.
+pub(crate) fn none_match(if_none_match: &str, etag: &str) -> bool {
+    let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match.split(',').any(|tag| tag.trim() == "*" || weak(tag) == weak(etag))
+}
+
+/// 64 bit FNV-1a, which unlike the hasher of the standard library is the same on every build.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    struct Repo(Option<&'static str>);
+
+    impl CRUDRepository for Repo {
+        fn get_version_column(&self) -> Option<&'static str> {
+            self.0
+        }
+    }
+
+    fn config(etags: bool, version_field: Option<&str>) -> RouterConfig {
+        RouterConfig {
+            etags,
+            version_field: version_field.map(String::from),
+            ..RouterConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_etag() {
+        let item = json!({"id": 1, "title": "Post", "version": 3});
+        assert_eq!(config(false, None).etag(None, &item), None);
+
+        let etag = config(true, None).etag(None, &item).unwrap();
+        assert_eq!(config(true, None).etag(None, &item), Some(etag.clone()));
+        assert_ne!(config(true, None).etag(None, &json!({"id": 1, "title": "Changed", "version": 3})), Some(etag));
+
+        assert_eq!(config(true, None).etag(Some("version"), &item), Some(String::from("\"3\"")));
+        assert_eq!(config(true, None).etag(Some("version"), &json!({"version": "abc"})), Some(String::from("\"abc\"")));
+        assert_eq!(config(true, None).etag(Some("version"), &json!({"id": 1})), config(true, None).etag(None, &json!({"id": 1})));
+    }
+
+    #[test]
+    fn test_version_field() {
+        assert_eq!(config(true, None).version_field(&Repo(None)).unwrap(), None);
+        assert_eq!(config(true, Some("version")).version_field(&Repo(None)).unwrap(), Some("version"));
+        assert_eq!(config(true, None).version_field(&Repo(Some("version"))).unwrap(), Some("version"));
+        assert_eq!(config(true, Some("version")).version_field(&Repo(Some("version"))).unwrap(), Some("version"));
+        assert!(config(true, Some("revision")).version_field(&Repo(Some("version"))).is_err());
+    }
+
+    #[test]
+    fn test_pin_version() {
+        let mut item = json!({"title": "Post", "version": 1});
+        pin_version(&mut item, Some("version"), Some(3));
+        assert_eq!(item, json!({"title": "Post", "version": 3}));
+
+        pin_version(&mut item, None, Some(4));
+        pin_version(&mut item, Some("version"), None);
+        assert_eq!(item, json!({"title": "Post", "version": 3}));
+    }
+
+    #[test]
+    fn test_none_match() {
+        assert!(none_match("\"1\"", "\"1\""));
+        assert!(none_match("\"0\", W/\"1\"", "\"1\""));
+        assert!(none_match("*", "\"1\""));
+        assert!(!none_match("\"0\"", "\"1\""));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::RouterConfig;
+
+/// Type of a filterable field, used to parse the values in the query string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Bool,
+    Integer,
+    Float,
+    String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterCondition {
+    Eq(FilterValue),
+    Ne(FilterValue),
+    Lt(FilterValue),
+    Gt(FilterValue),
+    In(Vec<FilterValue>),
+    /// The field contains the text, `%` and `_` match literally.
+    Contains(String),
+    /// `true` matches null fields, `false` matches the others.
+    IsNull(bool),
+}
+
+/// One condition of the list route, parsed from `field=value` or `field__op=value`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    pub field: String,
+    pub condition: FilterCondition,
+}
+
+/// Escape character of the `LIKE` patterns built for [FilterCondition::Contains].
+#[cfg(any(feature = "diesel", feature = "sea-orm", feature = "sqlx"))]
+pub(crate) const LIKE_ESCAPE: char = '!';
+
+/// Returns the `LIKE` pattern that matches values containing `text`.
+#[cfg(any(feature = "diesel", feature = "sea-orm", feature = "sqlx"))]
+pub(crate) fn contains_pattern(text: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in text.chars() {
+        if matches!(c, '%' | '_') || c == LIKE_ESCAPE {
+            pattern.push(LIKE_ESCAPE);
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+impl FilterValue {
+    /// The value of a JSON scalar, `None` for null, arrays and objects.
+    pub(crate) fn from_json(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::Bool(value) => Some(FilterValue::Bool(*value)),
+            serde_json::Value::Number(value) => match value.as_i64() {
+                Some(value) => Some(FilterValue::Integer(value)),
+                None => Some(FilterValue::Float(value.as_f64().unwrap_or(f64::NAN))),
+            },
+            serde_json::Value::String(value) => Some(FilterValue::String(value.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl FieldType {
+    fn parse(&self, value: &str) -> Result<FilterValue, String> {
+        match self {
+            FieldType::Bool => value.parse().map(FilterValue::Bool)
+                .map_err(|_| format!("`{}` is not a boolean", value)),
+            FieldType::Integer => value.parse().map(FilterValue::Integer)
+                .map_err(|_| format!("`{}` is not an integer", value)),
+            FieldType::Float => value.parse().map(FilterValue::Float)
+                .map_err(|_| format!("`{}` is not a number", value)),
+            FieldType::String => Ok(FilterValue::String(value.to_string())),
+        }
+    }
+
+    /// Operators, besides equality, that can be used with fields of this type.
+    pub(crate) fn operators(&self) -> &'static [&'static str] {
+        match self {
+            FieldType::Bool => &["ne", "in", "is_null"],
+            FieldType::Integer | FieldType::Float => &["ne", "lt", "gt", "in", "is_null"],
+            FieldType::String => &["ne", "lt", "gt", "in", "contains", "is_null"],
+        }
+    }
+}
+
+impl Filter {
+    fn parse(field: &str, operator: &str, field_type: FieldType, value: &str) -> Result<Self, String> {
+        if operator != "eq" && !field_type.operators().contains(&operator) {
+            return Err(format!("`{}` can't be filtered with `{}`", field, operator));
+        }
+
+        let condition = match operator {
+            "eq" => FilterCondition::Eq(field_type.parse(value)?),
+            "ne" => FilterCondition::Ne(field_type.parse(value)?),
+            "lt" => FilterCondition::Lt(field_type.parse(value)?),
+            "gt" => FilterCondition::Gt(field_type.parse(value)?),
+            "in" => FilterCondition::In(
+                value.split(',')
+                    .map(|value| field_type.parse(value))
+                    .collect::<Result<_, _>>()?
+            ),
+            "contains" => FilterCondition::Contains(value.to_string()),
+            _ => FilterCondition::IsNull(
+                value.parse().map_err(|_| format!("`{}` is not a boolean", value))?
+            ),
+        };
+
+        Ok(Self {
+            field: field.to_string(),
+            condition,
+        })
+    }
+}
+
+impl RouterConfig {
+    /// Picks the filters of the declared fields out of the query string,
+    /// other parameters like `skip` and `limit` are ignored.
+    pub(crate) fn parse_filters(&self, query: &[(String, String)]) -> Result<Vec<Filter>, String> {
+        let mut filters = Vec::new();
+
+        for (key, value) in query {
+            let (field, operator) = match key.rsplit_once("__") {
+                Some((field, operator)) => (field, operator),
+                None => (key.as_str(), "eq"),
+            };
+
+            let Some((_, field_type)) = self.filter_fields.iter().find(|(name, _)| name == field) else {
+                continue;
+            };
+            filters.push(Filter::parse(field, operator, *field_type, value)?);
+        }
+
+        Ok(filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RouterConfig {
+        RouterConfig {
+            filter_fields: vec![
+                (String::from("id"), FieldType::Integer),
+                (String::from("title"), FieldType::String),
+                (String::from("published"), FieldType::Bool),
+            ],
+            ..RouterConfig::default()
+        }
+    }
+
+    fn query(params: &[(&str, &str)]) -> Vec<(String, String)> {
+        params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        let filters = config().parse_filters(&query(&[
+            ("published", "true"),
+            ("title__contains", "foo"),
+            ("id__in", "1,2,3"),
+            ("id__gt", "4"),
+            ("title__is_null", "false"),
+            ("skip", "10"),
+        ])).unwrap();
+
+        assert_eq!(filters, vec![
+            Filter { field: String::from("published"), condition: FilterCondition::Eq(FilterValue::Bool(true)) },
+            Filter { field: String::from("title"), condition: FilterCondition::Contains(String::from("foo")) },
+            Filter { field: String::from("id"), condition: FilterCondition::In(vec![FilterValue::Integer(1), FilterValue::Integer(2), FilterValue::Integer(3)]) },
+            Filter { field: String::from("id"), condition: FilterCondition::Gt(FilterValue::Integer(4)) },
+            Filter { field: String::from("title"), condition: FilterCondition::IsNull(false) },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_invalid_filters() {
+        assert!(config().parse_filters(&query(&[("published", "maybe")])).is_err());
+        assert!(config().parse_filters(&query(&[("id__in", "1,a")])).is_err());
+        assert!(config().parse_filters(&query(&[("published__lt", "true")])).is_err());
+        assert!(config().parse_filters(&query(&[("id__contains", "1")])).is_err());
+        assert!(config().parse_filters(&query(&[("title__like", "foo")])).is_err());
+    }
+
+    #[cfg(any(feature = "diesel", feature = "sea-orm", feature = "sqlx"))]
+    #[test]
+    fn test_contains_pattern() {
+        assert_eq!(contains_pattern("100%_!"), "%100!%!_!!%");
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::{CreateRepository, CrudError, ReadDeleteRepository, RouterConfig, UpdateRepository};
+use crate::etag::fnv1a;
+
+/// Header with which clients mark retries of the same create request.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Header set on responses that are replayed from the [IdempotencyStore].
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// A response of the create item route, stored under its `Idempotency-Key`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotentResponse {
+    /// The `Idempotency-Key` header prefixed with the path of the route.
+    pub key: String,
+    /// `0` while the request that reserved the key is still running.
+    pub status: u16,
+    pub location: Option<String>,
+    /// The created item as JSON.
+    pub body: String,
+    /// Hash of the request body, a retry with another body is rejected.
+    pub fingerprint: String,
+    /// Unix timestamp in seconds after which the response isn't replayed any more.
+    pub expires_at: i64,
+}
+
+impl IdempotentResponse {
+    fn is_expired(&self) -> bool {
+        self.expires_at <= now()
+    }
+
+    fn is_pending(&self) -> bool {
+        self.status == 0
+    }
+}
+
+/// Stores the responses of the create item route, see [crate::CrudRouterBuilder::idempotency_store].
+///
+/// Expired responses may still be returned by `get`, they are skipped by the router.
+pub trait IdempotencyStore: Send + Sync + 'static {
+    fn get(&self, key: &str) -> impl Future<Output = Result<Option<IdempotentResponse>, CrudError>> + Send;
+    /// Stores the response unless one is already stored under its key, and returns whether it did.
+    /// This reserves the key, so it has to be atomic.
+    fn put(&self, response: IdempotentResponse) -> impl Future<Output = Result<bool, CrudError>> + Send;
+    /// Replaces the response stored under its key.
+    fn replace(&self, response: IdempotentResponse) -> impl Future<Output = Result<(), CrudError>> + Send;
+    fn remove(&self, key: &str) -> impl Future<Output = Result<(), CrudError>> + Send;
+}
+
+/// Shares a store between routers, e.g. the ones of the actix workers.
+impl<Store: IdempotencyStore> IdempotencyStore for Arc<Store> {
+    async fn get(&self, key: &str) -> Result<Option<IdempotentResponse>, CrudError> {
+        self.as_ref().get(key).await
+    }
+
+    async fn put(&self, response: IdempotentResponse) -> Result<bool, CrudError> {
+        self.as_ref().put(response).await
+    }
+
+    async fn replace(&self, response: IdempotentResponse) -> Result<(), CrudError> {
+        self.as_ref().replace(response).await
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), CrudError> {
+        self.as_ref().remove(key).await
+    }
+}
+
+/// Object safe [IdempotencyStore], so the router config doesn't depend on the store type.
+pub(crate) trait DynIdempotencyStore: Send + Sync {
+    fn get<'a>(&'a self, key: &'a str) -> Pin<Box<dyn Future<Output = Result<Option<IdempotentResponse>, CrudError>> + Send + 'a>>;
+    fn put(&self, response: IdempotentResponse) -> Pin<Box<dyn Future<Output = Result<bool, CrudError>> + Send + '_>>;
+    fn replace(&self, response: IdempotentResponse) -> Pin<Box<dyn Future<Output = Result<(), CrudError>> + Send + '_>>;
+    fn remove<'a>(&'a self, key: &'a str) -> Pin<Box<dyn Future<Output = Result<(), CrudError>> + Send + 'a>>;
+}
+
+impl<Store: IdempotencyStore> DynIdempotencyStore for Store {
+    fn get<'a>(&'a self, key: &'a str) -> Pin<Box<dyn Future<Output = Result<Option<IdempotentResponse>, CrudError>> + Send + 'a>> {
+        Box::pin(IdempotencyStore::get(self, key))
+    }
+
+    fn put(&self, response: IdempotentResponse) -> Pin<Box<dyn Future<Output = Result<bool, CrudError>> + Send + '_>> {
+        Box::pin(IdempotencyStore::put(self, response))
+    }
+
+    fn replace(&self, response: IdempotentResponse) -> Pin<Box<dyn Future<Output = Result<(), CrudError>> + Send + '_>> {
+        Box::pin(IdempotencyStore::replace(self, response))
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> Pin<Box<dyn Future<Output = Result<(), CrudError>> + Send + 'a>> {
+        Box::pin(IdempotencyStore::remove(self, key))
+    }
+}
+
+/// How long a key stays reserved for a running request. A server that stops before it stores the
+/// response leaves the reservation behind, which then only blocks retries for this long.
+const RESERVATION_LEASE: Duration = Duration::from_secs(60);
+
+/// The store of a router and how long its responses are replayed.
+#[derive(Clone)]
+pub(crate) struct Idempotency {
+    pub(crate) store: Arc<dyn DynIdempotencyStore>,
+    pub(crate) ttl: Duration,
+}
+
+/// Keeps the responses in memory, they are lost on restart and not shared between instances.
+#[derive(Default)]
+pub struct InMemoryIdempotencyStore {
+    responses: Mutex<HashMap<String, IdempotentResponse>>
+}
+
+impl InMemoryIdempotencyStore {
+    pub fn new() -> Self{
+        Self::default()
+    }
+}
+
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    async fn get(&self, key: &str) -> Result<Option<IdempotentResponse>, CrudError> {
+        let responses = self.responses.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(responses.get(key).cloned())
+    }
+
+    async fn put(&self, response: IdempotentResponse) -> Result<bool, CrudError> {
+        let mut responses = self.responses.lock().unwrap_or_else(PoisonError::into_inner);
+        responses.retain(|_, response| !response.is_expired());
+        if responses.contains_key(&response.key) {
+            return Ok(false);
+        }
+        responses.insert(response.key.clone(), response);
+        Ok(true)
+    }
+
+    async fn replace(&self, response: IdempotentResponse) -> Result<(), CrudError> {
+        let mut responses = self.responses.lock().unwrap_or_else(PoisonError::into_inner);
+        responses.insert(response.key.clone(), response);
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), CrudError> {
+        let mut responses = self.responses.lock().unwrap_or_else(PoisonError::into_inner);
+        responses.remove(key);
+        Ok(())
+    }
+}
+
+/// Keeps the responses in a table of a repository, so they survive restarts and are shared by all
+/// instances of the server.
+///
+/// `Record` is the row of the table. It is converted through JSON, so it needs the fields of
+/// [IdempotentResponse] with `key` as primary key.
+pub struct RepositoryIdempotencyStore<Repo, Record> {
+    repository: Arc<Repo>,
+    _marker: PhantomData<fn() -> Record>,
+}
+
+impl<Repo, Record> RepositoryIdempotencyStore<Repo, Record> {
+    pub fn new(repository: Arc<Repo>) -> Self{
+        Self{
+            repository,
+            _marker: PhantomData
+        }
+    }
+}
+
+impl<Repo, Record> IdempotencyStore for RepositoryIdempotencyStore<Repo, Record>
+where
+    Repo: ReadDeleteRepository<Record, String> + CreateRepository<Record, Record> + UpdateRepository<Record, String, Record> + Send + Sync + 'static,
+    Record: Serialize + DeserializeOwned + Send + 'static,
+{
+    async fn get(&self, key: &str) -> Result<Option<IdempotentResponse>, CrudError> {
+        let Some(record) = self.repository.get_item(key.to_string()).await? else {
+            return Ok(None);
+        };
+        let response: IdempotentResponse = serde_json::from_value(serde_json::to_value(record)?)?;
+        if response.is_expired() {
+            match self.repository.delete_item(response.key).await {
+                Ok(()) | Err(CrudError::NotFound) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Some(response))
+    }
+
+    async fn put(&self, response: IdempotentResponse) -> Result<bool, CrudError> {
+        let record: Record = serde_json::from_value(serde_json::to_value(response)?)?;
+        match self.repository.create_item(record).await {
+            Ok(_) => Ok(true),
+            // the primary key makes the insert fail if the key is taken
+            Err(CrudError::Conflict(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn replace(&self, response: IdempotentResponse) -> Result<(), CrudError> {
+        let key = response.key.clone();
+        let record: Record = serde_json::from_value(serde_json::to_value(response)?)?;
+        self.repository.update_item(key, record).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), CrudError> {
+        match self.repository.delete_item(key.to_string()).await {
+            Ok(()) | Err(CrudError::NotFound) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl RouterConfig {
+    /// The key under which the response of a create request is stored, `None` without a store
+    /// or an `Idempotency-Key` header.
+    pub(crate) fn idempotency_key(&self, path: &str, key: Option<&str>) -> Option<String> {
+        self.idempotency.as_ref()?;
+        key.filter(|key| !key.is_empty())
+            .map(|key| format!("{} {}", path, key))
+    }
+
+    /// Reserves the key for a create request with the given body, or returns the response of an
+    /// earlier request with the same key. Fails with [CrudError::Conflict] while that request is
+    /// still running and with [CrudError::Validation] if it had another body.
+    pub(crate) async fn reserve(&self, key: Option<String>, body: &Value) -> Result<Reservation, CrudError> {
+        let (Some(idempotency), Some(key)) = (&self.idempotency, key) else {
+            return Ok(Reservation::Untracked);
+        };
+        let lease = i64::try_from(idempotency.ttl.min(RESERVATION_LEASE).as_secs()).unwrap_or(i64::MAX);
+        let pending = IdempotentResponse {
+            key,
+            status: 0,
+            location: None,
+            body: String::new(),
+            fingerprint: format!("{:016x}", fnv1a(body.to_string().as_bytes())),
+            expires_at: now().saturating_add(lease),
+        };
+
+        // an expired response is only dropped by the next get, so the put is tried once more
+        for _ in 0..2 {
+            if idempotency.store.put(pending.clone()).await? {
+                return Ok(Reservation::Reserved(pending));
+            }
+            let Some(stored) = idempotency.store.get(&pending.key).await?.filter(|response| !response.is_expired()) else {
+                continue;
+            };
+            if stored.fingerprint != pending.fingerprint {
+                return Err(CrudError::Validation("the idempotency key was used for a request with another body".to_string()));
+            }
+            if stored.is_pending() {
+                return Err(CrudError::Conflict("a request with the same idempotency key is still running".to_string()));
+            }
+            return Ok(Reservation::Replay(stored));
+        }
+        Err(CrudError::Conflict("a request with the same idempotency key is still running".to_string()))
+    }
+
+    /// Stores the response of a create request under its reserved key for the TTL. The item is created at
+    /// this point, so a failing store is only logged and retries get 409 until the reservation expires.
+    ///
+    /// The item is serialized right away, so the future doesn't borrow it.
+    pub(crate) fn remember(&self, reservation: Reservation, status: u16, location: Option<&str>, item: &impl Serialize) -> impl Future<Output = ()> + Send + '_ {
+        let response = match (&self.idempotency, reservation) {
+            (Some(idempotency), Reservation::Reserved(pending)) => Some((&idempotency.store, serde_json::to_string(item).map(|body| IdempotentResponse {
+                status,
+                location: location.map(String::from),
+                body,
+                expires_at: now().saturating_add(i64::try_from(idempotency.ttl.as_secs()).unwrap_or(i64::MAX)),
+                ..pending.clone()
+            }), pending.key)),
+            _ => None,
+        };
+
+        async move {
+            let result = match response {
+                Some((store, Ok(response), _)) => store.replace(response).await,
+                // without a body to replay a retry has to create the item again
+                Some((store, Err(_), key)) => store.remove(&key).await,
+                None => Ok(()),
+            };
+            if let Err(e) = result {
+                log::error!("failed to store the response of an idempotent request: {}", e);
+            }
+        }
+    }
+
+    /// Releases the key of a failed create request, failed requests are not stored so they can be retried.
+    pub(crate) async fn release(&self, reservation: Reservation) {
+        if let (Some(idempotency), Reservation::Reserved(pending)) = (&self.idempotency, reservation) {
+            if let Err(e) = idempotency.store.remove(&pending.key).await {
+                log::error!("failed to release the idempotency key of a failed request: {}", e);
+            }
+        }
+    }
+}
+
+/// What the create item route does with a request, see [RouterConfig::reserve].
+pub(crate) enum Reservation {
+    /// There is no store or no `Idempotency-Key` header.
+    Untracked,
+    /// The key is reserved for this request.
+    Reserved(IdempotentResponse),
+    /// An earlier request with the same key was answered with this response.
+    Replay(IdempotentResponse),
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use crate::InMemoryRepository;
+    use super::*;
+
+    fn response(key: &str, expires_at: i64) -> IdempotentResponse {
+        IdempotentResponse {
+            key: key.to_string(),
+            status: 201,
+            location: Some(String::from("/posts/1")),
+            body: String::from("{\"id\":1}"),
+            fingerprint: String::from("0123456789abcdef"),
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        let store = InMemoryIdempotencyStore::new();
+        let (valid, expired) = (now() + 60, now() - 60);
+        assert_eq!(IdempotencyStore::get(&store, "/posts a").await, Ok(None));
+
+        assert_eq!(IdempotencyStore::put(&store, response("/posts a", valid)).await, Ok(true));
+        assert_eq!(IdempotencyStore::put(&store, response("/posts b", expired)).await, Ok(true));
+        assert_eq!(IdempotencyStore::get(&store, "/posts a").await, Ok(Some(response("/posts a", valid))));
+        assert!(IdempotencyStore::get(&store, "/posts b").await.unwrap().is_some());
+
+        // the first response under a key is kept, expired ones are dropped on the next put
+        let mut other = response("/posts a", valid);
+        other.body = String::from("{\"id\":2}");
+        assert_eq!(IdempotencyStore::put(&store, other.clone()).await, Ok(false));
+        assert_eq!(IdempotencyStore::get(&store, "/posts a").await.unwrap().unwrap().body, "{\"id\":1}");
+        assert_eq!(IdempotencyStore::get(&store, "/posts b").await, Ok(None));
+
+        IdempotencyStore::replace(&store, other).await.unwrap();
+        assert_eq!(IdempotencyStore::get(&store, "/posts a").await.unwrap().unwrap().body, "{\"id\":2}");
+        IdempotencyStore::remove(&store, "/posts a").await.unwrap();
+        assert_eq!(IdempotencyStore::get(&store, "/posts a").await, Ok(None));
+    }
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct IdempotencyKey {
+        key: String,
+        status: u16,
+        location: Option<String>,
+        body: String,
+        fingerprint: String,
+        expires_at: i64,
+    }
+
+    async fn check_reserve(store: impl IdempotencyStore) {
+        let config = RouterConfig {
+            idempotency: Some(Idempotency { store: Arc::new(store), ttl: Duration::from_secs(3600) }),
+            ..RouterConfig::default()
+        };
+        let store = &config.idempotency.as_ref().unwrap().store;
+        let key = || Some(String::from("/posts a"));
+        let body = serde_json::json!({"title": "Post"});
+        assert!(matches!(config.reserve(None, &body).await, Ok(Reservation::Untracked)));
+
+        // a retry while the first request is running, the reservation only lasts for the lease
+        let Ok(reservation) = config.reserve(key(), &body).await else { panic!("the key is not reserved") };
+        assert!(matches!(config.reserve(key(), &body).await, Err(CrudError::Conflict(_))));
+        assert!(store.get("/posts a").await.unwrap().unwrap().expires_at <= now() + 60);
+
+        // the response is kept for the TTL
+        config.remember(reservation, 201, Some("/posts/1"), &serde_json::json!({"id": 1})).await;
+        let Ok(Reservation::Replay(response)) = config.reserve(key(), &body).await else { panic!("the response is not replayed") };
+        assert_eq!((response.status, response.body.as_str()), (201, "{\"id\":1}"));
+        assert!(response.expires_at > now() + 60);
+        assert!(matches!(config.reserve(key(), &serde_json::json!({"title": "Other Post"})).await, Err(CrudError::Validation(_))));
+
+        // a failed request releases the key
+        let Ok(reservation) = config.reserve(Some(String::from("/posts b")), &body).await else { panic!("the key is not reserved") };
+        config.release(reservation).await;
+        assert!(matches!(config.reserve(Some(String::from("/posts b")), &body).await, Ok(Reservation::Reserved(_))));
+
+        // an expired reservation is taken over
+        let mut expired = store.get("/posts b").await.unwrap().unwrap();
+        expired.expires_at = now() - 1;
+        store.replace(expired).await.unwrap();
+        assert!(matches!(config.reserve(Some(String::from("/posts b")), &body).await, Ok(Reservation::Reserved(_))));
+    }
+
+    #[tokio::test]
+    async fn test_reserve() {
+        check_reserve(InMemoryIdempotencyStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_repository_store() {
+        let repository = InMemoryRepository::<IdempotencyKey, String>::with_id_generator(String::new)
+            .primary_key("key");
+        check_reserve(RepositoryIdempotencyStore::<_, IdempotencyKey>::new(Arc::new(repository))).await;
+    }
+}
@@ -1,13 +1,32 @@
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
 use serde::Deserialize;
 
 mod servers;
 mod repositories;
+mod filter;
+mod sort;
+mod cursor;
+mod page;
+mod patch;
+mod batch;
+mod status;
+mod etag;
+mod idempotency;
 #[cfg(feature = "openapi")]
 mod openapi;
 
 pub use repositories::*;
 pub use servers::*;
+pub use filter::{FieldType, Filter, FilterCondition, FilterValue};
+pub use sort::Sort;
+pub use cursor::{Cursor, CursorPage, CursorPagination, PaginationMode};
+pub use page::{ListFormat, OffsetPage, TOTAL_COUNT_HEADER};
+pub use patch::{nullable, JSON_PATCH_CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE};
+pub use status::StatusCodes;
+pub use idempotency::{IdempotencyStore, IdempotentResponse, InMemoryIdempotencyStore, RepositoryIdempotencyStore, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER};
+use idempotency::Idempotency;
 
 pub struct Empty;
 pub struct Assigned<T>(PhantomData<T>);
@@ -26,12 +45,30 @@ impl<T> Assignable for Assigned<T>{
 pub struct CrudRouterBuilder<'a, Server: Assignable, Repo, Schema: Assignable, PrimaryKeyType: Assignable, CreateSchema:Assignable, UpdateSchema:Assignable> {
     prefix: Option<&'a str>,
     tag: Option<&'a str>,
+    error_format: ErrorFormat,
+    status_codes: StatusCodes,
+    pagination_mode: PaginationMode,
+    list_format: ListFormat,
+    default_limit: Option<u64>,
+    max_limit: Option<u64>,
+    filter_fields: Vec<(&'a str, FieldType)>,
+    sort_fields: Vec<(&'a str, FieldType)>,
+    etags_enabled: bool,
+    version_field: Option<&'a str>,
+    idempotency: Option<Idempotency>,
     list_items_route_disabled: bool,
     get_item_route_disabled: bool,
+    item_exists_route_disabled: bool,
+    count_items_route_disabled: bool,
     delete_item_route_disabled: bool,
+    delete_items_route_disabled: bool,
     delete_all_items_route_disabled: bool,
     create_item_route_disabled: bool,
+    create_items_route_disabled: bool,
     update_item_route_disabled: bool,
+    update_items_route_disabled: bool,
+    patch_item_route_disabled: bool,
+    json_patch_enabled: bool,
     _marker: PhantomData<(Server, Repo, Schema, PrimaryKeyType, CreateSchema, UpdateSchema)>,
 }
 
@@ -40,125 +77,229 @@ impl<'a, Repo> CrudRouterBuilder<'a, Empty, Repo, Empty, Empty, Empty, Empty> {
         CrudRouterBuilder {
             prefix: None,
             tag: None,
+            error_format: ErrorFormat::default(),
+            status_codes: StatusCodes::default(),
+            pagination_mode: PaginationMode::default(),
+            list_format: ListFormat::default(),
+            default_limit: None,
+            max_limit: None,
+            filter_fields: Vec::new(),
+            sort_fields: Vec::new(),
+            etags_enabled: false,
+            version_field: None,
+            idempotency: None,
             list_items_route_disabled: false,
             get_item_route_disabled: false,
+            item_exists_route_disabled: false,
+            count_items_route_disabled: false,
             delete_item_route_disabled: false,
-            delete_all_items_route_disabled: false,
+            delete_items_route_disabled: false,
+            delete_all_items_route_disabled: true,
             create_item_route_disabled: false,
+            create_items_route_disabled: false,
             update_item_route_disabled: false,
+            update_items_route_disabled: false,
+            patch_item_route_disabled: false,
+            json_patch_enabled: false,
             _marker: Default::default()
         }
     }
 }
 
-impl <'a, Server, Repo, Schema: Assignable, PrimaryKeyType: Assignable, CreateSchema:Assignable, UpdateSchema:Assignable> CrudRouterBuilder<'a, Assigned<Server>, Repo, Schema, PrimaryKeyType, CreateSchema, UpdateSchema> {
-    pub fn prefix(self, prefix: &'a str) -> Self{
+impl<'a, Server: Assignable, Repo, Schema: Assignable, PrimaryKeyType: Assignable, CreateSchema:Assignable, UpdateSchema:Assignable> CrudRouterBuilder<'a, Server, Repo, Schema, PrimaryKeyType, CreateSchema, UpdateSchema> {
+    /// Moves all settings to a builder with different type parameters.
+    fn assign<NewRepo, NewSchema: Assignable, NewPrimaryKeyType: Assignable, NewCreateSchema: Assignable, NewUpdateSchema: Assignable>(self) -> CrudRouterBuilder<'a, Server, NewRepo, NewSchema, NewPrimaryKeyType, NewCreateSchema, NewUpdateSchema> {
         CrudRouterBuilder{
-            prefix: Some(prefix),
+            prefix: self.prefix,
             tag: self.tag,
-            _marker: Default::default(),
+            error_format: self.error_format,
+            status_codes: self.status_codes,
+            pagination_mode: self.pagination_mode,
+            list_format: self.list_format,
+            default_limit: self.default_limit,
+            max_limit: self.max_limit,
+            filter_fields: self.filter_fields,
+            sort_fields: self.sort_fields,
+            etags_enabled: self.etags_enabled,
+            version_field: self.version_field,
+            idempotency: self.idempotency,
             list_items_route_disabled: self.list_items_route_disabled,
             get_item_route_disabled: self.get_item_route_disabled,
+            item_exists_route_disabled: self.item_exists_route_disabled,
+            count_items_route_disabled: self.count_items_route_disabled,
             delete_item_route_disabled: self.delete_item_route_disabled,
+            delete_items_route_disabled: self.delete_items_route_disabled,
             delete_all_items_route_disabled: self.delete_all_items_route_disabled,
             create_item_route_disabled: self.create_item_route_disabled,
+            create_items_route_disabled: self.create_items_route_disabled,
             update_item_route_disabled: self.update_item_route_disabled,
+            update_items_route_disabled: self.update_items_route_disabled,
+            patch_item_route_disabled: self.patch_item_route_disabled,
+            json_patch_enabled: self.json_patch_enabled,
+            _marker: Default::default(),
+        }
+    }
+
+    fn router_config(&self) -> RouterConfig {
+        RouterConfig {
+            error_format: self.error_format,
+            status_codes: self.status_codes,
+            pagination_mode: self.pagination_mode,
+            list_format: self.list_format,
+            default_limit: self.default_limit,
+            max_limit: self.max_limit,
+            filter_fields: self.filter_fields.iter()
+                .map(|(field, field_type)| (field.to_string(), *field_type))
+                .collect(),
+            sort_fields: self.sort_fields.iter()
+                .map(|(field, field_type)| (field.to_string(), *field_type))
+                .collect(),
+            merge_patch: !self.patch_item_route_disabled,
+            json_patch: self.json_patch_enabled,
+            delete_items: !self.delete_items_route_disabled,
+            delete_all_items: !self.delete_all_items_route_disabled,
+            etags: self.etags_enabled,
+            version_field: self.version_field.map(String::from),
+            idempotency: self.idempotency.clone(),
+        }
+    }
+}
+
+impl <'a, Server, Repo, Schema: Assignable, PrimaryKeyType: Assignable, CreateSchema:Assignable, UpdateSchema:Assignable> CrudRouterBuilder<'a, Assigned<Server>, Repo, Schema, PrimaryKeyType, CreateSchema, UpdateSchema> {
+    pub fn prefix(self, prefix: &'a str) -> Self{
+        Self {
+            prefix: Some(prefix),
+            ..self
         }
     }
 
     #[cfg(feature = "openapi")]
     pub fn tag(self, tag: &'a str) -> Self{
-        CrudRouterBuilder{
-            prefix: self.prefix,
+        Self {
             tag: Some(tag),
-            _marker: Default::default(),
-            list_items_route_disabled: self.list_items_route_disabled,
-            get_item_route_disabled: self.get_item_route_disabled,
-            delete_item_route_disabled: self.delete_item_route_disabled,
-            delete_all_items_route_disabled: self.delete_all_items_route_disabled,
-            create_item_route_disabled: self.create_item_route_disabled,
-            update_item_route_disabled: self.update_item_route_disabled,
+            ..self
+        }
+    }
+
+    pub fn error_format(self, error_format: ErrorFormat) -> Self{
+        Self {
+            error_format,
+            ..self
+        }
+    }
+
+    /// Selects the status codes of the create and delete item routes, [StatusCodes::Rest] by default.
+    pub fn status_codes(self, status_codes: StatusCodes) -> Self{
+        Self {
+            status_codes,
+            ..self
+        }
+    }
+
+    /// Selects how the list route is paginated, [PaginationMode::Offset] by default.
+    pub fn pagination_mode(self, pagination_mode: PaginationMode) -> Self{
+        Self {
+            pagination_mode,
+            ..self
+        }
+    }
+
+    /// Adds the total count to the offset paginated list route, [ListFormat::Array] by default.
+    pub fn list_format(self, list_format: ListFormat) -> Self{
+        Self {
+            list_format,
+            ..self
+        }
+    }
+
+    /// Limit of the list route when the request doesn't set one, unlimited by default.
+    /// It is capped at the [Self::max_limit].
+    pub fn default_limit(self, default_limit: u64) -> Self{
+        Self {
+            default_limit: Some(self.max_limit.map_or(default_limit, |max_limit| default_limit.min(max_limit))),
+            ..self
+        }
+    }
+
+    /// Rejects list requests with a greater limit with 400, it is also the
+    /// limit of requests without one if there is no [Self::default_limit].
+    pub fn max_limit(self, max_limit: u64) -> Self{
+        Self {
+            default_limit: self.default_limit.map(|default_limit| default_limit.min(max_limit)),
+            max_limit: Some(max_limit),
+            ..self
+        }
+    }
+
+    /// Allows filtering the list route by `field`, e.g. `?field=value` or `?field__gt=value`.
+    pub fn filterable_field(mut self, field: &'a str, field_type: FieldType) -> Self{
+        self.filter_fields.push((field, field_type));
+        self
+    }
+
+    /// Allows sorting the list route by `field` with `?sort=field` or `?sort=-field`, the type
+    /// checks its values in cursors.
+    pub fn sortable_field(mut self, field: &'a str, field_type: FieldType) -> Self{
+        self.sort_fields.push((field, field_type));
+        self
+    }
+
+    /// Adds an `ETag` header to the item routes, answers a matching `If-None-Match` with 304
+    /// and a stale `If-Match` on PUT, PATCH and DELETE with 412.
+    pub fn enable_etags(self) -> Self{
+        Self {
+            etags_enabled: true,
+            ..self
+        }
+    }
+
+    /// Takes the ETags from `field` of the schema, which changes on every update,
+    /// instead of hashing the whole item. Repositories with a version column use it without this,
+    /// and `field` has to name that column.
+    pub fn version_field(self, field: &'a str) -> Self{
+        Self {
+            version_field: Some(field),
+            ..self
+        }
+    }
+
+    /// Replays the response of the create item route to retries with the same `Idempotency-Key`
+    /// header for `ttl`, instead of creating the item again.
+    pub fn idempotency_store(self, store: impl IdempotencyStore, ttl: Duration) -> Self{
+        Self {
+            idempotency: Some(Idempotency { store: Arc::new(store), ttl }),
+            ..self
         }
     }
 }
 
 impl<'a, Server, Schema: Assignable, PrimaryKeyType: Assignable> CrudRouterBuilder<'a, Assigned<Server>, Empty, Schema, PrimaryKeyType, Empty, Empty> {
     pub fn repository<Repo: CRUDRepository>(self) -> CrudRouterBuilder<'a, Assigned<Server>, Repo, Schema, PrimaryKeyType, Empty, Empty>{
-        CrudRouterBuilder{
-            prefix: self.prefix,
-            tag: self.tag,
-            _marker: Default::default(),
-            list_items_route_disabled: self.list_items_route_disabled,
-            get_item_route_disabled: self.get_item_route_disabled,
-            delete_item_route_disabled: self.delete_item_route_disabled,
-            delete_all_items_route_disabled: self.delete_all_items_route_disabled,
-            create_item_route_disabled: self.create_item_route_disabled,
-            update_item_route_disabled: self.update_item_route_disabled,
-        }
+        self.assign()
     }
 }
 
 impl<'a, Server, Repo> CrudRouterBuilder<'a, Assigned<Server>, Repo, Empty, Empty, Empty, Empty> {
     #[cfg(not(feature = "openapi"))]
     pub fn schema<Schema, PrimaryKeyType>(self) -> CrudRouterBuilder<'a, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, Empty, Empty>{
-        CrudRouterBuilder{
-            prefix: self.prefix,
-            tag: self.tag,
-            _marker: Default::default(),
-            list_items_route_disabled: self.list_items_route_disabled,
-            get_item_route_disabled: self.get_item_route_disabled,
-            delete_item_route_disabled: self.delete_item_route_disabled,
-            delete_all_items_route_disabled: self.delete_all_items_route_disabled,
-            create_item_route_disabled: self.create_item_route_disabled,
-            update_item_route_disabled: self.update_item_route_disabled,
-        }
+        self.assign()
     }
 
     #[cfg(feature = "openapi")]
     pub fn schema<Schema: utoipa::ToSchema, PrimaryKeyType>(self) -> CrudRouterBuilder<'a, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, Empty, Empty>{
-        CrudRouterBuilder{
-            prefix: self.prefix,
-            tag: self.tag,
-            _marker: Default::default(),
-            list_items_route_disabled: self.list_items_route_disabled,
-            get_item_route_disabled: self.get_item_route_disabled,
-            delete_item_route_disabled: self.delete_item_route_disabled,
-            delete_all_items_route_disabled: self.delete_all_items_route_disabled,
-            create_item_route_disabled: self.create_item_route_disabled,
-            update_item_route_disabled: self.update_item_route_disabled,
-        }
+        self.assign()
     }
 }
 
 impl<'a, Server, Repo, Schema, PrimaryKeyType, UpdateSchema: Assignable> CrudRouterBuilder<'a, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, Empty, UpdateSchema> {
     #[cfg(not(feature = "openapi"))]
     pub fn create_schema<CreateSchema>(self) -> CrudRouterBuilder<'a, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, UpdateSchema>{
-        CrudRouterBuilder{
-            prefix: self.prefix,
-            tag: self.tag,
-            _marker: Default::default(),
-            list_items_route_disabled: self.list_items_route_disabled,
-            get_item_route_disabled: self.get_item_route_disabled,
-            delete_item_route_disabled: self.delete_item_route_disabled,
-            delete_all_items_route_disabled: self.delete_all_items_route_disabled,
-            create_item_route_disabled: self.create_item_route_disabled,
-            update_item_route_disabled: self.update_item_route_disabled,
-        }
+        self.assign()
     }
 
     #[cfg(feature = "openapi")]
     pub fn create_schema<CreateSchema: utoipa::ToSchema>(self) -> CrudRouterBuilder<'a, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, UpdateSchema>{
-        CrudRouterBuilder{
-            prefix: self.prefix,
-            tag: self.tag,
-            _marker: Default::default(),
-            list_items_route_disabled: self.list_items_route_disabled,
-            get_item_route_disabled: self.get_item_route_disabled,
-            delete_item_route_disabled: self.delete_item_route_disabled,
-            delete_all_items_route_disabled: self.delete_all_items_route_disabled,
-            create_item_route_disabled: self.create_item_route_disabled,
-            update_item_route_disabled: self.update_item_route_disabled,
-        }
+        self.assign()
     }
 }
 
@@ -175,36 +316,49 @@ impl<Server: Assignable, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sch
 impl<'a, Server, Repo, Schema, PrimaryKeyType, CreateSchema: Assignable> CrudRouterBuilder<'a, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, Empty> {
     #[cfg(not(feature = "openapi"))]
     pub fn update_schema<UpdateSchema>(self) -> CrudRouterBuilder<'a, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, Assigned<UpdateSchema>>{
-        CrudRouterBuilder{
-            prefix: self.prefix,
-            tag: self.tag,
-            _marker: Default::default(),
-            list_items_route_disabled: self.list_items_route_disabled,
-            get_item_route_disabled: self.get_item_route_disabled,
-            delete_item_route_disabled: self.delete_item_route_disabled,
-            delete_all_items_route_disabled: self.delete_all_items_route_disabled,
-            create_item_route_disabled: self.create_item_route_disabled,
-            update_item_route_disabled: self.update_item_route_disabled,
-        }
+        self.assign()
     }
 
     #[cfg(feature = "openapi")]
     pub fn update_schema<UpdateSchema: utoipa::ToSchema>(self) -> CrudRouterBuilder<'a, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, Assigned<UpdateSchema>>{
-        CrudRouterBuilder{
-            prefix: self.prefix,
-            tag: self.tag,
-            _marker: Default::default(),
-            list_items_route_disabled: self.list_items_route_disabled,
-            get_item_route_disabled: self.get_item_route_disabled,
-            delete_item_route_disabled: self.delete_item_route_disabled,
-            delete_all_items_route_disabled: self.delete_all_items_route_disabled,
-            create_item_route_disabled: self.create_item_route_disabled,
-            update_item_route_disabled: self.update_item_route_disabled,
-        }
+        self.assign()
     }
 }
 
+/// Sets of routes that can be enabled at once with [CrudRouterBuilder::route_preset].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoutePreset {
+    /// Only the list and get item routes, and their `HEAD` routes.
+    ReadOnly,
+    /// All routes but deleting all items without filters, the default.
+    #[default]
+    ReadWrite,
+    /// All routes.
+    Full,
+}
+
 impl<Server, Repo, Schema, PrimaryKeyType, CreateSchema: Assignable, UpdateSchema: Assignable> CrudRouterBuilder<'_, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, UpdateSchema> {
+    /// Enables the routes of the preset and disables the others,
+    /// single routes can still be enabled or disabled afterwards.
+    pub fn route_preset(self, preset: RoutePreset) -> Self{
+        let read_only = preset == RoutePreset::ReadOnly;
+        Self {
+            list_items_route_disabled: false,
+            get_item_route_disabled: false,
+            item_exists_route_disabled: false,
+            count_items_route_disabled: false,
+            delete_item_route_disabled: read_only,
+            delete_items_route_disabled: read_only,
+            delete_all_items_route_disabled: preset != RoutePreset::Full,
+            create_item_route_disabled: read_only,
+            create_items_route_disabled: read_only,
+            update_item_route_disabled: read_only,
+            update_items_route_disabled: read_only,
+            patch_item_route_disabled: read_only,
+            ..self
+        }
+    }
+
     pub fn disable_list_items_route(self) -> Self{
         Self {
             list_items_route_disabled: true,
@@ -219,6 +373,23 @@ impl<Server, Repo, Schema, PrimaryKeyType, CreateSchema: Assignable, UpdateSchem
         }
     }
 
+    /// Disables `HEAD /{prefix}/{id}`, which answers 200 or 404 without loading the item.
+    pub fn disable_item_exists_route(self) -> Self{
+        Self {
+            item_exists_route_disabled: true,
+            ..self
+        }
+    }
+
+    /// Disables `HEAD /{prefix}`, which sends the number of items matching the filters
+    /// in the [TOTAL_COUNT_HEADER] header.
+    pub fn disable_count_items_route(self) -> Self{
+        Self {
+            count_items_route_disabled: true,
+            ..self
+        }
+    }
+
     pub fn disable_delete_item_route(self) -> Self{
         Self {
             delete_item_route_disabled: true,
@@ -226,12 +397,29 @@ impl<Server, Repo, Schema, PrimaryKeyType, CreateSchema: Assignable, UpdateSchem
         }
     }
 
+    /// Disables `DELETE /{prefix}?ids=1,2,3`, which deletes the listed items.
+    pub fn disable_delete_items_route(self) -> Self{
+        Self {
+            delete_items_route_disabled: true,
+            ..self
+        }
+    }
+
     pub fn disable_delete_all_items_route(self) -> Self{
         Self {
             delete_all_items_route_disabled: true,
             ..self
         }
     }
+
+    /// Enables deleting all items with `DELETE /{prefix}?confirm=true`, and the ones matching filters
+    /// without `confirm`. It is disabled by default.
+    pub fn enable_delete_all_items_route(self) -> Self{
+        Self {
+            delete_all_items_route_disabled: false,
+            ..self
+        }
+    }
 }
 
 impl<Server, Repo, Schema, PrimaryKeyType, CreateSchema, UpdateSchema: Assignable> CrudRouterBuilder<'_, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, UpdateSchema> {
@@ -241,6 +429,14 @@ impl<Server, Repo, Schema, PrimaryKeyType, CreateSchema, UpdateSchema: Assignabl
             ..self
         }
     }
+
+    /// Disables `POST /{prefix}/bulk`, which creates a list of items at once.
+    pub fn disable_create_items_route(self) -> Self{
+        Self {
+            create_items_route_disabled: true,
+            ..self
+        }
+    }
 }
 
 impl<Server, Repo, Schema, PrimaryKeyType, CreateSchema: Assignable, UpdateSchema> CrudRouterBuilder<'_, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, Assigned<UpdateSchema>> {
@@ -250,6 +446,73 @@ impl<Server, Repo, Schema, PrimaryKeyType, CreateSchema: Assignable, UpdateSchem
             ..self
         }
     }
+
+    /// Disables `PATCH /{prefix}?ids=1,2,3`, which applies one update schema to the listed items.
+    pub fn disable_update_items_route(self) -> Self{
+        Self {
+            update_items_route_disabled: true,
+            ..self
+        }
+    }
+
+    pub fn disable_patch_item_route(self) -> Self{
+        Self {
+            patch_item_route_disabled: true,
+            ..self
+        }
+    }
+
+    /// Also accepts JSON Patch (RFC 6902) documents on the patch item route,
+    /// sent with the [JSON_PATCH_CONTENT_TYPE] content type.
+    pub fn enable_json_patch(self) -> Self{
+        Self {
+            json_patch_enabled: true,
+            ..self
+        }
+    }
+}
+
+/// Settings of a built router that are needed while handling requests.
+#[derive(Clone)]
+pub(crate) struct RouterConfig {
+    pub(crate) error_format: ErrorFormat,
+    pub(crate) status_codes: StatusCodes,
+    pub(crate) pagination_mode: PaginationMode,
+    pub(crate) list_format: ListFormat,
+    pub(crate) default_limit: Option<u64>,
+    pub(crate) max_limit: Option<u64>,
+    pub(crate) filter_fields: Vec<(String, FieldType)>,
+    pub(crate) sort_fields: Vec<(String, FieldType)>,
+    pub(crate) merge_patch: bool,
+    pub(crate) json_patch: bool,
+    pub(crate) delete_items: bool,
+    pub(crate) delete_all_items: bool,
+    pub(crate) etags: bool,
+    pub(crate) version_field: Option<String>,
+    pub(crate) idempotency: Option<Idempotency>,
+}
+
+/// The config of a router built with the default settings.
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            error_format: ErrorFormat::default(),
+            status_codes: StatusCodes::default(),
+            pagination_mode: PaginationMode::default(),
+            list_format: ListFormat::default(),
+            default_limit: None,
+            max_limit: None,
+            filter_fields: Vec::new(),
+            sort_fields: Vec::new(),
+            merge_patch: true,
+            json_patch: false,
+            delete_items: true,
+            delete_all_items: false,
+            etags: false,
+            version_field: None,
+            idempotency: None,
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -263,8 +526,11 @@ mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
 
+    #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
     struct Schema;
+    #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
     struct CreateSchema;
+    #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
     struct UpdateSchema;
     struct PrimaryKeyType;
     struct Repo;
@@ -273,26 +539,42 @@ mod tests {
             String::from("test_table_name")
         }
 
-        async fn list_items(&mut self, _pagination: Pagination) -> Vec<Schema> {
+        fn get_primary_key_name(&self) -> String {
+            unimplemented!()
+        }
+
+        async fn list_items(&self, _pagination: Pagination, _filters: Vec<Filter>, _sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+            unimplemented!()
+        }
+
+        async fn list_items_after(&self, _cursor: Option<Cursor>, _limit: Option<u64>, _filters: Vec<Filter>, _sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
             unimplemented!()
         }
 
-        async fn get_item(&mut self, _id: PrimaryKeyType) -> Option<Schema> {
+        async fn count_items(&self, _filters: Vec<Filter>) -> Result<u64, CrudError> {
             unimplemented!()
         }
 
-        async fn delete_item(&mut self, _id: PrimaryKeyType) {
+        async fn get_item(&self, _id: PrimaryKeyType) -> Result<Option<Schema>, CrudError> {
             unimplemented!()
         }
 
-        async fn delete_all_items(&mut self) -> usize {
+        async fn delete_item(&self, _id: PrimaryKeyType) -> Result<(), CrudError> {
+            unimplemented!()
+        }
+
+        async fn delete_items(&self, _ids: Vec<PrimaryKeyType>) -> Result<usize, CrudError> {
+            unimplemented!()
+        }
+
+        async fn delete_all_items(&self, _filters: Vec<Filter>) -> Result<usize, CrudError> {
             unimplemented!()
         }
     }
     impl CRUDRepository for Repo {}
     struct TestServer;
     impl ApiServer for TestServer {
-        fn get_id_path(prefix: &str) -> String {
+        fn get_id_path(_prefix: &str) -> String {
             unimplemented!()
         }
     }
@@ -306,13 +588,62 @@ mod tests {
     #[test]
     fn test_all_routes_enabled() {
         let b = CrudRouterBuilder::new::<TestServer>()
-            .repository::<Repo>();
+            .repository::<Repo>()
+            .schema::<Schema, PrimaryKeyType>()
+            .enable_delete_all_items_route();
         assert!(!b.list_items_route_disabled);
         assert!(!b.get_item_route_disabled);
         assert!(!b.delete_item_route_disabled);
         assert!(!b.delete_all_items_route_disabled);
         assert!(!b.create_item_route_disabled);
         assert!(!b.update_item_route_disabled);
+        assert!(!b.patch_item_route_disabled);
+    }
+
+    #[test]
+    fn test_delete_all_disabled_by_default() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .schema::<Schema, PrimaryKeyType>();
+        assert!(b.delete_all_items_route_disabled);
+        assert!(!b.delete_items_route_disabled);
+        assert!(!b.router_config().delete_all_items);
+    }
+
+    #[test]
+    fn test_route_presets() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .schema::<Schema, PrimaryKeyType>()
+            .create_schema::<CreateSchema>()
+            .update_schema::<UpdateSchema>()
+            .route_preset(RoutePreset::ReadOnly);
+        assert!(!b.list_items_route_disabled);
+        assert!(!b.get_item_route_disabled);
+        assert!(b.delete_item_route_disabled);
+        assert!(b.delete_items_route_disabled);
+        assert!(b.delete_all_items_route_disabled);
+        assert!(b.create_item_route_disabled);
+        assert!(b.create_items_route_disabled);
+        assert!(b.update_item_route_disabled);
+        assert!(b.update_items_route_disabled);
+        assert!(b.patch_item_route_disabled);
+
+        let b = b.route_preset(RoutePreset::ReadWrite);
+        assert!(!b.delete_item_route_disabled);
+        assert!(!b.delete_items_route_disabled);
+        assert!(b.delete_all_items_route_disabled);
+        assert!(!b.create_items_route_disabled);
+        assert!(!b.patch_item_route_disabled);
+
+        let b = b.route_preset(RoutePreset::Full);
+        assert!(!b.delete_all_items_route_disabled);
+        assert!(b.router_config().delete_all_items);
+
+        let b = b.route_preset(RoutePreset::ReadOnly)
+            .enable_delete_all_items_route();
+        assert!(b.delete_item_route_disabled);
+        assert!(!b.delete_all_items_route_disabled);
     }
 
     #[test]
@@ -330,6 +661,27 @@ mod tests {
         assert!(b.delete_all_items_route_disabled);
         assert!(!b.create_item_route_disabled);
         assert!(!b.update_item_route_disabled);
+        assert!(!b.patch_item_route_disabled);
+    }
+
+    #[test]
+    fn test_disable_head_routes() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .schema::<Schema, PrimaryKeyType>();
+        assert!(!b.item_exists_route_disabled);
+        assert!(!b.count_items_route_disabled);
+
+        let b = b.disable_item_exists_route()
+            .disable_count_items_route();
+        assert!(b.item_exists_route_disabled);
+        assert!(b.count_items_route_disabled);
+        assert!(!b.get_item_route_disabled);
+        assert!(!b.list_items_route_disabled);
+
+        let b = b.route_preset(RoutePreset::ReadOnly);
+        assert!(!b.item_exists_route_disabled);
+        assert!(!b.count_items_route_disabled);
     }
 
     #[test]
@@ -342,9 +694,43 @@ mod tests {
         assert!(!b.list_items_route_disabled);
         assert!(!b.get_item_route_disabled);
         assert!(!b.delete_item_route_disabled);
-        assert!(!b.delete_all_items_route_disabled);
+        assert!(b.delete_all_items_route_disabled);
         assert!(b.create_item_route_disabled);
         assert!(!b.update_item_route_disabled);
+        assert!(!b.patch_item_route_disabled);
+    }
+
+    #[test]
+    fn test_disable_create_items_route() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .schema::<Schema, PrimaryKeyType>()
+            .create_schema::<CreateSchema>();
+        assert!(!b.create_items_route_disabled);
+
+        let b = b.disable_create_items_route();
+        assert!(!b.create_item_route_disabled);
+        assert!(b.create_items_route_disabled);
+    }
+
+    #[test]
+    fn test_disable_batch_routes() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .schema::<Schema, PrimaryKeyType>()
+            .update_schema::<UpdateSchema>()
+            .enable_delete_all_items_route();
+        assert!(!b.delete_items_route_disabled);
+        assert!(!b.update_items_route_disabled);
+
+        let b = b.disable_delete_items_route()
+            .disable_update_items_route();
+        assert!(b.delete_items_route_disabled);
+        assert!(!b.delete_all_items_route_disabled);
+        assert!(b.update_items_route_disabled);
+        assert!(!b.update_item_route_disabled);
+        assert!(!b.router_config().delete_items);
+        assert!(b.router_config().delete_all_items);
     }
 
     #[test]
@@ -357,9 +743,128 @@ mod tests {
         assert!(!b.list_items_route_disabled);
         assert!(!b.get_item_route_disabled);
         assert!(!b.delete_item_route_disabled);
-        assert!(!b.delete_all_items_route_disabled);
+        assert!(b.delete_all_items_route_disabled);
         assert!(!b.create_item_route_disabled);
         assert!(b.update_item_route_disabled);
+        assert!(!b.patch_item_route_disabled);
+    }
+
+    #[test]
+    fn test_disable_patch_route() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .schema::<Schema, PrimaryKeyType>()
+            .update_schema::<UpdateSchema>()
+            .disable_patch_item_route();
+        assert!(!b.update_item_route_disabled);
+        assert!(b.patch_item_route_disabled);
+        assert!(!b.router_config().merge_patch);
+    }
+
+    #[test]
+    fn test_enable_json_patch() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .schema::<Schema, PrimaryKeyType>()
+            .update_schema::<UpdateSchema>();
+        assert!(!b.router_config().json_patch);
+
+        let b = b.enable_json_patch();
+        assert!(b.router_config().merge_patch);
+        assert!(b.router_config().json_patch);
+    }
+
+    #[test]
+    fn test_error_format() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>();
+        assert_eq!(b.error_format, ErrorFormat::PlainText);
+
+        let b = b.error_format(ErrorFormat::ProblemJson);
+        assert_eq!(b.router_config().error_format, ErrorFormat::ProblemJson);
+    }
+
+    #[test]
+    fn test_server_error_detail() {
+        let config = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .router_config();
+
+        let e = config.error(CrudError::Backend(String::from("relation \"posts\" does not exist")));
+        assert_eq!((e.status, e.detail.as_str()), (500, "internal server error"));
+
+        let e = config.error(CrudError::Connection(String::from("connection refused at 10.0.0.1:5432")));
+        assert_eq!((e.status, e.detail.as_str()), (503, "the database is unavailable"));
+
+        let e = config.error(CrudError::NotFound);
+        assert_eq!(e.status, 404);
+    }
+
+    #[test]
+    fn test_status_codes() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>();
+        assert_eq!(b.router_config().status_codes, StatusCodes::Rest);
+
+        let b = b.status_codes(StatusCodes::Legacy);
+        assert_eq!(b.router_config().status_codes, StatusCodes::Legacy);
+    }
+
+    #[test]
+    fn test_pagination_mode() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>();
+        assert_eq!(b.router_config().pagination_mode, PaginationMode::Offset);
+
+        let b = b.pagination_mode(PaginationMode::Cursor);
+        assert_eq!(b.router_config().pagination_mode, PaginationMode::Cursor);
+    }
+
+    #[test]
+    fn test_list_format() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>();
+        assert_eq!(b.router_config().list_format, ListFormat::Array);
+
+        let b = b.list_format(ListFormat::Envelope);
+        assert_eq!(b.router_config().list_format, ListFormat::Envelope);
+    }
+
+    #[test]
+    fn test_limits() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>();
+        assert_eq!(b.router_config().default_limit, None);
+        assert_eq!(b.router_config().max_limit, None);
+
+        let b = b.default_limit(20).max_limit(100);
+        assert_eq!(b.router_config().default_limit, Some(20));
+        assert_eq!(b.router_config().max_limit, Some(100));
+        // a greater default limit is capped, in either order
+        let b = b.default_limit(200);
+        assert_eq!(b.router_config().default_limit, Some(100));
+        let b = b.max_limit(50);
+        assert_eq!(b.router_config().default_limit, Some(50));
+    }
+
+    #[test]
+    fn test_filterable_fields() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .filterable_field("title", FieldType::String)
+            .filterable_field("published", FieldType::Bool);
+        assert_eq!(b.router_config().filter_fields, vec![
+            (String::from("title"), FieldType::String),
+            (String::from("published"), FieldType::Bool),
+        ]);
+    }
+
+    #[test]
+    fn test_sortable_fields() {
+        let b = CrudRouterBuilder::new::<TestServer>()
+            .repository::<Repo>()
+            .sortable_field("title", FieldType::String);
+        assert_eq!(b.router_config().sort_fields, vec![(String::from("title"), FieldType::String)]);
     }
 
     #[test]
@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use utoipa::openapi::request_body::RequestBodyBuilder;
 use utoipa::openapi::Tag;
-use crate::{ApiServer, Assignable, Assigned, CrudRouterBuilder, Empty, Pagination, ReadDeleteRepository};
+use crate::{ApiServer, Assignable, Assigned, CrudRouterBuilder, CursorPagination, Empty, ErrorFormat, FieldType, JSON_PATCH_CONTENT_TYPE, ListFormat, MERGE_PATCH_CONTENT_TYPE, Pagination, PaginationMode, ProblemDetails, ReadDeleteRepository, StatusCodes, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER, TOTAL_COUNT_HEADER};
 
 impl utoipa::PartialSchema for Empty {
     fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::Schema> {
@@ -35,13 +35,173 @@ impl<T: utoipa::ToSchema> utoipa::ToSchema for Assigned<T> {
     }
 }
 
+impl FieldType {
+    fn schema_type(&self) -> utoipa::openapi::schema::Type {
+        match self {
+            FieldType::Bool => utoipa::openapi::schema::Type::Boolean,
+            FieldType::Integer => utoipa::openapi::schema::Type::Integer,
+            FieldType::Float => utoipa::openapi::schema::Type::Number,
+            FieldType::String => utoipa::openapi::schema::Type::String,
+        }
+    }
+}
+
+/// A non-negative integer.
+fn count_schema() -> utoipa::openapi::ObjectBuilder {
+    utoipa::openapi::ObjectBuilder::new()
+        .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::Integer))
+        .minimum(Some(0f64))
+}
+
+/// The `limit` query parameter with the default and maximum limits of the router.
+fn limit_parameter(default_limit: Option<u64>, max_limit: Option<u64>) -> utoipa::openapi::path::Parameter {
+    let default_limit = default_limit.or(max_limit);
+    utoipa::openapi::path::ParameterBuilder::from(utoipa::openapi::path::Parameter::new("limit"))
+        .parameter_in(utoipa::openapi::path::ParameterIn::Query)
+        .schema(Some(
+            utoipa::openapi::ObjectBuilder::new()
+                .schema_type(utoipa::openapi::schema::SchemaType::from_iter([
+                    utoipa::openapi::schema::Type::Integer,
+                    utoipa::openapi::schema::Type::Null
+                ]))
+                .format(Some(utoipa::openapi::SchemaFormat::KnownFormat(utoipa::openapi::KnownFormat::Int64)))
+                .minimum(Some(0f64))
+                .maximum(max_limit.map(|max_limit| max_limit as f64))
+                .default(default_limit.map(serde_json::Value::from))
+        ))
+        .required(utoipa::openapi::Required::False)
+        .build()
+}
+
+/// The `ids` query parameter of the batch routes.
+fn ids_parameter(table_name: &str, required: bool) -> utoipa::openapi::path::Parameter {
+    utoipa::openapi::path::ParameterBuilder::from(utoipa::openapi::path::Parameter::new("ids"))
+        .parameter_in(utoipa::openapi::path::ParameterIn::Query)
+        .description(Some(format!("Comma separated {} ids", table_name)))
+        .schema(Some(
+            utoipa::openapi::ObjectBuilder::new()
+                .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::String))
+        ))
+        .required(if required { utoipa::openapi::Required::True } else { utoipa::openapi::Required::False })
+        .build()
+}
+
+/// The schema with all of its properties required, for bodies that replace an item.
+fn all_required<T: utoipa::ToSchema>() -> utoipa::openapi::schema::AllOfBuilder {
+    let mut required = utoipa::openapi::ObjectBuilder::new();
+    if let utoipa::openapi::RefOr::T(utoipa::openapi::Schema::Object(object)) = T::schema() {
+        for property in object.properties.keys() {
+            required = required.required(property);
+        }
+    }
+    utoipa::openapi::schema::AllOfBuilder::new()
+        .item(utoipa::openapi::schema::RefBuilder::new().ref_location_from_schema_name(T::name()))
+        .item(required)
+}
+
+/// A JSON Patch document, a list of operations.
+fn json_patch_schema() -> utoipa::openapi::schema::ArrayBuilder {
+    let string = || utoipa::openapi::ObjectBuilder::new()
+        .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::String));
+    utoipa::openapi::schema::ArrayBuilder::new()
+        .items(
+            utoipa::openapi::ObjectBuilder::new()
+                .property("op", string().enum_values(Some(["add", "remove", "replace", "move", "copy", "test"])))
+                .required("op")
+                .property("path", string().description(Some("JSON Pointer to the field")))
+                .required("path")
+                .property("from", string().description(Some("JSON Pointer to the source of move and copy")))
+                .property(
+                    "value",
+                    utoipa::openapi::ObjectBuilder::new()
+                        .schema_type(utoipa::openapi::schema::SchemaType::AnyValue)
+                        .description(Some("Value of add, replace and test"))
+                )
+        )
+}
+
+/// Query parameters of the list, count and delete routes for one filterable field, one per operator.
+fn filter_parameters(field: &str, field_type: FieldType) -> Vec<utoipa::openapi::path::Parameter> {
+    let parameter = |name: String, description: String, schema_type: utoipa::openapi::schema::Type| {
+        utoipa::openapi::path::ParameterBuilder::from(utoipa::openapi::path::Parameter::new(name))
+            .parameter_in(utoipa::openapi::path::ParameterIn::Query)
+            .description(Some(description))
+            .schema(Some(
+                utoipa::openapi::ObjectBuilder::new()
+                    .schema_type(utoipa::openapi::schema::SchemaType::new(schema_type))
+            ))
+            .required(utoipa::openapi::Required::False)
+            .build()
+    };
+
+    let mut parameters = vec![parameter(field.to_string(), format!("{} equals the value", field), field_type.schema_type())];
+    for operator in field_type.operators() {
+        let name = format!("{}__{}", field, operator);
+        parameters.push(match *operator {
+            "ne" => parameter(name, format!("{} does not equal the value", field), field_type.schema_type()),
+            "lt" => parameter(name, format!("{} is less than the value", field), field_type.schema_type()),
+            "gt" => parameter(name, format!("{} is greater than the value", field), field_type.schema_type()),
+            "in" => parameter(name, format!("{} equals one of the comma separated values", field), utoipa::openapi::schema::Type::String),
+            "contains" => parameter(name, format!("{} contains the value", field), utoipa::openapi::schema::Type::String),
+            _ => parameter(name, format!("{} is null, or is not null when false", field), utoipa::openapi::schema::Type::Boolean),
+        });
+    }
+    parameters
+}
+
+/// Documents the `ETag` header and the conditional requests of the item routes.
+fn conditional_requests(item_path: &mut utoipa::openapi::path::PathItem, precondition_failed: utoipa::openapi::Response) {
+    let string = || utoipa::openapi::ObjectBuilder::new()
+        .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::String));
+    let header_parameter = |name: &str, description: &str| {
+        utoipa::openapi::path::ParameterBuilder::from(utoipa::openapi::path::Parameter::new(name))
+            .parameter_in(utoipa::openapi::path::ParameterIn::Header)
+            .description(Some(description))
+            .schema(Some(string()))
+            .required(utoipa::openapi::Required::False)
+            .build()
+    };
+    let etag = utoipa::openapi::HeaderBuilder::new()
+        .schema(string())
+        .description(Some("Current version of the item"))
+        .build();
+    let add_etag = |operation: &mut utoipa::openapi::path::Operation| {
+        if let Some(utoipa::openapi::RefOr::T(response)) = operation.responses.responses.get_mut("200") {
+            response.headers.insert(String::from("ETag"), etag.clone());
+        }
+    };
+
+    if let Some(get) = item_path.get.as_mut() {
+        add_etag(get);
+        get.parameters.get_or_insert_with(Vec::new)
+            .push(header_parameter("If-None-Match", "Answers with 304 if one of the comma separated ETags is current"));
+        get.responses.responses.insert(
+            String::from("304"),
+            utoipa::openapi::ResponseBuilder::new()
+                .description("The item has not been modified")
+                .header("ETag", etag.clone())
+                .build()
+                .into()
+        );
+    }
+    for operation in [item_path.put.as_mut(), item_path.patch.as_mut()].into_iter().flatten() {
+        add_etag(operation);
+    }
+    for operation in [item_path.put.as_mut(), item_path.patch.as_mut(), item_path.delete.as_mut()].into_iter().flatten() {
+        operation.parameters.get_or_insert_with(Vec::new)
+            .push(header_parameter("If-Match", "Answers with 412 unless one of the comma separated ETags is current"));
+        operation.responses.responses.insert(String::from("412"), precondition_failed.clone().into());
+    }
+}
+
 impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Schema: utoipa::ToSchema, PrimaryKeyType, CreateSchema: Assignable + utoipa::ToSchema, UpdateSchema: Assignable + utoipa::ToSchema> CrudRouterBuilder<'_, Assigned<Server>, Repo, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, UpdateSchema> {
     pub fn build_openapi(self, openapi: &mut utoipa::openapi::OpenApi) -> Self {
         let table_name = Repo::get_table_name();
-        let tag = self.tag.or(Some(&table_name)).unwrap();
+        let tag = self.tag.unwrap_or(&table_name);
         let prefix = self.get_prefix();
-        let path = Server::get_path(&prefix);
+        let path = Server::get_path(prefix);
         let id_path = format!("/{}/{{id}}", &prefix);
+        let bulk_path = Server::get_bulk_path(prefix);
         let mut openapi_paths = utoipa::openapi::path::Paths::new();
         let mut openapi_schemas = Vec::<(String, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>)>::new();
 
@@ -62,13 +222,125 @@ impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sche
             .schema(Some(
                 single_item_ref.clone()
             )).build();
+        let error_content = match self.error_format {
+            ErrorFormat::PlainText => utoipa::openapi::content::ContentBuilder::new()
+                .schema(Some(
+                    utoipa::openapi::ObjectBuilder::new()
+                        .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::String))
+                )).build(),
+            ErrorFormat::ProblemJson => {
+                openapi_schemas.push((<ProblemDetails as utoipa::ToSchema>::name().to_string(), <ProblemDetails as utoipa::PartialSchema>::schema()));
+                utoipa::openapi::content::ContentBuilder::new()
+                    .schema(Some(
+                        utoipa::openapi::schema::RefBuilder::new()
+                            .ref_location_from_schema_name(<ProblemDetails as utoipa::ToSchema>::name())
+                            .build()
+                    )).build()
+            }
+        };
+        let error_content_type = match self.error_format {
+            ErrorFormat::PlainText => "text/plain",
+            ErrorFormat::ProblemJson => "application/problem+json",
+        };
+        let error_response = utoipa::openapi::ResponseBuilder::new()
+            .description("Request failed")
+            .content(error_content_type, error_content.clone())
+            .build();
+        let not_found_response = utoipa::openapi::ResponseBuilder::new()
+            .description(format!("{} with given id is not found", table_name))
+            .content(error_content_type, error_content.clone())
+            .build();
 
         if !self.list_items_route_disabled {
-            let list_of_items_response = utoipa::openapi::content::ContentBuilder::new()
-                .schema(Some(
-                    utoipa::openapi::schema::ArrayBuilder::new()
-                        .items(single_item_ref.clone()))
-                ).build();
+            let list_of_items = utoipa::openapi::schema::ArrayBuilder::new()
+                .items(single_item_ref.clone());
+            let (list_of_items_response, mut list_parameters) = match self.pagination_mode {
+                PaginationMode::Offset if self.list_format == ListFormat::Envelope => (
+                    utoipa::openapi::content::ContentBuilder::new()
+                        .schema(Some(
+                            utoipa::openapi::ObjectBuilder::new()
+                                .property("items", list_of_items)
+                                .required("items")
+                                .property("total", count_schema().description(Some("Number of items matching the filters, on all pages")))
+                                .required("total")
+                                .property("skip", count_schema())
+                                .required("skip")
+                                .property(
+                                    "limit",
+                                    utoipa::openapi::ObjectBuilder::new()
+                                        .schema_type(utoipa::openapi::schema::SchemaType::from_iter([
+                                            utoipa::openapi::schema::Type::Integer,
+                                            utoipa::openapi::schema::Type::Null
+                                        ]))
+                                        .minimum(Some(0f64))
+                                )
+                        ))
+                        .build(),
+                    <Pagination as utoipa::IntoParams>::into_params(|| Some(utoipa::openapi::path::ParameterIn::Query))
+                ),
+                PaginationMode::Offset => (
+                    utoipa::openapi::content::ContentBuilder::new()
+                        .schema(Some(list_of_items))
+                        .build(),
+                    <Pagination as utoipa::IntoParams>::into_params(|| Some(utoipa::openapi::path::ParameterIn::Query))
+                ),
+                PaginationMode::Cursor => (
+                    utoipa::openapi::content::ContentBuilder::new()
+                        .schema(Some(
+                            utoipa::openapi::ObjectBuilder::new()
+                                .property("items", list_of_items)
+                                .required("items")
+                                .property(
+                                    "next_cursor",
+                                    utoipa::openapi::ObjectBuilder::new()
+                                        .schema_type(utoipa::openapi::schema::SchemaType::from_iter([
+                                            utoipa::openapi::schema::Type::String,
+                                            utoipa::openapi::schema::Type::Null
+                                        ]))
+                                        .description(Some("Cursor of the next page, null on the last page"))
+                                )
+                        ))
+                        .build(),
+                    <CursorPagination as utoipa::IntoParams>::into_params(|| Some(utoipa::openapi::path::ParameterIn::Query))
+                ),
+            };
+            if self.default_limit.is_some() || self.max_limit.is_some() {
+                for parameter in list_parameters.iter_mut().filter(|parameter| parameter.name == "limit") {
+                    *parameter = limit_parameter(self.default_limit, self.max_limit);
+                }
+            }
+            for (field, field_type) in &self.filter_fields {
+                list_parameters.extend(filter_parameters(field, *field_type));
+            }
+            if !self.sort_fields.is_empty() {
+                list_parameters.push(
+                    utoipa::openapi::path::ParameterBuilder::from(utoipa::openapi::path::Parameter::new("sort"))
+                        .parameter_in(utoipa::openapi::path::ParameterIn::Query)
+                        .description(Some(format!(
+                            "Comma separated fields to sort by, prefixed with `-` for descending order. Sortable fields: {}",
+                            self.sort_fields.iter().map(|(field, _)| *field).collect::<Vec<_>>().join(", ")
+                        )))
+                        .schema(Some(
+                            utoipa::openapi::ObjectBuilder::new()
+                                .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::String))
+                        ))
+                        .required(utoipa::openapi::Required::False)
+                        .build()
+                );
+            }
+
+            let mut list_response = utoipa::openapi::ResponseBuilder::new()
+                .description(format!("All {} listed successfully", table_name))
+                .content("application/json", list_of_items_response);
+            if self.pagination_mode == PaginationMode::Offset && self.list_format == ListFormat::TotalCountHeader {
+                list_response = list_response.header(
+                    TOTAL_COUNT_HEADER,
+                    utoipa::openapi::HeaderBuilder::new()
+                        .schema(count_schema())
+                        .description(Some("Number of items matching the filters, on all pages"))
+                        .build()
+                );
+            }
 
             openapi_paths.add_path_operation(
                 &path,
@@ -77,28 +349,15 @@ impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sche
                     .tag(tag)
                     .description(Some(format!("Lists all {}", table_name)))
                     .operation_id(Some(format!("list_all_{}", table_name)))
-                    .parameters(Some(<Pagination as utoipa::IntoParams>::into_params(|| Some(utoipa::openapi::path::ParameterIn::Query))))
-                    .response(
-                        "200",
-                        utoipa::openapi::ResponseBuilder::new()
-                            .description(format!("All {} listed successfully", table_name))
-                            .content("application/json", list_of_items_response)
-                            .build()
-                    )
+                    .parameters(Some(list_parameters))
+                    .response("200", list_response.build())
+                    .response("default", error_response.clone())
             );
             openapi_schemas.push((<Schema as utoipa::ToSchema>::name().to_string(), <Schema as utoipa::PartialSchema>::schema()));
             <Schema as utoipa::ToSchema>::schemas(&mut openapi_schemas);
         }
 
         if !self.get_item_route_disabled {
-            let optional_item_response = utoipa::openapi::content::ContentBuilder::new()
-                .schema(Some(utoipa::openapi::schema::OneOfBuilder::new()
-                    .item(
-                        utoipa::openapi::schema::ObjectBuilder::new()
-                            .schema_type(utoipa::openapi::schema::Type::Null)
-                    )
-                    .item(single_item_ref))).build();
-
             openapi_paths.add_path_operation(
                 &id_path,
                 vec![utoipa::openapi::HttpMethod::Get],
@@ -112,38 +371,124 @@ impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sche
                         utoipa::openapi::ResponseBuilder::new()
                             .description(format!("One {} is fetched successfully", table_name))
                             .content(
-                                "application/json", optional_item_response
+                                "application/json", single_item_response.clone()
                             ).build()
                     )
+                    .response("404", not_found_response.clone())
+                    .response("default", error_response.clone())
             );
             openapi_schemas.push((<Schema as utoipa::ToSchema>::name().to_string(), <Schema as utoipa::PartialSchema>::schema()));
             <Schema as utoipa::ToSchema>::schemas(&mut openapi_schemas);
         }
 
-        if !self.delete_all_items_route_disabled {
-            let integer_response = utoipa::openapi::content::ContentBuilder::new()
-                .schema(Some(
-                    utoipa::openapi::ObjectBuilder::new()
-                        .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::Integer))
-                        .minimum(Some(0f64))
-                )).build();
+        if !self.item_exists_route_disabled {
+            openapi_paths.add_path_operation(
+                &id_path,
+                vec![utoipa::openapi::HttpMethod::Head],
+                utoipa::openapi::path::OperationBuilder::new()
+                    .tag(tag)
+                    .description(Some(format!("Checks if one {} exists without loading it", table_name)))
+                    .operation_id(Some(format!("exists_{}", table_name)))
+                    .parameter(id_parameter.clone())
+                    .response(
+                        "200",
+                        utoipa::openapi::ResponseBuilder::new()
+                            .description(format!("{} with given id exists", table_name))
+                            .build()
+                    )
+                    .response("404", not_found_response.clone())
+                    .response("default", error_response.clone())
+            );
+        }
+
+        if !self.count_items_route_disabled {
+            let mut operation = utoipa::openapi::path::OperationBuilder::new()
+                .tag(tag)
+                .description(Some(format!("Counts the {} matching the filters", table_name)))
+                .operation_id(Some(format!("count_{}", table_name)));
+            for (field, field_type) in &self.filter_fields {
+                operation = operation.parameters(Some(filter_parameters(field, *field_type)));
+            }
+            openapi_paths.add_path_operation(
+                &path,
+                vec![utoipa::openapi::HttpMethod::Head],
+                operation
+                    .response(
+                        "200",
+                        utoipa::openapi::ResponseBuilder::new()
+                            .description(format!("The {} are counted successfully", table_name))
+                            .header(
+                                TOTAL_COUNT_HEADER,
+                                utoipa::openapi::HeaderBuilder::new()
+                                    .schema(count_schema())
+                                    .description(Some("Number of items matching the filters"))
+                                    .build()
+                            )
+                            .build()
+                    )
+                    .response("default", error_response.clone())
+            );
+        }
+
+        let integer_response = utoipa::openapi::content::ContentBuilder::new()
+            .schema(Some(count_schema()))
+            .build();
+
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            let mut operation = utoipa::openapi::path::OperationBuilder::new()
+                .tag(tag);
+            if self.delete_all_items_route_disabled {
+                operation = operation
+                    .description(Some(format!("Deletes the {} with the given ids in one transaction", table_name)))
+                    .operation_id(Some(format!("delete_many_{}", table_name)));
+            } else if self.delete_items_route_disabled {
+                operation = operation
+                    .description(Some(format!("Deletes the {} matching the filters, or all of them when confirmed", table_name)))
+                    .operation_id(Some(format!("delete_all_{}", table_name)));
+            } else {
+                operation = operation
+                    .description(Some(format!("Deletes the {} with the given ids in one transaction, or the ones matching the filters, or all of them when confirmed", table_name)))
+                    .operation_id(Some(format!("delete_all_{}", table_name)));
+            }
+            if !self.delete_items_route_disabled {
+                operation = operation.parameter(ids_parameter(&table_name, self.delete_all_items_route_disabled));
+            }
+            if !self.delete_all_items_route_disabled {
+                for (field, field_type) in &self.filter_fields {
+                    operation = operation.parameters(Some(filter_parameters(field, *field_type)));
+                }
+                operation = operation.parameter(
+                    utoipa::openapi::path::ParameterBuilder::from(utoipa::openapi::path::Parameter::new("confirm"))
+                        .parameter_in(utoipa::openapi::path::ParameterIn::Query)
+                        .description(Some(format!("Has to be true to delete all {} without filters", table_name)))
+                        .schema(Some(
+                            utoipa::openapi::ObjectBuilder::new()
+                                .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::Boolean))
+                        ))
+                        .required(utoipa::openapi::Required::False)
+                        .build()
+                );
+            }
 
             openapi_paths.add_path_operation(
                 &path,
                 vec![utoipa::openapi::HttpMethod::Delete],
-                utoipa::openapi::path::OperationBuilder::new()
-                    .tag(tag)
-                    .description(Some(format!("Deletes all {}", table_name)))
-                    .operation_id(Some(format!("delete_all_{}", table_name)))
+                operation
                     .response(
                         "200",
                         utoipa::openapi::ResponseBuilder::new()
-                            .description(format!("All {} deleted successfully", table_name))
-                            .content("text/plain",integer_response).build()
+                            .description(format!("Number of deleted {}", table_name))
+                            .content("text/plain", integer_response.clone()).build()
                     )
+                    .response("default", error_response.clone())
             );
         }
 
+        let (created_status, deleted_status) = match self.status_codes {
+            StatusCodes::Rest => ("201", "204"),
+            StatusCodes::Legacy => ("200", "200"),
+        };
+
         if !self.delete_item_route_disabled {
             openapi_paths.add_path_operation(
                 &id_path,
@@ -154,11 +499,13 @@ impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sche
                     .operation_id(Some(format!("delete_{}", table_name)))
                     .parameter(id_parameter.clone())
                     .response(
-                        "200",
+                        deleted_status,
                         utoipa::openapi::ResponseBuilder::new()
                             .description(format!("One {} is deleted successfully", table_name))
                             .build()
                     )
+                    .response("404", not_found_response.clone())
+                    .response("default", error_response.clone())
             );
         }
 
@@ -169,30 +516,109 @@ impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sche
                         .ref_location_from_schema_name(<CreateSchema as utoipa::ToSchema>::name())
                         .build()
                 )).build();
+            let mut create_item_response = utoipa::openapi::ResponseBuilder::new()
+                .description(format!("One {} is created successfully", table_name))
+                .content("application/json", single_item_response.clone());
+            if self.status_codes == StatusCodes::Rest {
+                create_item_response = create_item_response.header(
+                    "Location",
+                    utoipa::openapi::HeaderBuilder::new()
+                        .schema(
+                            utoipa::openapi::ObjectBuilder::new()
+                                .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::String))
+                        )
+                        .description(Some(format!("Path of the created {}", table_name)))
+                        .build()
+                );
+            }
+            let mut create_item_operation = utoipa::openapi::path::OperationBuilder::new()
+                .tag(tag)
+                .description(Some(format!("Creates {}", table_name)))
+                .operation_id(Some(format!("create_{}", table_name)));
+            if self.idempotency.is_some() {
+                let string = || utoipa::openapi::ObjectBuilder::new()
+                    .schema_type(utoipa::openapi::schema::SchemaType::new(utoipa::openapi::schema::Type::String));
+                create_item_operation = create_item_operation.parameter(
+                    utoipa::openapi::path::ParameterBuilder::from(utoipa::openapi::path::Parameter::new(IDEMPOTENCY_KEY_HEADER))
+                        .parameter_in(utoipa::openapi::path::ParameterIn::Header)
+                        .description(Some("Retries with the same key are answered with the response of the first request"))
+                        .schema(Some(string()))
+                        .required(utoipa::openapi::Required::False)
+                );
+                create_item_response = create_item_response.header(
+                    IDEMPOTENT_REPLAYED_HEADER,
+                    utoipa::openapi::HeaderBuilder::new()
+                        .schema(string())
+                        .description(Some("`true` if the response is the one of an earlier request with the same key"))
+                        .build()
+                );
+                create_item_operation = create_item_operation.response(
+                    "409",
+                    utoipa::openapi::ResponseBuilder::new()
+                        .description("A request with the same key is still running")
+                        .content(error_content_type, error_content.clone())
+                        .build()
+                );
+            }
 
             openapi_paths.add_path_operation(
                 &path,
                 vec![utoipa::openapi::HttpMethod::Post],
+                create_item_operation
+                    .request_body(Some(
+                        RequestBodyBuilder::new()
+                            .content("application/json", create_item_request)
+                            .required(Some(utoipa::openapi::Required::True))
+                            .build()
+                    ))
+                    .response(created_status, create_item_response.build())
+                    .response("default", error_response.clone())
+            );
+            openapi_schemas.push((<Schema as utoipa::ToSchema>::name().to_string(), <Schema as utoipa::PartialSchema>::schema()));
+            openapi_schemas.push((<CreateSchema as utoipa::ToSchema>::name().to_string(), <CreateSchema as utoipa::PartialSchema>::schema()));
+            <Schema as utoipa::ToSchema>::schemas(&mut openapi_schemas);
+            <CreateSchema as utoipa::ToSchema>::schemas(&mut openapi_schemas);
+        }
+
+        if !self.create_items_route_disabled && CreateSchema::IS_ASSIGNED {
+            let create_items_request = utoipa::openapi::content::ContentBuilder::new()
+                .schema(Some(
+                    utoipa::openapi::schema::ArrayBuilder::new()
+                        .items(
+                            utoipa::openapi::schema::RefBuilder::new()
+                                .ref_location_from_schema_name(<CreateSchema as utoipa::ToSchema>::name())
+                                .build()
+                        )
+                )).build();
+
+            openapi_paths.add_path_operation(
+                &bulk_path,
+                vec![utoipa::openapi::HttpMethod::Post],
                 utoipa::openapi::path::OperationBuilder::new()
                     .tag(tag)
-                    .description(Some(format!("Creates {}", table_name)))
-                    .operation_id(Some(format!("create_{}", table_name)))
+                    .description(Some(format!("Creates a list of {}, if one of them fails none are created", table_name)))
+                    .operation_id(Some(format!("create_many_{}", table_name)))
                     .request_body(Some(
                         RequestBodyBuilder::new()
-                            .content("application/json", create_item_request)
+                            .content("application/json", create_items_request)
                             .required(Some(utoipa::openapi::Required::True))
                             .build()
                     ))
                     .response(
-                        "200",
+                        created_status,
                         utoipa::openapi::ResponseBuilder::new()
-                            .description(format!("One {} is created successfully", table_name))
+                            .description(format!("All {} are created successfully", table_name))
                             .content(
                                 "application/json",
-                                single_item_response.clone()
+                                utoipa::openapi::content::ContentBuilder::new()
+                                    .schema(Some(
+                                        utoipa::openapi::schema::ArrayBuilder::new()
+                                            .items(single_item_ref.clone())
+                                    )).build()
                             )
                             .build()
                     )
+                    .response("default", error_response.clone())
             );
             openapi_schemas.push((<Schema as utoipa::ToSchema>::name().to_string(), <Schema as utoipa::PartialSchema>::schema()));
             openapi_schemas.push((<CreateSchema as utoipa::ToSchema>::name().to_string(), <CreateSchema as utoipa::PartialSchema>::schema()));
@@ -202,6 +628,40 @@ impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sche
 
         if !self.update_item_route_disabled && UpdateSchema::IS_ASSIGNED {
             let update_item_request = utoipa::openapi::content::ContentBuilder::new()
+                .schema(Some(all_required::<UpdateSchema>()))
+                .build();
+
+            openapi_paths.add_path_operation(
+                &id_path,
+                vec![utoipa::openapi::HttpMethod::Put],
+                utoipa::openapi::path::OperationBuilder::new()
+                    .tag(tag)
+                    .description(Some(format!("Replaces {}, every field has to be set", table_name)))
+                    .operation_id(Some(format!("update_{}", table_name)))
+                    .parameter(id_parameter.clone())
+                    .request_body(Some(
+                        RequestBodyBuilder::new()
+                            .content("application/json", update_item_request)
+                            .required(Some(utoipa::openapi::Required::True))
+                            .build()
+                    ))
+                    .response(
+                        "200",
+                        utoipa::openapi::ResponseBuilder::new()
+                            .description(format!("One {} is updated successfully", table_name))
+                            .content(
+                                "application/json",
+                                single_item_response.clone()
+                            )
+                            .build()
+                    )
+                    .response("404", not_found_response.clone())
+                    .response("default", error_response.clone())
+            );
+        }
+
+        if !self.update_items_route_disabled && UpdateSchema::IS_ASSIGNED {
+            let update_items_request = utoipa::openapi::content::ContentBuilder::new()
                 .schema(Some(
                     utoipa::openapi::schema::RefBuilder::new()
                         .ref_location_from_schema_name(<UpdateSchema as utoipa::ToSchema>::name())
@@ -209,19 +669,76 @@ impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sche
                 )).build();
 
             openapi_paths.add_path_operation(
-                &id_path,
-                vec![utoipa::openapi::HttpMethod::Put],
+                &path,
+                vec![utoipa::openapi::HttpMethod::Patch],
                 utoipa::openapi::path::OperationBuilder::new()
                     .tag(tag)
-                    .description(Some(format!("Updates {}", table_name)))
-                    .operation_id(Some(format!("update_{}", table_name)))
-                    .parameter(id_parameter)
+                    .description(Some(format!("Updates the given fields of the {} with the given ids in one transaction", table_name)))
+                    .operation_id(Some(format!("update_many_{}", table_name)))
+                    .parameter(ids_parameter(&table_name, true))
                     .request_body(Some(
                         RequestBodyBuilder::new()
-                            .content("application/json", update_item_request)
+                            .content("application/json", update_items_request)
                             .required(Some(utoipa::openapi::Required::True))
                             .build()
                     ))
+                    .response(
+                        "200",
+                        utoipa::openapi::ResponseBuilder::new()
+                            .description(format!("Number of updated {}", table_name))
+                            .content("text/plain", integer_response.clone())
+                            .build()
+                    )
+                    .response("default", error_response.clone())
+            );
+        }
+
+        if (!self.patch_item_route_disabled || self.json_patch_enabled) && UpdateSchema::IS_ASSIGNED {
+            let mut patch_item_request = RequestBodyBuilder::new()
+                .required(Some(utoipa::openapi::Required::True));
+            let mut formats = Vec::new();
+            if !self.patch_item_route_disabled {
+                patch_item_request = patch_item_request.content(
+                    MERGE_PATCH_CONTENT_TYPE,
+                    utoipa::openapi::content::ContentBuilder::new()
+                        .schema(Some(
+                            utoipa::openapi::schema::RefBuilder::new()
+                                .ref_location_from_schema_name(<UpdateSchema as utoipa::ToSchema>::name())
+                                .build()
+                        )).build()
+                );
+                formats.push("JSON Merge Patch");
+            }
+            if self.json_patch_enabled {
+                patch_item_request = patch_item_request.content(
+                    JSON_PATCH_CONTENT_TYPE,
+                    utoipa::openapi::content::ContentBuilder::new()
+                        .schema(Some(json_patch_schema()))
+                        .build()
+                );
+                formats.push("JSON Patch");
+            }
+
+            let mut operation = utoipa::openapi::path::OperationBuilder::new()
+                .tag(tag)
+                .description(Some(format!("Updates the given fields of {} with a {}", table_name, formats.join(" or "))))
+                .operation_id(Some(format!("patch_{}", table_name)))
+                .parameter(id_parameter)
+                .request_body(Some(patch_item_request.build()));
+            if self.json_patch_enabled {
+                operation = operation.response(
+                    "409",
+                    utoipa::openapi::ResponseBuilder::new()
+                        .description("A test operation of the JSON Patch failed")
+                        .content(error_content_type, error_content.clone())
+                        .build()
+                );
+            }
+
+            openapi_paths.add_path_operation(
+                &id_path,
+                vec![utoipa::openapi::HttpMethod::Patch],
+                operation
                     .response(
                         "200",
                         utoipa::openapi::ResponseBuilder::new()
@@ -232,13 +749,30 @@ impl<Server: ApiServer, Repo: ReadDeleteRepository<Schema, PrimaryKeyType>, Sche
                             )
                             .build()
                     )
+                    .response("404", not_found_response)
+                    .response("default", error_response.clone())
             );
+        }
+
+        if (!self.update_item_route_disabled || !self.update_items_route_disabled || !self.patch_item_route_disabled || self.json_patch_enabled) && UpdateSchema::IS_ASSIGNED {
             openapi_schemas.push((<Schema as utoipa::ToSchema>::name().to_string(), <Schema as utoipa::PartialSchema>::schema()));
             openapi_schemas.push((<UpdateSchema as utoipa::ToSchema>::name().to_string(), <UpdateSchema as utoipa::PartialSchema>::schema()));
             <Schema as utoipa::ToSchema>::schemas(&mut openapi_schemas);
             <UpdateSchema as utoipa::ToSchema>::schemas(&mut openapi_schemas);
         }
 
+        if self.etags_enabled {
+            if let Some(item_path) = openapi_paths.paths.get_mut(&id_path) {
+                conditional_requests(
+                    item_path,
+                    utoipa::openapi::ResponseBuilder::new()
+                        .description(format!("{} has been modified since the ETag in If-Match", table_name))
+                        .content(error_content_type, error_content)
+                        .build()
+                );
+            }
+        }
+
         openapi.paths.paths.extend(openapi_paths.paths);
         let tags = openapi
             .tags
@@ -0,0 +1,104 @@
+use serde::Serialize;
+use crate::{Filter, Pagination, ReadDeleteRepository, RouterConfig, Sort};
+use crate::repositories::CrudError;
+
+/// Name of the header carrying the total count in [ListFormat::TotalCountHeader].
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// Shape of the list route response in [crate::PaginationMode::Offset].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListFormat {
+    /// A JSON array of the items.
+    #[default]
+    Array,
+    /// An [OffsetPage] with the items and the total count.
+    Envelope,
+    /// A JSON array of the items and the total count in the `X-Total-Count` header.
+    TotalCountHeader,
+}
+
+/// Response of the list route in [ListFormat::Envelope].
+#[derive(Serialize)]
+pub struct OffsetPage<Schema> {
+    pub items: Vec<Schema>,
+    /// Number of items matching the filters, on all pages.
+    pub total: u64,
+    pub skip: u64,
+    pub limit: Option<u64>,
+}
+
+impl<Schema> OffsetPage<Schema> {
+    /// Loads the page and counts the items matching the filters.
+    pub(crate) async fn load<Repo, PrimaryKeyType>(repository: &Repo, pagination: Pagination, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Self, CrudError>
+    where
+        Repo: ReadDeleteRepository<Schema, PrimaryKeyType>,
+    {
+        let skip = pagination.skip.unwrap_or(0);
+        let limit = pagination.limit;
+        let total = repository.count_items(filters.clone()).await?;
+        let items = repository.list_items(pagination, filters, sort).await?;
+
+        Ok(Self {
+            items,
+            total,
+            skip,
+            limit,
+        })
+    }
+}
+
+impl RouterConfig {
+    /// The limit of a list request, falls back to the default limit and then to the maximum.
+    pub(crate) fn limit(&self, limit: Option<u64>) -> Result<Option<u64>, String> {
+        let limit = limit.or(self.default_limit).or(self.max_limit);
+        match (limit, self.max_limit) {
+            (Some(limit), Some(max_limit)) if limit > max_limit => Err(format!("`limit` can't be greater than {}", max_limit)),
+            // databases take signed limits, and cursor pages load one more item
+            (Some(limit), _) if limit >= i64::MAX as u64 => Err(format!("`limit` must be less than {}", i64::MAX)),
+            _ => Ok(limit),
+        }
+    }
+
+    /// The pagination of an offset list request with its limit, see [RouterConfig::limit].
+    pub(crate) fn pagination(&self, pagination: Pagination) -> Result<Pagination, String> {
+        match pagination.skip {
+            Some(skip) if skip > i64::MAX as u64 => Err(format!("`skip` can't be greater than {}", i64::MAX)),
+            skip => Ok(Pagination {
+                skip,
+                limit: self.limit(pagination.limit)?,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(default_limit: Option<u64>, max_limit: Option<u64>) -> RouterConfig {
+        RouterConfig {
+            default_limit,
+            max_limit,
+            ..RouterConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_limit() {
+        assert_eq!(config(None, None).limit(None).unwrap(), None);
+        assert_eq!(config(None, None).limit(Some(1000)).unwrap(), Some(1000));
+        assert_eq!(config(Some(20), None).limit(None).unwrap(), Some(20));
+        assert_eq!(config(Some(20), Some(100)).limit(None).unwrap(), Some(20));
+        assert_eq!(config(None, Some(100)).limit(None).unwrap(), Some(100));
+        assert_eq!(config(Some(20), Some(100)).limit(Some(100)).unwrap(), Some(100));
+        assert!(config(Some(20), Some(100)).limit(Some(101)).is_err());
+        assert!(config(None, None).limit(Some(u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_pagination() {
+        let pagination = config(Some(20), None).pagination(Pagination { skip: Some(40), limit: None }).unwrap();
+        assert_eq!((pagination.skip, pagination.limit), (Some(40), Some(20)));
+        assert!(config(None, None).pagination(Pagination { skip: Some(u64::MAX), limit: None }).is_err());
+    }
+}
@@ -0,0 +1,351 @@
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use json_patch::{Patch, PatchErrorKind};
+use crate::{ReadDeleteRepository, RouterConfig, UpdateRepository};
+use crate::repositories::{required_version, CrudError};
+
+/// Content type of the PATCH route.
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+
+/// Content type of the PATCH route for JSON Patch documents, see [crate::CrudRouterBuilder::enable_json_patch].
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+/// Format of the body of a PATCH request.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum PatchFormat {
+    MergePatch,
+    JsonPatch,
+}
+
+impl RouterConfig {
+    /// Picks the format of a PATCH request by its content type, `None` if the router doesn't accept it.
+    pub(crate) fn patch_format(&self, content_type: Option<&str>) -> Option<PatchFormat> {
+        let mime = content_type
+            .and_then(|content_type| content_type.split(';').next())
+            .unwrap_or_default()
+            .trim();
+        if mime.eq_ignore_ascii_case(JSON_PATCH_CONTENT_TYPE) {
+            self.json_patch.then_some(PatchFormat::JsonPatch)
+        } else {
+            self.merge_patch.then_some(PatchFormat::MergePatch)
+        }
+    }
+}
+
+/// Applies a JSON Merge Patch (RFC 7396) to `target`, null members of the patch remove the field.
+pub(crate) fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (field, value) in patch {
+            if value.is_null() {
+                target.remove(&field);
+            } else {
+                merge_patch(target.entry(field).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Applies a JSON Patch (RFC 6902) to `target`, a failed `test` operation is a [CrudError::Conflict].
+pub(crate) fn json_patch(target: &mut Value, patch: &Patch) -> Result<(), CrudError> {
+    json_patch::patch(target, patch).map_err(|error| match error.kind {
+        PatchErrorKind::TestFailed => CrudError::Conflict(error.to_string()),
+        _ => CrudError::Validation(error.to_string()),
+    })
+}
+
+/// Collects the field names a `Deserialize` implementation asks for and fails on purpose.
+struct FieldNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], _visitor: V) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+        Err(de::Error::custom("fields collected"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Names of the fields of a struct deriving `Deserialize`, `None` for other types and for structs
+/// that serde reads as a map, like those with a `#[serde(flatten)]` field.
+pub(crate) fn field_names<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut fields = None;
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// Reads the body of the PUT route, which replaces the item and so has to set every field,
+/// `null` included. Whether `null` clears the column depends on the repository, Diesel only clears
+/// `Option<Option<T>>` fields read with [nullable]. A missing version fails with
+/// [CrudError::PreconditionRequired] like in the repository.
+///
+/// The fields of an update schema with `#[serde(flatten)]` are unknown, so its completeness isn't checked.
+pub(crate) fn replacement<UpdateSchema: DeserializeOwned>(item: Value, version_column: Option<&str>) -> Result<UpdateSchema, CrudError> {
+    let Value::Object(fields) = &item else {
+        return Err(CrudError::Validation("expected a JSON object".to_string()));
+    };
+    let Some(field_names) = field_names::<UpdateSchema>() else {
+        return Ok(serde_json::from_value(item)?);
+    };
+    let missing: Vec<_> = field_names.iter()
+        .filter(|field| !fields.contains_key(**field) && Some(**field) != version_column)
+        .map(|field| format!("`{}`", field))
+        .collect();
+    if !missing.is_empty() {
+        return Err(CrudError::Validation(format!("{} must be set to replace the item, use PATCH to change some fields", missing.join(", "))));
+    }
+    if let Some(version_column) = version_column.filter(|version_column| field_names.contains(version_column)) {
+        required_version(fields.get(version_column), version_column)?;
+    }
+
+    Ok(serde_json::from_value(item)?)
+}
+
+/// Applies `patch` to the JSON of the stored item and writes the fields it changed back with
+/// [UpdateRepository::update_item], a removed field is written as `null`. Fields that are not in
+/// `UpdateSchema` are ignored, so every field of it has to be optional, and a field that the update
+/// schema can't set to `null` can't be removed either.
+///
+/// Unchanged fields are left out so that they can't overwrite a concurrent update, except for the
+/// version column of the repository, which makes the update fail with [CrudError::Conflict] if
+/// the item changed since it was read, or since it had `version` when that is given. Without a
+/// version column the read and the write aren't atomic, so a JSON Patch `test` is only advisory.
+pub(crate) async fn patch_item<Repo, Schema, PrimaryKeyType, UpdateSchema>(repository: &Repo, id: PrimaryKeyType, version: Option<i64>, patch: impl FnOnce(&mut Value) -> Result<(), CrudError>) -> Result<Schema, CrudError>
+where
+    Repo: ReadDeleteRepository<Schema, PrimaryKeyType> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema>,
+    Schema: Serialize,
+    PrimaryKeyType: Clone,
+    UpdateSchema: Serialize + DeserializeOwned,
+{
+    let item = repository.get_item(id.clone()).await?.ok_or(CrudError::NotFound)?;
+    let Value::Object(stored) = serde_json::to_value(&item)? else {
+        return Err(CrudError::Backend("stored item is not a JSON object".to_string()));
+    };
+    let stored_version = repository.get_version_column().and_then(|column| stored.get(column));
+    if version.is_some() && stored_version.and_then(Value::as_i64) != version {
+        return Err(CrudError::Conflict("the item has been modified".to_string()));
+    }
+    let mut patched = Value::Object(stored.clone());
+    patch(&mut patched)?;
+    let Value::Object(patched) = patched else {
+        return Err(CrudError::Validation("expected a JSON object".to_string()));
+    };
+
+    let field_names = field_names::<UpdateSchema>();
+    let removed = stored.keys()
+        .filter(|field| !patched.contains_key(*field))
+        .map(|field| (field.clone(), Value::Null));
+    let changed = patched.iter()
+        .filter(|(field, value)| stored.get(*field) != Some(*value))
+        .map(|(field, value)| (field.clone(), value.clone()));
+    let mut changes: Map<String, Value> = removed.chain(changed)
+        .filter(|(field, _)| match field_names {
+            Some(field_names) => field_names.contains(&field.as_str()),
+            None => true,
+        })
+        .collect();
+    if changes.is_empty() {
+        return Ok(item);
+    }
+    if let Some((version_column, version)) = repository.get_version_column().and_then(|column| stored.get_key_value(column)) {
+        changes.entry(version_column.clone()).or_insert_with(|| version.clone());
+    }
+
+    let update: UpdateSchema = serde_json::from_value(Value::Object(changes.clone()))?;
+    // a field that isn't nullable in the update schema would keep its value
+    let written = serde_json::to_value(&update)?;
+    let uncleared: Vec<_> = changes.iter()
+        .filter(|(field, value)| value.is_null() && written.get(field.as_str()) != Some(&Value::Null))
+        .map(|(field, _)| format!("`{}`", field))
+        .collect();
+    if !uncleared.is_empty() {
+        return Err(CrudError::Validation(format!("{} can't be removed or set to null", uncleared.join(", "))));
+    }
+    repository.update_item(id, update).await
+}
+
+/// Serde helper for nullable columns in an update schema, for `Option<Option<T>>` fields with
+/// `#[serde(default, with = "crud_routers::nullable", skip_serializing_if = "Option::is_none")]`.
+/// A missing field is `None` and leaves the column unchanged, `null` is `Some(None)` and sets it to NULL.
+pub mod nullable {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T: Serialize, S: Serializer>(value: &Option<Option<T>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<T>>, D::Error> {
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+    use crate::{CreateRepository, InMemoryRepository};
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct PostForm {
+        title: Option<String>,
+        published: Option<bool>,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct FlattenedForm {
+        title: Option<String>,
+        #[serde(flatten)]
+        other: Map<String, Value>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Article {
+        id: u64,
+        title: String,
+        summary: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct ArticleForm {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, with = "nullable", skip_serializing_if = "Option::is_none")]
+        summary: Option<Option<String>>,
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let mut item = json!({"id": 1, "title": "Post", "tags": {"a": 1, "b": 2}});
+        merge_patch(&mut item, json!({"title": "Patched", "tags": {"a": null, "c": 3}, "body": "Body"}));
+        assert_eq!(item, json!({"id": 1, "title": "Patched", "tags": {"b": 2, "c": 3}, "body": "Body"}));
+
+        merge_patch(&mut item, json!([1]));
+        assert_eq!(item, json!([1]));
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let mut item = json!({"id": 1, "title": "Post", "body": "Body"});
+        let patch: Patch = serde_json::from_value(json!([
+            {"op": "test", "path": "/title", "value": "Post"},
+            {"op": "replace", "path": "/title", "value": "Patched"},
+            {"op": "remove", "path": "/body"},
+        ])).unwrap();
+        json_patch(&mut item, &patch).unwrap();
+        assert_eq!(item, json!({"id": 1, "title": "Patched"}));
+
+        let patch: Patch = serde_json::from_value(json!([{"op": "test", "path": "/title", "value": "Post"}])).unwrap();
+        assert!(matches!(json_patch(&mut item, &patch), Err(CrudError::Conflict(_))));
+
+        let patch: Patch = serde_json::from_value(json!([{"op": "replace", "path": "/body", "value": "Body"}])).unwrap();
+        assert!(matches!(json_patch(&mut item, &patch), Err(CrudError::Validation(_))));
+    }
+
+    #[test]
+    fn test_patch_format() {
+        let config = |merge_patch, json_patch| RouterConfig {
+            merge_patch,
+            json_patch,
+            ..RouterConfig::default()
+        };
+
+        assert_eq!(config(true, false).patch_format(Some(MERGE_PATCH_CONTENT_TYPE)), Some(PatchFormat::MergePatch));
+        assert_eq!(config(true, false).patch_format(Some(JSON_PATCH_CONTENT_TYPE)), None);
+        assert_eq!(config(true, true).patch_format(Some("application/json-patch+json; charset=utf-8")), Some(PatchFormat::JsonPatch));
+        assert_eq!(config(false, true).patch_format(Some(MERGE_PATCH_CONTENT_TYPE)), None);
+    }
+
+    #[test]
+    fn test_field_names() {
+        assert_eq!(field_names::<PostForm>(), Some(["title", "published"].as_slice()));
+        assert_eq!(field_names::<String>(), None);
+        assert_eq!(field_names::<FlattenedForm>(), None);
+    }
+
+    #[test]
+    fn test_replacement() {
+        assert!(replacement::<PostForm>(json!({"title": "Post", "published": true}), None).is_ok());
+        assert!(replacement::<PostForm>(json!({"title": "Post", "published": null}), None).is_ok());
+        assert!(matches!(replacement::<PostForm>(json!({"title": "Post"}), None), Err(CrudError::Validation(_))));
+        assert!(matches!(replacement::<PostForm>(json!({"title": "Post", "published": 1}), None), Err(CrudError::Validation(_))));
+        assert!(replacement::<FlattenedForm>(json!({"title": "Post"}), None).is_ok());
+
+        let form: ArticleForm = replacement(json!({"title": "Article", "summary": null}), None).unwrap();
+        assert_eq!(form, ArticleForm { title: Some(String::from("Article")), summary: Some(None) });
+    }
+
+    #[test]
+    fn test_nullable() {
+        let form: ArticleForm = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(form.summary, None);
+        let form: ArticleForm = serde_json::from_value(json!({"summary": null})).unwrap();
+        assert_eq!(form.summary, Some(None));
+        let form: ArticleForm = serde_json::from_value(json!({"summary": "Summary"})).unwrap();
+        assert_eq!(form.summary, Some(Some(String::from("Summary"))));
+
+        assert_eq!(serde_json::to_value(ArticleForm { title: None, summary: None }).unwrap(), json!({}));
+        assert_eq!(serde_json::to_value(ArticleForm { title: None, summary: Some(None) }).unwrap(), json!({"summary": null}));
+    }
+
+    #[tokio::test]
+    async fn test_patch_item() {
+        let repository = InMemoryRepository::<Article, u64>::new();
+        let article: Article = CreateRepository::create_item(&repository, json!({"title": "Article", "summary": "Summary"})).await.unwrap();
+
+        // a removed field is cleared and the others are kept
+        let patched = patch_item::<_, _, _, ArticleForm>(&repository, article.id, None, |item| {
+            merge_patch(item, json!({"summary": null}));
+            Ok(())
+        }).await.unwrap();
+        assert_eq!(patched, Article { summary: None, ..article.clone() });
+
+        let patch: Patch = serde_json::from_value(json!([{"op": "add", "path": "/summary", "value": "New Summary"}])).unwrap();
+        let patched = patch_item::<_, _, _, ArticleForm>(&repository, article.id, None, |item| json_patch(item, &patch)).await.unwrap();
+        assert_eq!(patched.summary.as_deref(), Some("New Summary"));
+
+        let patch: Patch = serde_json::from_value(json!([{"op": "remove", "path": "/summary"}])).unwrap();
+        let patched = patch_item::<_, _, _, ArticleForm>(&repository, article.id, None, |item| json_patch(item, &patch)).await.unwrap();
+        assert_eq!(patched, Article { summary: None, ..article.clone() });
+
+        // a field that isn't nullable in the update schema can't be removed
+        let result = patch_item::<_, _, _, ArticleForm>(&repository, article.id, None, |item| {
+            merge_patch(item, json!({"title": null}));
+            Ok(())
+        }).await;
+        assert!(matches!(result, Err(CrudError::Validation(_))));
+
+        // every field of the update schema has to be optional
+        let result = patch_item::<_, _, _, Article>(&repository, article.id, None, |item| {
+            merge_patch(item, json!({"title": "Patched"}));
+            Ok(())
+        }).await;
+        assert!(matches!(result, Err(CrudError::Validation(_))));
+        assert_eq!(ReadDeleteRepository::get_item(&repository, article.id).await.unwrap().map(|article: Article| article.title), Some(String::from("Article")));
+
+        let result = patch_item::<_, _, _, ArticleForm>(&repository, article.id + 1, None, |_| Ok(())).await;
+        assert!(matches!(result, Err(CrudError::NotFound)));
+    }
+}
@@ -1,152 +1,1134 @@
 use diesel::associations::HasTable;
+use diesel::backend::Backend;
 use diesel::connection::LoadConnection;
-use diesel::helper_types::{delete, Find, Limit, Offset, Update};
-use diesel::internal::table_macro::{FromClause, SelectStatement, StaticQueryFragment};
+use diesel::dsl::{count_star, Filter};
+use diesel::expression::{is_aggregate, SqlLiteral, ValidGrouping};
+use diesel::sql_types::{BigInt, Bool, Double, HasSqlType, Integer, Text, Untyped};
+use diesel::helper_types::{delete, Find, Limit, Update};
+use diesel::internal::table_macro::{BoxedSelectStatement, FromClause, SelectStatement, StaticQueryFragment};
 use diesel::prelude::*;
-use diesel::query_builder::{AsQuery, InsertStatement, IntoUpdateTarget, QueryFragment, QueryId};
-use diesel::query_dsl::filter_dsl::FindDsl;
-use diesel::query_dsl::methods::{ExecuteDsl, LimitDsl, OffsetDsl};
+use diesel::query_builder::{AsChangeset, AsQuery, AstPass, IntoUpdateTarget, QueryFragment, QueryId};
+#[cfg(any(feature = "diesel-postgres", feature = "diesel-mysql", feature = "diesel-sqlite"))]
+use diesel::query_builder::InsertStatement;
+use diesel::query_dsl::filter_dsl::{FilterDsl, FindDsl};
+use diesel::query_dsl::methods::{BoxedDsl, ExecuteDsl, LimitDsl, OffsetDsl, OrderDsl, SelectDsl, ThenOrderDsl};
 use diesel::query_dsl::LoadQuery;
+use diesel::serialize::ToSql;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use crate::Pagination;
-use crate::repositories::{CRUDRepository, CreateRepository, ReadDeleteRepository, UpdateRepository};
+use diesel::result::DatabaseErrorKind;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, PoisonError};
+use crate::{Cursor, FilterCondition, FilterValue, Pagination, Sort};
+use crate::filter::{contains_pattern, LIKE_ESCAPE};
+use crate::repositories::{required_version, signed, CRUDRepository, CreateRepository, CrudError, ReadDeleteRepository, UpdateRepository};
 
-pub struct DieselRepository<DBConnection, SchemaTable> {
-    connection: DBConnection,
-    table: SchemaTable
+/// Provides the connections a [DieselRepository] runs its queries on.
+pub trait ConnectionSource {
+    type Connection: Connection + LoadConnection + 'static;
+
+    fn with_connection<T>(&self, f: impl FnOnce(&mut Self::Connection) -> Result<T, CrudError>) -> Result<T, CrudError>;
+}
+
+/// A single connection shared by all requests.
+impl<DBConnection> ConnectionSource for Mutex<DBConnection>
+where
+    DBConnection: Connection + LoadConnection + 'static,
+{
+    type Connection = DBConnection;
+
+    fn with_connection<T>(&self, f: impl FnOnce(&mut Self::Connection) -> Result<T, CrudError>) -> Result<T, CrudError> {
+        let mut connection = self.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut connection)
+    }
+}
+
+/// Checks out a connection from the pool for every call.
+#[cfg(feature = "r2d2")]
+impl<DBConnection> ConnectionSource for diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<DBConnection>>
+where
+    DBConnection: diesel::r2d2::R2D2Connection + LoadConnection + Send + 'static,
+{
+    type Connection = DBConnection;
+
+    fn with_connection<T>(&self, f: impl FnOnce(&mut Self::Connection) -> Result<T, CrudError>) -> Result<T, CrudError> {
+        let mut connection = self.get()
+            .map_err(|e| CrudError::Connection(e.to_string()))?;
+        f(&mut connection)
+    }
+}
+
+/// A boxed `SELECT * FROM table` that filters and pagination are added to at runtime.
+pub(crate) type BoxedTableQuery<SchemaTable, DB> = BoxedSelectStatement<'static, <SchemaTable as AsQuery>::SqlType, FromClause<SchemaTable>, DB>;
+
+/// A `SELECT COUNT(*)` of a [BoxedTableQuery].
+pub(crate) type BoxedCountQuery<SchemaTable, DB> = BoxedSelectStatement<'static, BigInt, FromClause<SchemaTable>, DB>;
+
+/// A [FilterClause] that can be added to a [BoxedTableQuery].
+pub(crate) type BoxedFilter<SchemaTable, DB> = Box<dyn BoxableExpression<SchemaTable, DB, SqlType = Bool>>;
+
+/// The filters of the list route as one `WHERE` condition, the columns are looked up by name.
+pub(crate) struct FilterClause(Vec<crate::Filter>, &'static [(&'static str, &'static str)]);
+
+impl FilterClause {
+    pub(crate) fn new(filters: Vec<crate::Filter>) -> Self {
+        // the pattern is bound by reference, so it has to live in the clause
+        Self(filters.into_iter()
+            .map(|filter| match filter.condition {
+                FilterCondition::Contains(text) => crate::Filter {
+                    condition: FilterCondition::Contains(contains_pattern(&text)),
+                    ..filter
+                },
+                _ => filter,
+            })
+            .collect(), &[])
+    }
+
+    /// Casts the values compared with the columns of `column_types` to their SQL types,
+    /// see [DieselRepository::column_types].
+    pub(crate) fn cast(self, column_types: &'static [(&'static str, &'static str)]) -> Self {
+        Self(self.0, column_types)
+    }
+
+    fn push_value<'b, DB>(out: &mut AstPass<'_, 'b, DB>, value: &'b FilterValue, column_type: Option<&str>) -> QueryResult<()>
+    where
+        DB: Backend + HasSqlType<Bool> + HasSqlType<BigInt> + HasSqlType<Double> + HasSqlType<Text>,
+        bool: ToSql<Bool, DB>,
+        i64: ToSql<BigInt, DB>,
+        f64: ToSql<Double, DB>,
+        String: ToSql<Text, DB>,
+    {
+        if column_type.is_some() {
+            out.push_sql("CAST(");
+        }
+        match value {
+            FilterValue::Bool(value) => out.push_bind_param::<Bool, _>(value)?,
+            FilterValue::Integer(value) => out.push_bind_param::<BigInt, _>(value)?,
+            FilterValue::Float(value) => out.push_bind_param::<Double, _>(value)?,
+            FilterValue::String(value) => out.push_bind_param::<Text, _>(value)?,
+        }
+        if let Some(column_type) = column_type {
+            out.push_sql(" AS ");
+            out.push_sql(column_type);
+            out.push_sql(")");
+        }
+        Ok(())
+    }
+}
+
+impl Expression for FilterClause {
+    type SqlType = Bool;
+}
+
+impl<QS> AppearsOnTable<QS> for FilterClause {}
+
+impl<QS> SelectableExpression<QS> for FilterClause {}
+
+impl ValidGrouping<()> for FilterClause {
+    type IsAggregate = is_aggregate::Never;
+}
+
+impl QueryId for FilterClause {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<DB> QueryFragment<DB> for FilterClause
+where
+    DB: Backend + HasSqlType<Bool> + HasSqlType<BigInt> + HasSqlType<Double> + HasSqlType<Text>,
+    bool: ToSql<Bool, DB>,
+    i64: ToSql<BigInt, DB>,
+    f64: ToSql<Double, DB>,
+    String: ToSql<Text, DB>,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, DB>) -> QueryResult<()> {
+        if self.0.is_empty() {
+            out.push_sql("1 = 1");
+        }
+
+        for (i, filter) in self.0.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(" AND ");
+            }
+            out.push_identifier(&filter.field)?;
+            let column_type = self.1.iter()
+                .find(|(column, _)| *column == filter.field)
+                .map(|(_, column_type)| *column_type);
+            match &filter.condition {
+                FilterCondition::Eq(value) => {
+                    out.push_sql(" = ");
+                    Self::push_value(&mut out, value, column_type)?;
+                }
+                FilterCondition::Ne(value) => {
+                    out.push_sql(" <> ");
+                    Self::push_value(&mut out, value, column_type)?;
+                }
+                FilterCondition::Lt(value) => {
+                    out.push_sql(" < ");
+                    Self::push_value(&mut out, value, column_type)?;
+                }
+                FilterCondition::Gt(value) => {
+                    out.push_sql(" > ");
+                    Self::push_value(&mut out, value, column_type)?;
+                }
+                FilterCondition::In(values) => {
+                    out.push_sql(" IN (");
+                    for (i, value) in values.iter().enumerate() {
+                        if i > 0 {
+                            out.push_sql(", ");
+                        }
+                        Self::push_value(&mut out, value, column_type)?;
+                    }
+                    out.push_sql(")");
+                }
+                FilterCondition::Contains(pattern) => {
+                    out.push_sql(" LIKE ");
+                    out.push_bind_param::<Text, _>(pattern)?;
+                    out.push_sql(&format!(" ESCAPE '{}'", LIKE_ESCAPE));
+                }
+                FilterCondition::IsNull(true) => out.push_sql(" IS NULL"),
+                FilterCondition::IsNull(false) => out.push_sql(" IS NOT NULL"),
+            }
+        }
+        Ok(())
+    }
 }
 
+/// The items after a cursor as alternatives of [FilterClause]s, see [crate::Cursor::keyset].
+pub(crate) struct KeysetClause(Vec<FilterClause>);
+
+impl KeysetClause {
+    pub(crate) fn new(keyset: Vec<Vec<crate::Filter>>, column_types: &'static [(&'static str, &'static str)]) -> Self {
+        Self(keyset.into_iter().map(|filters| FilterClause::new(filters).cast(column_types)).collect())
+    }
+}
 
-impl<DBConnection, SchemaTable> DieselRepository<DBConnection, SchemaTable>
+impl Expression for KeysetClause {
+    type SqlType = Bool;
+}
+
+impl<QS> AppearsOnTable<QS> for KeysetClause {}
+
+impl<QS> SelectableExpression<QS> for KeysetClause {}
+
+impl ValidGrouping<()> for KeysetClause {
+    type IsAggregate = is_aggregate::Never;
+}
+
+impl QueryId for KeysetClause {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<DB> QueryFragment<DB> for KeysetClause
+where
+    DB: Backend,
+    FilterClause: QueryFragment<DB>,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, DB>) -> QueryResult<()> {
+        if self.0.is_empty() {
+            out.push_sql("1 = 1");
+        }
+
+        for (i, clause) in self.0.iter().enumerate() {
+            out.push_sql(if i == 0 { "(" } else { " OR (" });
+            clause.walk_ast(out.reborrow())?;
+            out.push_sql(")");
+        }
+        Ok(())
+    }
+}
+
+/// The sort keys of the list route as an `ORDER BY` list, the columns are looked up by name.
+/// Nulls sort first like the smallest value, which not every database does on its own.
+pub(crate) struct SortClause(pub(crate) Vec<Sort>);
+
+impl Expression for SortClause {
+    type SqlType = Untyped;
+}
+
+impl<QS> AppearsOnTable<QS> for SortClause {}
+
+impl<DB: Backend> QueryFragment<DB> for SortClause {
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, DB>) -> QueryResult<()> {
+        for (i, sort) in self.0.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            out.push_identifier(&sort.field)?;
+            out.push_sql(if sort.descending { " IS NULL, " } else { " IS NULL DESC, " });
+            out.push_identifier(&sort.field)?;
+            out.push_sql(if sort.descending { " DESC" } else { " ASC" });
+        }
+        Ok(())
+    }
+}
+
+/// `SET version = version + 1`, the changeset that bumps the version column of a [DieselRepository].
+pub(crate) struct VersionBump<SchemaTable>(&'static str, PhantomData<SchemaTable>);
+
+impl<SchemaTable> VersionBump<SchemaTable> {
+    pub(crate) fn new(version_column: &'static str) -> Self {
+        Self(version_column, PhantomData)
+    }
+}
+
+impl<SchemaTable: QuerySource> AsChangeset for VersionBump<SchemaTable> {
+    type Target = SchemaTable;
+    type Changeset = Self;
+
+    fn as_changeset(self) -> Self::Changeset {
+        self
+    }
+}
+
+impl<SchemaTable, DB: Backend> QueryFragment<DB> for VersionBump<SchemaTable> {
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, DB>) -> QueryResult<()> {
+        out.push_identifier(self.0)?;
+        out.push_sql(" = ");
+        out.push_identifier(self.0)?;
+        out.push_sql(" + 1");
+        Ok(())
+    }
+}
+
+/// Inserts items and loads the inserted rows, with `RETURNING` where the backend supports it.
+pub trait InsertReturning<DBConnection, SchemaTable, CreateSchema, Schema> {
+    fn insert_returning(connection: &mut DBConnection, table: SchemaTable, new_item: CreateSchema) -> QueryResult<Schema>;
+    fn insert_all_returning(connection: &mut DBConnection, table: SchemaTable, new_items: Vec<CreateSchema>) -> QueryResult<Vec<Schema>>;
+}
+
+/// Updates an item and loads the updated row, with `RETURNING` where the backend supports it.
+pub trait UpdateReturning<DBConnection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema> {
+    fn update_returning(connection: &mut DBConnection, table: SchemaTable, id: PrimaryKeyType, item: UpdateSchema) -> QueryResult<Schema>;
+}
+
+#[cfg(feature = "diesel-postgres")]
+impl<DBConnection, SchemaTable, CreateSchema, Schema> InsertReturning<DBConnection, SchemaTable, CreateSchema, Schema> for diesel::pg::Pg
+where
+    SchemaTable: Table,
+    CreateSchema: Insertable<SchemaTable>,
+    for<'a> InsertStatement<SchemaTable, CreateSchema::Values>: AsQuery + LoadQuery<'a, DBConnection, Schema>,
+    Vec<CreateSchema>: Insertable<SchemaTable>,
+    for<'a> InsertStatement<SchemaTable, <Vec<CreateSchema> as Insertable<SchemaTable>>::Values>: AsQuery + LoadQuery<'a, DBConnection, Schema>,
+{
+    fn insert_returning(connection: &mut DBConnection, table: SchemaTable, new_item: CreateSchema) -> QueryResult<Schema> {
+        diesel::insert_into(table)
+            .values(new_item)
+            .get_result(connection)
+    }
+
+    fn insert_all_returning(connection: &mut DBConnection, table: SchemaTable, new_items: Vec<CreateSchema>) -> QueryResult<Vec<Schema>> {
+        diesel::insert_into(table)
+            .values(new_items)
+            .get_results(connection)
+    }
+}
+
+#[cfg(feature = "diesel-postgres")]
+impl<DBConnection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema> UpdateReturning<DBConnection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema> for diesel::pg::Pg
+where
+    SchemaTable: Table + FindDsl<PrimaryKeyType>,
+    UpdateSchema: AsChangeset<Target=SchemaTable>,
+    Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    for<'a> Update<Find<SchemaTable, PrimaryKeyType>, UpdateSchema>: AsQuery + LoadQuery<'a, DBConnection, Schema>,
+{
+    fn update_returning(connection: &mut DBConnection, table: SchemaTable, id: PrimaryKeyType, item: UpdateSchema) -> QueryResult<Schema> {
+        diesel::update(table.find(id))
+            .set(item)
+            .get_result(connection)
+    }
+}
+
+/// The condition that finds the row written by [insert_then_select].
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+pub(crate) enum InsertedRow {
+    /// The primary key was part of the inserted item.
+    Key(FilterClause),
+    /// The primary key was generated by the database, a SQL condition on the last inserted row.
+    Generated(String),
+}
+
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+impl Expression for InsertedRow {
+    type SqlType = Bool;
+}
+
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+impl<QS> AppearsOnTable<QS> for InsertedRow {}
+
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+impl ValidGrouping<()> for InsertedRow {
+    type IsAggregate = is_aggregate::Never;
+}
+
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+impl QueryId for InsertedRow {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+impl<DB> QueryFragment<DB> for InsertedRow
+where
+    DB: Backend,
+    FilterClause: QueryFragment<DB>,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, DB>) -> QueryResult<()> {
+        match self {
+            InsertedRow::Key(clause) => clause.walk_ast(out),
+            InsertedRow::Generated(condition) => {
+                out.push_sql(condition);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+type LastInserted<SchemaTable> = Filter<SchemaTable, InsertedRow>;
+
+/// Fallback for backends without `RETURNING`: inserts, then selects the row by the primary key of
+/// `new_item`. Without a key in `new_item` the row is selected with `generated_key`, a condition
+/// on the row the backend inserted last.
+///
+/// The key is read from the JSON representation of `new_item`, so its field has to be named like
+/// the primary key column.
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+fn insert_then_select<DBConnection, SchemaTable, CreateSchema, Schema>(connection: &mut DBConnection, table: SchemaTable, new_item: CreateSchema, generated_key: &str) -> QueryResult<Schema>
+where
+    DBConnection: Connection,
+    SchemaTable: Table + FilterDsl<InsertedRow> + Copy,
+    SchemaTable::PrimaryKey: Column,
+    CreateSchema: Insertable<SchemaTable> + Serialize,
+    InsertStatement<SchemaTable, CreateSchema::Values>: ExecuteDsl<DBConnection>,
+    LastInserted<SchemaTable>: LimitDsl,
+    for<'a> Limit<LastInserted<SchemaTable>>: LoadQuery<'a, DBConnection, Schema>,
+{
+    let primary_key = <SchemaTable::PrimaryKey as Column>::NAME;
+    let key = serde_json::to_value(&new_item)
+        .map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))?
+        .get(primary_key)
+        .and_then(FilterValue::from_json);
+    let inserted_row = match key {
+        Some(key) => InsertedRow::Key(FilterClause::new(vec![crate::Filter {
+            field: primary_key.to_string(),
+            condition: FilterCondition::Eq(key),
+        }])),
+        None => InsertedRow::Generated(generated_key.to_string()),
+    };
+
+    connection.transaction(|connection| {
+        diesel::insert_into(table)
+            .values(new_item)
+            .execute(connection)?;
+
+        table
+            .filter(inserted_row)
+            .limit(1)
+            .get_result(connection)
+    })
+}
+
+/// Fallback for backends without `RETURNING`: updates, then selects the row by its id.
+#[cfg(any(feature = "diesel-mysql", feature = "diesel-sqlite"))]
+fn update_then_select<DBConnection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema>(connection: &mut DBConnection, table: SchemaTable, id: PrimaryKeyType, item: UpdateSchema) -> QueryResult<Schema>
+where
+    DBConnection: Connection,
+    SchemaTable: Table + FindDsl<PrimaryKeyType> + Copy,
+    PrimaryKeyType: Clone,
+    UpdateSchema: AsChangeset<Target=SchemaTable>,
+    Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget + LimitDsl,
+    Update<Find<SchemaTable, PrimaryKeyType>, UpdateSchema>: AsQuery + ExecuteDsl<DBConnection>,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, DBConnection, Schema>,
+{
+    connection.transaction(|connection| {
+        diesel::update(table.find(id.clone()))
+            .set(item)
+            .execute(connection)?;
+
+        table
+            .find(id)
+            .limit(1)
+            .get_result(connection)
+    })
+}
+
+/// `LAST_INSERT_ID()` is only set by `AUTO_INCREMENT` columns, other keys generated by MySQL
+/// can't be selected back.
+#[cfg(feature = "diesel-mysql")]
+fn mysql_generated_key<SchemaTable>() -> String
+where
+    SchemaTable: Table,
+    SchemaTable::PrimaryKey: Column,
+{
+    format!("`{}` = LAST_INSERT_ID()", <SchemaTable::PrimaryKey as Column>::NAME.replace('`', "``"))
+}
+
+#[cfg(feature = "diesel-mysql")]
+impl<DBConnection, SchemaTable, CreateSchema, Schema> InsertReturning<DBConnection, SchemaTable, CreateSchema, Schema> for diesel::mysql::Mysql
+where
+    DBConnection: Connection,
+    SchemaTable: Table + FilterDsl<InsertedRow> + Copy,
+    SchemaTable::PrimaryKey: Column,
+    CreateSchema: Insertable<SchemaTable> + Serialize,
+    InsertStatement<SchemaTable, CreateSchema::Values>: ExecuteDsl<DBConnection>,
+    LastInserted<SchemaTable>: LimitDsl,
+    for<'a> Limit<LastInserted<SchemaTable>>: LoadQuery<'a, DBConnection, Schema>,
+{
+    fn insert_returning(connection: &mut DBConnection, table: SchemaTable, new_item: CreateSchema) -> QueryResult<Schema> {
+        insert_then_select(connection, table, new_item, &mysql_generated_key::<SchemaTable>())
+    }
+
+    fn insert_all_returning(connection: &mut DBConnection, table: SchemaTable, new_items: Vec<CreateSchema>) -> QueryResult<Vec<Schema>> {
+        connection.transaction(|connection| {
+            new_items.into_iter()
+                .map(|new_item| insert_then_select(connection, table, new_item, &mysql_generated_key::<SchemaTable>()))
+                .collect()
+        })
+    }
+}
+
+#[cfg(feature = "diesel-mysql")]
+impl<DBConnection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema> UpdateReturning<DBConnection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema> for diesel::mysql::Mysql
+where
+    DBConnection: Connection,
+    SchemaTable: Table + FindDsl<PrimaryKeyType> + Copy,
+    PrimaryKeyType: Clone,
+    UpdateSchema: AsChangeset<Target=SchemaTable>,
+    Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget + LimitDsl,
+    Update<Find<SchemaTable, PrimaryKeyType>, UpdateSchema>: AsQuery + ExecuteDsl<DBConnection>,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, DBConnection, Schema>,
+{
+    fn update_returning(connection: &mut DBConnection, table: SchemaTable, id: PrimaryKeyType, item: UpdateSchema) -> QueryResult<Schema> {
+        update_then_select(connection, table, id, item)
+    }
+}
+
+// `RETURNING` needs SQLite 3.35 and an opt-in diesel feature, so SQLite always takes the fallback
+#[cfg(feature = "diesel-sqlite")]
+impl<DBConnection, SchemaTable, CreateSchema, Schema> InsertReturning<DBConnection, SchemaTable, CreateSchema, Schema> for diesel::sqlite::Sqlite
+where
+    DBConnection: Connection,
+    SchemaTable: Table + FilterDsl<InsertedRow> + Copy,
+    SchemaTable::PrimaryKey: Column,
+    CreateSchema: Insertable<SchemaTable> + Serialize,
+    InsertStatement<SchemaTable, CreateSchema::Values>: ExecuteDsl<DBConnection>,
+    LastInserted<SchemaTable>: LimitDsl,
+    for<'a> Limit<LastInserted<SchemaTable>>: LoadQuery<'a, DBConnection, Schema>,
+{
+    // the rowid also finds rows with other generated keys, except in `WITHOUT ROWID` tables
+    fn insert_returning(connection: &mut DBConnection, table: SchemaTable, new_item: CreateSchema) -> QueryResult<Schema> {
+        insert_then_select(connection, table, new_item, "rowid = last_insert_rowid()")
+    }
+
+    fn insert_all_returning(connection: &mut DBConnection, table: SchemaTable, new_items: Vec<CreateSchema>) -> QueryResult<Vec<Schema>> {
+        connection.transaction(|connection| {
+            new_items.into_iter()
+                .map(|new_item| insert_then_select(connection, table, new_item, "rowid = last_insert_rowid()"))
+                .collect()
+        })
+    }
+}
+
+#[cfg(feature = "diesel-sqlite")]
+impl<DBConnection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema> UpdateReturning<DBConnection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema> for diesel::sqlite::Sqlite
+where
+    DBConnection: Connection,
+    SchemaTable: Table + FindDsl<PrimaryKeyType> + Copy,
+    PrimaryKeyType: Clone,
+    UpdateSchema: AsChangeset<Target=SchemaTable>,
+    Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget + LimitDsl,
+    Update<Find<SchemaTable, PrimaryKeyType>, UpdateSchema>: AsQuery + ExecuteDsl<DBConnection>,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, DBConnection, Schema>,
+{
+    fn update_returning(connection: &mut DBConnection, table: SchemaTable, id: PrimaryKeyType, item: UpdateSchema) -> QueryResult<Schema> {
+        update_then_select(connection, table, id, item)
+    }
+}
+
+pub struct DieselRepository<Source, SchemaTable> {
+    connection: Arc<Source>,
+    table: SchemaTable,
+    version_column: Option<&'static str>,
+    column_types: &'static [(&'static str, &'static str)]
+}
+
+impl<Source, SchemaTable> DieselRepository<Source, SchemaTable>
+where
+    Source: ConnectionSource + Send + Sync + 'static,
+{
+    /// Runs the blocking diesel query on tokio's blocking thread pool,
+    /// so slow queries don't stall the async workers.
+    async fn run<T: Send + 'static>(&self, query: impl FnOnce(&mut Source::Connection) -> Result<T, CrudError> + Send + 'static) -> Result<T, CrudError> {
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || connection.with_connection(query))
+            .await
+            .map_err(|e| CrudError::Backend(e.to_string()))?
+    }
+}
+
+impl<Source, SchemaTable> DieselRepository<Source, SchemaTable> {
+    /// Enables optimistic locking on an integer column: `update_item` and `update_items` only update
+    /// the rows if their version equals the one of the update, bump it, and fail with
+    /// [CrudError::Conflict] otherwise and with [CrudError::PreconditionRequired] without a version.
+    pub fn version_column(self, version_column: &'static str) -> Self{
+        Self{
+            version_column: Some(version_column),
+            ..self
+        }
+    }
+
+    /// SQL types of the columns whose filter and cursor values need a `CAST`, e.g. `("created_at", "timestamptz")`.
+    ///
+    /// Values are bound as `BIGINT`, `DOUBLE` or `TEXT`, which Postgres doesn't compare with
+    /// timestamp, uuid or text columns of another type without a cast.
+    pub fn column_types(self, column_types: &'static [(&'static str, &'static str)]) -> Self{
+        Self{
+            column_types,
+            ..self
+        }
+    }
+}
+
+
+impl<DBConnection, SchemaTable> DieselRepository<Mutex<DBConnection>, SchemaTable>
 where
     SchemaTable: Table,
 {
     pub fn new(connection: DBConnection, table: SchemaTable) -> Self{
         Self{
-            connection,
-            table
+            connection: Arc::new(Mutex::new(connection)),
+            table,
+            version_column: None,
+            column_types: &[]
+        }
+    }
+}
+
+#[cfg(feature = "r2d2")]
+impl<DBConnection, SchemaTable> DieselRepository<diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<DBConnection>>, SchemaTable>
+where
+    DBConnection: diesel::r2d2::R2D2Connection + 'static,
+    SchemaTable: Table,
+{
+    pub fn from_pool(pool: diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<DBConnection>>, table: SchemaTable) -> Self{
+        Self{
+            connection: Arc::new(pool),
+            table,
+            version_column: None,
+            column_types: &[]
         }
     }
+}
 
+impl From<diesel::result::Error> for CrudError {
+    fn from(error: diesel::result::Error) -> Self {
+        match error {
+            diesel::result::Error::NotFound => CrudError::NotFound,
+            diesel::result::Error::DatabaseError(kind, info) => match kind {
+                DatabaseErrorKind::UniqueViolation | DatabaseErrorKind::ForeignKeyViolation => CrudError::Conflict(info.message().to_string()),
+                DatabaseErrorKind::NotNullViolation | DatabaseErrorKind::CheckViolation => CrudError::Validation(info.message().to_string()),
+                DatabaseErrorKind::ClosedConnection => CrudError::Connection(info.message().to_string()),
+                _ => CrudError::Backend(info.message().to_string()),
+            },
+            diesel::result::Error::BrokenTransactionManager => CrudError::Connection(error.to_string()),
+            error => CrudError::Backend(error.to_string()),
+        }
+    }
 }
 
-impl<DBConnection, SchemaTable> CRUDRepository for DieselRepository<DBConnection, SchemaTable> {}
+impl<Source, SchemaTable> CRUDRepository for DieselRepository<Source, SchemaTable> {
+    fn get_version_column(&self) -> Option<&'static str> {
+        self.version_column
+    }
+}
 
-impl<DBConnection, SchemaTable, Schema, PrimaryKeyType> ReadDeleteRepository<Schema, PrimaryKeyType> for DieselRepository<DBConnection, SchemaTable>
+impl<Source, SchemaTable, Schema, PrimaryKeyType> ReadDeleteRepository<Schema, PrimaryKeyType> for DieselRepository<Source, SchemaTable>
 where
-    DBConnection: Connection + LoadConnection + 'static,
-    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<DBConnection::Backend> + StaticQueryFragment<Component=diesel::internal::table_macro::Identifier<'static>> + Table + QueryId + Copy + Send + 'static,
+    Source: ConnectionSource + Send + Sync + 'static,
+    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<<Source::Connection as Connection>::Backend> + StaticQueryFragment<Component=diesel::internal::table_macro::Identifier<'static>> + Table + QueryId + Copy + Send + Sync + 'static,
 
-    PrimaryKeyType: Send + DeserializeOwned + 'static,
+    PrimaryKeyType: Send + DeserializeOwned + Clone + 'static,
     SchemaTable::PrimaryKey: EqAll<PrimaryKeyType>,
 
     // for list_items
     Schema: Serialize + Send + 'static,
-    for<'a> Offset<Limit<SelectStatement<FromClause<SchemaTable>>>>: LoadQuery<'a, DBConnection, Schema>,
-    for<'a> Limit<SelectStatement<FromClause<SchemaTable>>>: LoadQuery<'a, DBConnection, Schema>,
-    for<'a> Offset<SelectStatement<FromClause<SchemaTable>>>: LoadQuery<'a, DBConnection, Schema>,
-    for<'a> SchemaTable: LoadQuery<'a, DBConnection, Schema>,
+    <Source::Connection as Connection>::Backend: HasSqlType<Bool> + HasSqlType<BigInt> + HasSqlType<Double> + HasSqlType<Text>,
+    bool: ToSql<Bool, <Source::Connection as Connection>::Backend>,
+    i64: ToSql<BigInt, <Source::Connection as Connection>::Backend>,
+    f64: ToSql<Double, <Source::Connection as Connection>::Backend>,
+    String: ToSql<Text, <Source::Connection as Connection>::Backend>,
+    SchemaTable: BoxedDsl<'static, <Source::Connection as Connection>::Backend, Output = BoxedTableQuery<SchemaTable, <Source::Connection as Connection>::Backend>>,
+    BoxedTableQuery<SchemaTable, <Source::Connection as Connection>::Backend>: FilterDsl<BoxedFilter<SchemaTable, <Source::Connection as Connection>::Backend>, Output = BoxedTableQuery<SchemaTable, <Source::Connection as Connection>::Backend>> + LimitDsl<Output = BoxedTableQuery<SchemaTable, <Source::Connection as Connection>::Backend>> + OffsetDsl<Output = BoxedTableQuery<SchemaTable, <Source::Connection as Connection>::Backend>>,
+    for<'a> BoxedTableQuery<SchemaTable, <Source::Connection as Connection>::Backend>: LoadQuery<'a, Source::Connection, Schema>,
+    SchemaTable::PrimaryKey: Column + QueryFragment<<Source::Connection as Connection>::Backend> + AppearsOnTable<SchemaTable> + Send,
+
+    // for count_items
+    BoxedTableQuery<SchemaTable, <Source::Connection as Connection>::Backend>: SelectDsl<count_star, Output = BoxedCountQuery<SchemaTable, <Source::Connection as Connection>::Backend>>,
+    for<'a> BoxedCountQuery<SchemaTable, <Source::Connection as Connection>::Backend>: LoadQuery<'a, Source::Connection, i64>,
 
     // for get_item
     SchemaTable: LimitDsl + FindDsl<PrimaryKeyType>,
     Find<SchemaTable, PrimaryKeyType>: LimitDsl,
-    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, DBConnection, Schema>,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, Source::Connection, Schema>,
+
+    // for exists
+    Find<SchemaTable, PrimaryKeyType>: SelectDsl<SqlLiteral<Integer>>,
+    diesel::dsl::Select<Find<SchemaTable, PrimaryKeyType>, SqlLiteral<Integer>>: LimitDsl,
+    for<'a> Limit<diesel::dsl::Select<Find<SchemaTable, PrimaryKeyType>, SqlLiteral<Integer>>>: LoadQuery<'a, Source::Connection, i32>,
 
     // for delete_item
     Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
-    delete<Find<SchemaTable, PrimaryKeyType>>: ExecuteDsl<DBConnection>,
+    delete<Find<SchemaTable, PrimaryKeyType>>: ExecuteDsl<Source::Connection>,
+
+    // for delete_item_at_version
+    Find<SchemaTable, PrimaryKeyType>: FilterDsl<FilterClause>,
+    Filter<Find<SchemaTable, PrimaryKeyType>, FilterClause>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    delete<Filter<Find<SchemaTable, PrimaryKeyType>, FilterClause>>: ExecuteDsl<Source::Connection>,
 
     // for delete_all_items
-    SchemaTable: IntoUpdateTarget,
-    delete<SchemaTable>: ExecuteDsl<DBConnection>
+    SchemaTable: FilterDsl<FilterClause>,
+    Filter<SchemaTable, FilterClause>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    delete<Filter<SchemaTable, FilterClause>>: ExecuteDsl<Source::Connection>
 
 {
     fn get_table_name() -> String {
         <SchemaTable as StaticQueryFragment>::STATIC_COMPONENT.0.to_string()
     }
 
-    async fn list_items(&mut self, pagination: Pagination) -> Vec<Schema> {
-        let result = match (pagination.limit, pagination.skip) {
-            (Some(limit), Some(skip)) =>
-                OffsetDsl::offset(
-                    LimitDsl::limit(self.table.as_query(), limit as i64),
-                    skip as i64
-                )
-                .load::<Schema>(&mut self.connection),
-            (Some(limit), None) =>
-                LimitDsl::limit(self.table.as_query(), limit as i64)
-                .load::<Schema>(&mut self.connection),
-            (None, Some(skip)) =>
-                OffsetDsl::offset(self.table.as_query(), skip as i64)
-                .load::<Schema>(&mut self.connection),
-            (None, None) =>
-                self.table.load::<Schema>(&mut self.connection),
-        };
-        result.expect("Error loading items")
+    fn get_primary_key_name(&self) -> String {
+        <SchemaTable::PrimaryKey as Column>::NAME.to_string()
     }
 
-    async fn get_item(&mut self, id: PrimaryKeyType) -> Option<Schema> {
-        self.table
-            .find(id)
-            .limit(1)
-            .get_result::<Schema>(&mut self.connection)
-            .optional()
-            .unwrap()
+    async fn list_items(&self, pagination: Pagination, filters: Vec<crate::Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let table = self.table;
+        let column_types = self.column_types;
+        self.run(move |connection| {
+            let mut query = table.into_boxed();
+            if !filters.is_empty() {
+                let filter: BoxedFilter<SchemaTable, _> = Box::new(FilterClause::new(filters).cast(column_types));
+                query = FilterDsl::filter(query, filter);
+            }
+            // ties and unsorted lists are ordered by the primary key
+            query = if sort.is_empty() {
+                OrderDsl::order(query, table.primary_key())
+            } else {
+                ThenOrderDsl::then_order_by(OrderDsl::order(query, SortClause(sort)), table.primary_key())
+            };
+            if let Some(limit) = pagination.limit {
+                query = LimitDsl::limit(query, signed(limit)?);
+            }
+            if let Some(skip) = pagination.skip {
+                query = OffsetDsl::offset(query, signed(skip)?);
+            }
+            Ok(query.load::<Schema>(connection)?)
+        }).await
+    }
+
+    async fn list_items_after(&self, cursor: Option<Cursor>, limit: Option<u64>, filters: Vec<crate::Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let table = self.table;
+        let column_types = self.column_types;
+        self.run(move |connection| {
+            let mut query = table.into_boxed();
+            if !filters.is_empty() {
+                let filter: BoxedFilter<SchemaTable, _> = Box::new(FilterClause::new(filters).cast(column_types));
+                query = FilterDsl::filter(query, filter);
+            }
+            if let Some(cursor) = cursor {
+                let keyset: BoxedFilter<SchemaTable, _> = Box::new(KeysetClause::new(cursor.keyset(&sort, <SchemaTable::PrimaryKey as Column>::NAME), column_types));
+                query = FilterDsl::filter(query, keyset);
+            }
+            query = if sort.is_empty() {
+                OrderDsl::order(query, table.primary_key())
+            } else {
+                ThenOrderDsl::then_order_by(OrderDsl::order(query, SortClause(sort)), table.primary_key())
+            };
+            if let Some(limit) = limit {
+                query = LimitDsl::limit(query, signed(limit)?);
+            }
+            Ok(query.load::<Schema>(connection)?)
+        }).await
+    }
+
+    async fn count_items(&self, filters: Vec<crate::Filter>) -> Result<u64, CrudError> {
+        let table = self.table;
+        let column_types = self.column_types;
+        self.run(move |connection| {
+            let mut query = table.into_boxed();
+            if !filters.is_empty() {
+                let filter: BoxedFilter<SchemaTable, _> = Box::new(FilterClause::new(filters).cast(column_types));
+                query = FilterDsl::filter(query, filter);
+            }
+            Ok(SelectDsl::select(query, count_star()).get_result::<i64>(connection)? as u64)
+        }).await
+    }
+
+    async fn get_item(&self, id: PrimaryKeyType) -> Result<Option<Schema>, CrudError> {
+        let table = self.table;
+        self.run(move |connection| {
+            Ok(table
+                .find(id)
+                .limit(1)
+                .get_result::<Schema>(connection)
+                .optional()?)
+        }).await
+    }
+    async fn exists(&self, id: PrimaryKeyType) -> Result<bool, CrudError> {
+        let table = self.table;
+        self.run(move |connection| {
+            Ok(table
+                .find(id)
+                .select(diesel::dsl::sql::<Integer>("1"))
+                .limit(1)
+                .get_result::<i32>(connection)
+                .optional()?
+                .is_some())
+        }).await
+    }
+    async fn delete_item(&self, id: PrimaryKeyType) -> Result<(), CrudError> {
+        let table = self.table;
+        let deleted = self.run(move |connection| {
+            Ok(diesel::delete(table.find(id))
+                .execute(connection)?)
+        }).await?;
+
+        if deleted == 0 {
+            return Err(CrudError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete_item_at_version(&self, id: PrimaryKeyType, version: i64) -> Result<(), CrudError> {
+        let table = self.table;
+        let Some(version_column) = self.version_column else {
+            return ReadDeleteRepository::<Schema, PrimaryKeyType>::delete_item(self, id).await;
+        };
+        let guard = FilterClause::new(vec![
+            crate::Filter { field: version_column.to_string(), condition: FilterCondition::Eq(FilterValue::Integer(version)) }
+        ]);
+
+        self.run(move |connection| {
+            connection.transaction(|connection| {
+                let deleted = diesel::delete(table.find(id.clone()).filter(guard))
+                    .execute(connection)?;
+                if deleted == 0 {
+                    // a missing item is not found rather than modified
+                    table.find(id).limit(1).get_result::<Schema>(connection)?;
+                    return Err(CrudError::Conflict("the item has been modified".to_string()));
+                }
+                Ok(())
+            })
+        }).await
     }
-    async fn delete_item(&mut self, id: PrimaryKeyType) {
-        diesel::delete(self.table.find(id))
-            .execute(&mut self.connection)
-            .expect("Error deleting item");
+
+    async fn delete_items(&self, ids: Vec<PrimaryKeyType>) -> Result<usize, CrudError> {
+        let table = self.table;
+        self.run(move |connection| {
+            Ok(connection.transaction(|connection| {
+                ids.into_iter()
+                    .map(|id| diesel::delete(table.find(id)).execute(connection))
+                    .sum::<QueryResult<usize>>()
+            })?)
+        }).await
     }
 
-    async fn delete_all_items(&mut self) -> usize {
-        diesel::delete(self.table)
-            .execute(&mut self.connection)
-            .expect("Error deleting items")
+    async fn delete_all_items(&self, filters: Vec<crate::Filter>) -> Result<usize, CrudError> {
+        let table = self.table;
+        let column_types = self.column_types;
+        self.run(move |connection| {
+            Ok(diesel::delete(FilterDsl::filter(table, FilterClause::new(filters).cast(column_types)))
+                .execute(connection)?)
+        }).await
     }
 }
 
-impl<DBConnection, SchemaTable, Schema, CreateSchema> CreateRepository<Schema, CreateSchema> for DieselRepository<DBConnection, SchemaTable>
+impl<Source, SchemaTable, Schema, CreateSchema> CreateRepository<Schema, CreateSchema> for DieselRepository<Source, SchemaTable>
 where
-    DBConnection: Connection + LoadConnection + 'static,
-    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<DBConnection::Backend> + StaticQueryFragment + Table + QueryId + Copy + Send + 'static,
+    Source: ConnectionSource + Send + Sync + 'static,
+    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<<Source::Connection as Connection>::Backend> + StaticQueryFragment + Table + QueryId + Copy + Send + Sync + 'static,
 
     // for create_item
-    CreateSchema: DeserializeOwned + Insertable<SchemaTable> + Send + 'static,
-    for<'a> InsertStatement<SchemaTable, CreateSchema::Values>: AsQuery + LoadQuery<'a, DBConnection, Schema>,
+    Schema: Send + 'static,
+    CreateSchema: DeserializeOwned + Send + 'static,
+    <Source::Connection as Connection>::Backend: InsertReturning<Source::Connection, SchemaTable, CreateSchema, Schema>,
 {
-    async fn create_item(&mut self, new_item: CreateSchema) -> Schema {
-        diesel::insert_into(self.table)
-            .values(new_item)
-            .get_result(&mut self.connection)
-            .expect("Error creating item")
-            .into()
+    async fn create_item(&self, new_item: CreateSchema) -> Result<Schema, CrudError> {
+        let table = self.table;
+        self.run(move |connection| {
+            Ok(<Source::Connection as Connection>::Backend::insert_returning(connection, table, new_item)?)
+        }).await
+    }
+
+    async fn create_items(&self, new_items: Vec<CreateSchema>) -> Result<Vec<Schema>, CrudError> {
+        let table = self.table;
+        self.run(move |connection| {
+            Ok(<Source::Connection as Connection>::Backend::insert_all_returning(connection, table, new_items)?)
+        }).await
     }
 }
 
-impl<DBConnection, SchemaTable, Schema, PrimaryKeyType, UpdateSchema> UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> for DieselRepository<DBConnection, SchemaTable>
+impl<Source, SchemaTable, Schema, PrimaryKeyType, UpdateSchema> UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> for DieselRepository<Source, SchemaTable>
 where
-    DBConnection: Connection + LoadConnection + 'static,
-    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<DBConnection::Backend> + StaticQueryFragment + Table + QueryId + Copy + Send + 'static,
+    Source: ConnectionSource + Send + Sync + 'static,
+    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<<Source::Connection as Connection>::Backend> + StaticQueryFragment + Table + QueryId + Copy + Send + Sync + 'static,
 
-    PrimaryKeyType: Send + DeserializeOwned + 'static,
-    SchemaTable::PrimaryKey: EqAll<PrimaryKeyType>,
-
-    // for get_item
-    SchemaTable: LimitDsl + FindDsl<PrimaryKeyType>,
-    Find<SchemaTable, PrimaryKeyType>: LimitDsl,
-    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, DBConnection, Schema>,
+    PrimaryKeyType: Send + DeserializeOwned + Clone + 'static,
 
     // for update_item
-    UpdateSchema: DeserializeOwned + AsChangeset<Target=SchemaTable> + Send + 'static,
+    Schema: Send + 'static,
+    UpdateSchema: Serialize + DeserializeOwned + Send + 'static,
+    <Source::Connection as Connection>::Backend: UpdateReturning<Source::Connection, SchemaTable, PrimaryKeyType, UpdateSchema, Schema>,
+
+    // for update_item with a version column
+    Find<SchemaTable, PrimaryKeyType>: FilterDsl<FilterClause> + LimitDsl,
+    Filter<Find<SchemaTable, PrimaryKeyType>, FilterClause>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    for<'b> Update<Filter<Find<SchemaTable, PrimaryKeyType>, FilterClause>, &'b UpdateSchema>: AsQuery + ExecuteDsl<Source::Connection>,
+    Update<Find<SchemaTable, PrimaryKeyType>, VersionBump<SchemaTable>>: AsQuery + ExecuteDsl<Source::Connection>,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, Source::Connection, Schema>,
+
+    // for update_items
+    SchemaTable: FindDsl<PrimaryKeyType>,
     Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
-    for<'a> Update<Find<SchemaTable, PrimaryKeyType>, UpdateSchema>: AsQuery + LoadQuery<'a, DBConnection, Schema>,
+    for<'b> &'b UpdateSchema: AsChangeset<Target=SchemaTable>,
+    for<'b> Update<Find<SchemaTable, PrimaryKeyType>, &'b UpdateSchema>: AsQuery + ExecuteDsl<Source::Connection>,
 {
-    async fn update_item(&mut self, id: PrimaryKeyType, item: UpdateSchema) -> Schema {
-        diesel::update(self.table.find(id))
-            .set(item)
-            .get_result(&mut self.connection)
-            .expect("Error updating item")
+    async fn update_item(&self, id: PrimaryKeyType, item: UpdateSchema) -> Result<Schema, CrudError> {
+        let table = self.table;
+        let Some(version_column) = self.version_column else {
+            return self.run(move |connection| {
+                Ok(<Source::Connection as Connection>::Backend::update_returning(connection, table, id, item)?)
+            }).await;
+        };
+
+        let version = required_version(serde_json::to_value(&item)?.get(version_column), version_column)?;
+        self.run(move |connection| {
+            connection.transaction(|connection| {
+                if !update_at_version(connection, table, id.clone(), &item, version_column, version)? {
+                    return Err(CrudError::NotFound);
+                }
+                Ok(table.find(id).limit(1).get_result(connection)?)
+            })
+        }).await
+    }
+
+    async fn update_items(&self, ids: Vec<PrimaryKeyType>, item: UpdateSchema) -> Result<usize, CrudError> {
+        let table = self.table;
+        let Some(version_column) = self.version_column else {
+            return self.run(move |connection| {
+                Ok(connection.transaction(|connection| {
+                    ids.into_iter()
+                        .map(|id| diesel::update(table.find(id)).set(&item).execute(connection))
+                        .sum::<QueryResult<usize>>()
+                })?)
+            }).await;
+        };
+
+        // all items have to be at the version of the update
+        let version = required_version(serde_json::to_value(&item)?.get(version_column), version_column)?;
+        self.run(move |connection| {
+            connection.transaction(|connection| {
+                let mut updated = 0;
+                for id in ids {
+                    if update_at_version(connection, table, id, &item, version_column, version)? {
+                        updated += 1;
+                    }
+                }
+                Ok(updated)
+            })
+        }).await
+    }
+}
+
+/// Updates the item if it is still at `version` and bumps its version. Returns false for a missing
+/// item and fails with [CrudError::Conflict] if it has been modified.
+fn update_at_version<DBConnection, SchemaTable, Schema, PrimaryKeyType, UpdateSchema>(connection: &mut DBConnection, table: SchemaTable, id: PrimaryKeyType, item: &UpdateSchema, version_column: &'static str, version: i64) -> Result<bool, CrudError>
+where
+    DBConnection: Connection + LoadConnection,
+    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<DBConnection::Backend> + StaticQueryFragment + Table + QueryId + Copy,
+    SchemaTable: FindDsl<PrimaryKeyType>,
+    PrimaryKeyType: Clone,
+    Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget + FilterDsl<FilterClause> + LimitDsl,
+    Filter<Find<SchemaTable, PrimaryKeyType>, FilterClause>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    for<'b> &'b UpdateSchema: AsChangeset<Target=SchemaTable>,
+    for<'b> Update<Filter<Find<SchemaTable, PrimaryKeyType>, FilterClause>, &'b UpdateSchema>: AsQuery + ExecuteDsl<DBConnection>,
+    Update<Find<SchemaTable, PrimaryKeyType>, VersionBump<SchemaTable>>: AsQuery + ExecuteDsl<DBConnection>,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, DBConnection, Schema>,
+{
+    let guard = FilterClause::new(vec![
+        crate::Filter { field: version_column.to_string(), condition: FilterCondition::Eq(FilterValue::Integer(version)) }
+    ]);
+    let updated = diesel::update(table.find(id.clone()).filter(guard))
+        .set(item)
+        .execute(connection)?;
+    if updated == 0 {
+        // a missing item is not found rather than modified
+        return match table.find(id).limit(1).get_result::<Schema>(connection).optional()? {
+            Some(_) => Err(CrudError::Conflict("the item has been modified".to_string())),
+            None => Ok(false),
+        };
     }
-}
\ No newline at end of file
+
+    diesel::update(table.find(id))
+        .set(VersionBump::new(version_column))
+        .execute(connection)?;
+    Ok(true)
+}
+#[cfg(all(test, feature = "diesel-sqlite"))]
+mod tests {
+    use diesel::connection::SimpleConnection;
+    use diesel::sqlite::SqliteConnection;
+    use serde::Deserialize;
+    use super::*;
+
+    diesel::table! {
+        tags (name) {
+            name -> Text,
+            color -> Text,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
+    #[diesel(table_name = tags)]
+    struct Tag {
+        name: String,
+        color: String,
+    }
+
+    diesel::table! {
+        notes (id) {
+            id -> Integer,
+            body -> Text,
+            version -> Integer,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Queryable)]
+    #[diesel(table_name = notes)]
+    struct Note {
+        id: i32,
+        body: String,
+        version: i32,
+    }
+
+    #[derive(Serialize, Deserialize, AsChangeset)]
+    #[diesel(table_name = notes)]
+    struct NoteForm {
+        body: Option<String>,
+        version: Option<i32>,
+    }
+
+    diesel::table! {
+        drafts (id) {
+            id -> Integer,
+            title -> Nullable<Text>,
+        }
+    }
+
+    #[derive(Serialize, Queryable)]
+    #[diesel(table_name = drafts)]
+    struct Draft {
+        id: i32,
+        title: Option<String>,
+    }
+
+    fn tag(name: &str, color: &str) -> Tag {
+        Tag { name: name.to_string(), color: color.to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_create_with_text_keys() {
+        let mut connection = SqliteConnection::establish(":memory:").unwrap();
+        connection.batch_execute("CREATE TABLE tags (name TEXT PRIMARY KEY NOT NULL, color TEXT NOT NULL)").unwrap();
+        let repository = DieselRepository::new(connection, tags::table);
+
+        // the rows are selected by their names, not by `last_insert_rowid()`
+        let created: Vec<Tag> = CreateRepository::<Tag, Tag>::create_items(&repository, vec![tag("rust", "orange"), tag("diesel", "red")]).await.unwrap();
+        assert_eq!(created, vec![tag("rust", "orange"), tag("diesel", "red")]);
+
+        let created: Tag = CreateRepository::<Tag, Tag>::create_item(&repository, tag("axum", "blue")).await.unwrap();
+        assert_eq!(created, tag("axum", "blue"));
+        assert!(matches!(CreateRepository::<Tag, Tag>::create_item(&repository, tag("axum", "green")).await, Err(CrudError::Conflict(_))));
+    }
+
+    #[tokio::test]
+    async fn test_delete_item_at_version() {
+        let mut connection = SqliteConnection::establish(":memory:").unwrap();
+        connection.batch_execute("
+            CREATE TABLE notes (id INTEGER PRIMARY KEY NOT NULL, body TEXT NOT NULL, version INTEGER NOT NULL);
+            INSERT INTO notes (id, body, version) VALUES (1, 'First', 2), (2, 'Second', 0);
+        ").unwrap();
+        let repository = DieselRepository::new(connection, notes::table)
+            .version_column("version");
+
+        // a stale version leaves the note in place
+        let result = ReadDeleteRepository::<Note, i32>::delete_item_at_version(&repository, 1, 1).await;
+        assert!(matches!(result, Err(CrudError::Conflict(_))));
+        assert!(ReadDeleteRepository::<Note, i32>::get_item(&repository, 1).await.unwrap().is_some());
+
+        ReadDeleteRepository::<Note, i32>::delete_item_at_version(&repository, 1, 2).await.unwrap();
+        assert_eq!(ReadDeleteRepository::<Note, i32>::get_item(&repository, 1).await.unwrap(), None);
+        assert_eq!(ReadDeleteRepository::<Note, i32>::delete_item_at_version(&repository, 1, 2).await, Err(CrudError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn test_update_items_at_version() {
+        let mut connection = SqliteConnection::establish(":memory:").unwrap();
+        connection.batch_execute("
+            CREATE TABLE notes (id INTEGER PRIMARY KEY NOT NULL, body TEXT NOT NULL, version INTEGER NOT NULL);
+            INSERT INTO notes (id, body, version) VALUES (1, 'First', 0), (2, 'Second', 1), (3, 'Third', 0);
+        ").unwrap();
+        let repository = DieselRepository::new(connection, notes::table)
+            .version_column("version");
+        let form = |version| NoteForm { body: Some("Updated".to_string()), version };
+
+        let result = UpdateRepository::<Note, i32, NoteForm>::update_items(&repository, vec![1, 3], form(None)).await;
+        assert!(matches!(result, Err(CrudError::PreconditionRequired(_))));
+
+        // one modified note fails the whole batch
+        let result = UpdateRepository::<Note, i32, NoteForm>::update_items(&repository, vec![1, 2, 3], form(Some(0))).await;
+        assert!(matches!(result, Err(CrudError::Conflict(_))));
+        assert_eq!(ReadDeleteRepository::<Note, i32>::get_item(&repository, 1).await.unwrap().unwrap().body, "First");
+
+        // missing notes are skipped and the updated ones are bumped
+        assert_eq!(UpdateRepository::<Note, i32, NoteForm>::update_items(&repository, vec![1, 3, 4], form(Some(0))).await, Ok(2));
+        let note = ReadDeleteRepository::<Note, i32>::get_item(&repository, 3).await.unwrap().unwrap();
+        assert_eq!(note, Note { id: 3, body: "Updated".to_string(), version: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_cursor_with_null_sort_keys() {
+        let mut connection = SqliteConnection::establish(":memory:").unwrap();
+        connection.batch_execute("
+            CREATE TABLE drafts (id INTEGER PRIMARY KEY NOT NULL, title TEXT);
+            INSERT INTO drafts (id, title) VALUES (1, 'b'), (2, NULL), (3, 'a'), (4, NULL);
+        ").unwrap();
+        let repository = DieselRepository::new(connection, drafts::table);
+
+        for (descending, expected) in [(false, vec![2, 4, 3, 1]), (true, vec![1, 3, 2, 4])] {
+            let sort = vec![crate::Sort { field: String::from("title"), descending }];
+            let mut ids = Vec::new();
+            let mut cursor = None;
+            loop {
+                let page: Vec<Draft> = ReadDeleteRepository::<Draft, i32>::list_items_after(&repository, cursor, Some(1), vec![], sort.clone()).await.unwrap();
+                let Some(draft) = page.last() else { break };
+                ids.push(draft.id);
+                cursor = Some(crate::Cursor::after(draft, &sort, "id").unwrap());
+            }
+            assert_eq!(ids, expected);
+        }
+    }
+
+    #[test]
+    fn test_filter_casts() {
+        let filters = vec![
+            crate::Filter { field: String::from("title"), condition: FilterCondition::In(vec![FilterValue::Integer(1), FilterValue::Integer(2)]) },
+            crate::Filter { field: String::from("id"), condition: FilterCondition::Gt(FilterValue::Integer(7)) },
+        ];
+        let query = FilterDsl::filter(drafts::table, FilterClause::new(filters).cast(&[("title", "TEXT")]));
+
+        let sql = diesel::debug_query::<diesel::sqlite::Sqlite, _>(&query).to_string();
+        assert!(sql.contains("`title` IN (CAST(? AS TEXT), CAST(? AS TEXT)) AND `id` > ?"), "{}", sql);
+    }
+}
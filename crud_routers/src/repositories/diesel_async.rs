@@ -0,0 +1,513 @@
+use std::ops::DerefMut;
+use diesel::associations::HasTable;
+use diesel::dsl::{count_star, Filter as FilterBy};
+use diesel::expression_methods::EqAll;
+use diesel::helper_types::{delete, Find, Limit, Update};
+use diesel::internal::table_macro::{FromClause, SelectStatement, StaticQueryFragment};
+use diesel::query_builder::{AsChangeset, AsQuery, InsertStatement, IntoUpdateTarget, QueryFragment, QueryId};
+use diesel::query_dsl::filter_dsl::{FilterDsl, FindDsl};
+use diesel::query_dsl::methods::{BoxedDsl, LimitDsl, OffsetDsl, OrderDsl, SelectDsl, ThenOrderDsl};
+use diesel::serialize::ToSql;
+use diesel::sql_types::{BigInt, Bool, Double, HasSqlType, Text};
+use diesel::{AppearsOnTable, Column, Insertable, OptionalExtension, Table};
+use diesel_async::methods::{ExecuteDsl, LoadQuery};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use crate::{Cursor, Filter, FilterCondition, FilterValue, Pagination, Sort};
+use crate::repositories::diesel::{BoxedCountQuery, BoxedFilter, BoxedTableQuery, FilterClause, KeysetClause, SortClause, VersionBump};
+use crate::repositories::{required_version, signed, CRUDRepository, CreateRepository, CrudError, ReadDeleteRepository, UpdateRepository};
+
+/// Provides the connections an [AsyncDieselRepository] runs its queries on.
+pub trait AsyncConnectionSource {
+    type Connection: AsyncConnection + 'static;
+    type Guard<'a>: DerefMut<Target = Self::Connection> + Send where Self: 'a;
+
+    fn connection(&self) -> impl std::future::Future<Output = Result<Self::Guard<'_>, CrudError>> + Send;
+}
+
+/// A single connection shared by all requests.
+impl<DBConnection> AsyncConnectionSource for Mutex<DBConnection>
+where
+    DBConnection: AsyncConnection + 'static,
+{
+    type Connection = DBConnection;
+    type Guard<'a> = tokio::sync::MutexGuard<'a, DBConnection>;
+
+    async fn connection(&self) -> Result<Self::Guard<'_>, CrudError> {
+        Ok(self.lock().await)
+    }
+}
+
+/// Checks out a connection from the deadpool pool for every call.
+#[cfg(feature = "deadpool")]
+impl<DBConnection> AsyncConnectionSource for diesel_async::pooled_connection::deadpool::Pool<DBConnection>
+where
+    DBConnection: diesel_async::pooled_connection::PoolableConnection + 'static,
+    diesel_async::pooled_connection::AsyncDieselConnectionManager<DBConnection>: deadpool::managed::Manager<Type = DBConnection, Error = diesel_async::pooled_connection::PoolError>,
+{
+    type Connection = DBConnection;
+    type Guard<'a> = diesel_async::pooled_connection::deadpool::Object<DBConnection>;
+
+    async fn connection(&self) -> Result<Self::Guard<'_>, CrudError> {
+        self.get().await
+            .map_err(|e| CrudError::Connection(e.to_string()))
+    }
+}
+
+/// Checks out a connection from the bb8 pool for every call.
+#[cfg(feature = "bb8")]
+impl<DBConnection> AsyncConnectionSource for diesel_async::pooled_connection::bb8::Pool<DBConnection>
+where
+    DBConnection: diesel_async::pooled_connection::PoolableConnection + 'static,
+    diesel_async::pooled_connection::AsyncDieselConnectionManager<DBConnection>: bb8::ManageConnection<Connection = DBConnection, Error = diesel_async::pooled_connection::PoolError>,
+{
+    type Connection = DBConnection;
+    type Guard<'a> = diesel_async::pooled_connection::bb8::PooledConnection<'a, DBConnection>;
+
+    async fn connection(&self) -> Result<Self::Guard<'_>, CrudError> {
+        self.get().await
+            .map_err(|e| CrudError::Connection(e.to_string()))
+    }
+}
+
+pub struct AsyncDieselRepository<Source, SchemaTable> {
+    connection: Source,
+    table: SchemaTable,
+    version_column: Option<&'static str>,
+    column_types: &'static [(&'static str, &'static str)]
+}
+
+impl<DBConnection, SchemaTable> AsyncDieselRepository<Mutex<DBConnection>, SchemaTable>
+where
+    SchemaTable: Table,
+{
+    pub fn new(connection: DBConnection, table: SchemaTable) -> Self{
+        Self{
+            connection: Mutex::new(connection),
+            table,
+            version_column: None,
+            column_types: &[]
+        }
+    }
+}
+
+impl<Source, SchemaTable> AsyncDieselRepository<Source, SchemaTable>
+where
+    Source: AsyncConnectionSource,
+    SchemaTable: Table,
+{
+    pub fn from_pool(pool: Source, table: SchemaTable) -> Self{
+        Self{
+            connection: pool,
+            table,
+            version_column: None,
+            column_types: &[]
+        }
+    }
+}
+
+impl<Source, SchemaTable> AsyncDieselRepository<Source, SchemaTable> {
+    /// Enables optimistic locking on an integer column, like [crate::DieselRepository::version_column].
+    pub fn version_column(self, version_column: &'static str) -> Self{
+        Self{
+            version_column: Some(version_column),
+            ..self
+        }
+    }
+
+    /// Casts filter and cursor values to the SQL types of these columns, like [crate::DieselRepository::column_types].
+    pub fn column_types(self, column_types: &'static [(&'static str, &'static str)]) -> Self{
+        Self{
+            column_types,
+            ..self
+        }
+    }
+}
+
+impl<Source, SchemaTable> CRUDRepository for AsyncDieselRepository<Source, SchemaTable> {
+    fn get_version_column(&self) -> Option<&'static str> {
+        self.version_column
+    }
+}
+
+/// `version_column = version`, the guard of the writes of a repository with a version column.
+fn version_guard(version_column: &'static str, version: i64) -> FilterClause {
+    FilterClause::new(vec![
+        Filter { field: version_column.to_string(), condition: FilterCondition::Eq(FilterValue::Integer(version)) }
+    ])
+}
+
+impl<Source, SchemaTable, Schema, PrimaryKeyType> ReadDeleteRepository<Schema, PrimaryKeyType> for AsyncDieselRepository<Source, SchemaTable>
+where
+    Source: AsyncConnectionSource + Sync,
+    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<<Source::Connection as AsyncConnection>::Backend> + StaticQueryFragment<Component=diesel::internal::table_macro::Identifier<'static>> + Table + QueryId + Copy + Send + Sync + 'static,
+
+    PrimaryKeyType: Send + Sync + DeserializeOwned + Clone + 'static,
+    SchemaTable::PrimaryKey: EqAll<PrimaryKeyType>,
+
+    // for list_items
+    Schema: Serialize + Send + 'static,
+    <Source::Connection as AsyncConnection>::Backend: HasSqlType<Bool> + HasSqlType<BigInt> + HasSqlType<Double> + HasSqlType<Text>,
+    bool: ToSql<Bool, <Source::Connection as AsyncConnection>::Backend>,
+    i64: ToSql<BigInt, <Source::Connection as AsyncConnection>::Backend>,
+    f64: ToSql<Double, <Source::Connection as AsyncConnection>::Backend>,
+    String: ToSql<Text, <Source::Connection as AsyncConnection>::Backend>,
+    SchemaTable: BoxedDsl<'static, <Source::Connection as AsyncConnection>::Backend, Output = BoxedTableQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>>,
+    BoxedTableQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>: FilterDsl<BoxedFilter<SchemaTable, <Source::Connection as AsyncConnection>::Backend>, Output = BoxedTableQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>> + LimitDsl<Output = BoxedTableQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>> + OffsetDsl<Output = BoxedTableQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>>,
+    for<'a> BoxedTableQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>: LoadQuery<'a, Source::Connection, Schema>,
+    SchemaTable::PrimaryKey: Column + QueryFragment<<Source::Connection as AsyncConnection>::Backend> + AppearsOnTable<SchemaTable> + Send,
+
+    // for count_items
+    BoxedTableQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>: SelectDsl<count_star, Output = BoxedCountQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>>,
+    for<'a> BoxedCountQuery<SchemaTable, <Source::Connection as AsyncConnection>::Backend>: LoadQuery<'a, Source::Connection, i64> + Send,
+    // the boxed query is held across an await
+    <SchemaTable as AsQuery>::SqlType: Send,
+    FromClause<SchemaTable>: Send,
+
+    // for get_item
+    SchemaTable: LimitDsl + FindDsl<PrimaryKeyType>,
+    Find<SchemaTable, PrimaryKeyType>: LimitDsl,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, Source::Connection, Schema> + Send,
+
+    // for delete_item
+    Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    delete<Find<SchemaTable, PrimaryKeyType>>: ExecuteDsl<Source::Connection> + Send,
+
+    // for delete_item_at_version
+    Find<SchemaTable, PrimaryKeyType>: FilterDsl<FilterClause>,
+    FilterBy<Find<SchemaTable, PrimaryKeyType>, FilterClause>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    delete<FilterBy<Find<SchemaTable, PrimaryKeyType>, FilterClause>>: ExecuteDsl<Source::Connection> + Send,
+
+    // for delete_all_items
+    SchemaTable: FilterDsl<FilterClause>,
+    diesel::dsl::Filter<SchemaTable, FilterClause>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    delete<diesel::dsl::Filter<SchemaTable, FilterClause>>: ExecuteDsl<Source::Connection> + Send
+
+{
+    fn get_table_name() -> String {
+        <SchemaTable as StaticQueryFragment>::STATIC_COMPONENT.0.to_string()
+    }
+
+    fn get_primary_key_name(&self) -> String {
+        <SchemaTable::PrimaryKey as Column>::NAME.to_string()
+    }
+
+    async fn list_items(&self, pagination: Pagination, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let mut query = diesel::QueryDsl::into_boxed(self.table);
+        if !filters.is_empty() {
+            let filter: BoxedFilter<SchemaTable, _> = Box::new(FilterClause::new(filters).cast(self.column_types));
+            query = FilterDsl::filter(query, filter);
+        }
+        // ties and unsorted lists are ordered by the primary key
+        query = if sort.is_empty() {
+            OrderDsl::order(query, self.table.primary_key())
+        } else {
+            ThenOrderDsl::then_order_by(OrderDsl::order(query, SortClause(sort)), self.table.primary_key())
+        };
+        if let Some(limit) = pagination.limit {
+            query = LimitDsl::limit(query, signed(limit)?);
+        }
+        if let Some(skip) = pagination.skip {
+            query = OffsetDsl::offset(query, signed(skip)?);
+        }
+
+        let mut connection = self.connection.connection().await?;
+        Ok(query.load::<Schema>(&mut *connection).await?)
+    }
+
+    async fn list_items_after(&self, cursor: Option<Cursor>, limit: Option<u64>, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let mut query = diesel::QueryDsl::into_boxed(self.table);
+        if !filters.is_empty() {
+            let filter: BoxedFilter<SchemaTable, _> = Box::new(FilterClause::new(filters).cast(self.column_types));
+            query = FilterDsl::filter(query, filter);
+        }
+        if let Some(cursor) = cursor {
+            let keyset: BoxedFilter<SchemaTable, _> = Box::new(KeysetClause::new(cursor.keyset(&sort, <SchemaTable::PrimaryKey as Column>::NAME), self.column_types));
+            query = FilterDsl::filter(query, keyset);
+        }
+        query = if sort.is_empty() {
+            OrderDsl::order(query, self.table.primary_key())
+        } else {
+            ThenOrderDsl::then_order_by(OrderDsl::order(query, SortClause(sort)), self.table.primary_key())
+        };
+        if let Some(limit) = limit {
+            query = LimitDsl::limit(query, signed(limit)?);
+        }
+
+        let mut connection = self.connection.connection().await?;
+        Ok(query.load::<Schema>(&mut *connection).await?)
+    }
+
+    async fn count_items(&self, filters: Vec<Filter>) -> Result<u64, CrudError> {
+        let mut query = diesel::QueryDsl::into_boxed(self.table);
+        if !filters.is_empty() {
+            let filter: BoxedFilter<SchemaTable, _> = Box::new(FilterClause::new(filters).cast(self.column_types));
+            query = FilterDsl::filter(query, filter);
+        }
+
+        let mut connection = self.connection.connection().await?;
+        Ok(SelectDsl::select(query, count_star()).get_result::<i64>(&mut *connection).await? as u64)
+    }
+
+    async fn get_item(&self, id: PrimaryKeyType) -> Result<Option<Schema>, CrudError> {
+        let mut connection = self.connection.connection().await?;
+
+        Ok(self.table
+            .find(id)
+            .limit(1)
+            .get_result::<Schema>(&mut *connection).await
+            .optional()?)
+    }
+
+    async fn delete_item(&self, id: PrimaryKeyType) -> Result<(), CrudError> {
+        let mut connection = self.connection.connection().await?;
+
+        let deleted = diesel::delete(self.table.find(id))
+            .execute(&mut *connection).await?;
+
+        if deleted == 0 {
+            return Err(CrudError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete_item_at_version(&self, id: PrimaryKeyType, version: i64) -> Result<(), CrudError> {
+        let Some(version_column) = self.version_column else {
+            return ReadDeleteRepository::<Schema, PrimaryKeyType>::delete_item(self, id).await;
+        };
+        let mut connection = self.connection.connection().await?;
+        let table = self.table;
+
+        connection.transaction::<_, CrudError, _>(|connection| async move {
+            let deleted = diesel::delete(table.find(id.clone()).filter(version_guard(version_column, version)))
+                .execute(connection).await?;
+            if deleted == 0 {
+                // a missing item is not found rather than modified
+                table.find(id).limit(1).get_result::<Schema>(connection).await?;
+                return Err(CrudError::Conflict("the item has been modified".to_string()));
+            }
+            Ok(())
+        }.scope_boxed()).await
+    }
+
+    async fn delete_items(&self, ids: Vec<PrimaryKeyType>) -> Result<usize, CrudError> {
+        let mut connection = self.connection.connection().await?;
+        let table = self.table;
+
+        Ok(connection.transaction::<_, diesel::result::Error, _>(|connection| async move {
+            let mut deleted = 0;
+            for id in ids {
+                deleted += diesel::delete(table.find(id)).execute(connection).await?;
+            }
+            Ok(deleted)
+        }.scope_boxed()).await?)
+    }
+
+    async fn delete_all_items(&self, filters: Vec<Filter>) -> Result<usize, CrudError> {
+        let mut connection = self.connection.connection().await?;
+
+        Ok(diesel::delete(FilterDsl::filter(self.table, FilterClause::new(filters).cast(self.column_types)))
+            .execute(&mut *connection).await?)
+    }
+}
+
+impl<Source, SchemaTable, Schema, CreateSchema> CreateRepository<Schema, CreateSchema> for AsyncDieselRepository<Source, SchemaTable>
+where
+    Source: AsyncConnectionSource + Sync,
+    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<<Source::Connection as AsyncConnection>::Backend> + StaticQueryFragment + Table + QueryId + Copy + Send + Sync + 'static,
+
+    // for create_item
+    Schema: Send + 'static,
+    CreateSchema: DeserializeOwned + Insertable<SchemaTable> + Send + 'static,
+    for<'a> InsertStatement<SchemaTable, CreateSchema::Values>: AsQuery + LoadQuery<'a, Source::Connection, Schema> + Send,
+
+    // for create_items
+    Vec<CreateSchema>: Insertable<SchemaTable>,
+    for<'a> InsertStatement<SchemaTable, <Vec<CreateSchema> as Insertable<SchemaTable>>::Values>: AsQuery + LoadQuery<'a, Source::Connection, Schema> + Send,
+{
+    async fn create_item(&self, new_item: CreateSchema) -> Result<Schema, CrudError> {
+        let mut connection = self.connection.connection().await?;
+
+        Ok(diesel::insert_into(self.table)
+            .values(new_item)
+            .get_result(&mut *connection).await?)
+    }
+
+    async fn create_items(&self, new_items: Vec<CreateSchema>) -> Result<Vec<Schema>, CrudError> {
+        let mut connection = self.connection.connection().await?;
+
+        Ok(diesel::insert_into(self.table)
+            .values(new_items)
+            .get_results(&mut *connection).await?)
+    }
+}
+
+impl<Source, SchemaTable, Schema, PrimaryKeyType, UpdateSchema> UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> for AsyncDieselRepository<Source, SchemaTable>
+where
+    Source: AsyncConnectionSource + Sync,
+    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<<Source::Connection as AsyncConnection>::Backend> + StaticQueryFragment + Table + QueryId + Copy + Send + Sync + 'static,
+
+    PrimaryKeyType: Send + Sync + DeserializeOwned + Clone + 'static,
+    SchemaTable::PrimaryKey: EqAll<PrimaryKeyType>,
+
+    // for update_item
+    Schema: Send + 'static,
+    UpdateSchema: Serialize + DeserializeOwned + AsChangeset<Target=SchemaTable> + Send + 'static,
+    SchemaTable: FindDsl<PrimaryKeyType>,
+    Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    for<'a> Update<Find<SchemaTable, PrimaryKeyType>, UpdateSchema>: AsQuery + LoadQuery<'a, Source::Connection, Schema> + Send,
+
+    // for update_items
+    UpdateSchema: Sync,
+    for<'b> &'b UpdateSchema: AsChangeset<Target=SchemaTable>,
+    for<'b> Update<Find<SchemaTable, PrimaryKeyType>, &'b UpdateSchema>: AsQuery + ExecuteDsl<Source::Connection> + Send,
+
+    // with a version column
+    Find<SchemaTable, PrimaryKeyType>: FilterDsl<FilterClause> + LimitDsl,
+    FilterBy<Find<SchemaTable, PrimaryKeyType>, FilterClause>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    for<'b> Update<FilterBy<Find<SchemaTable, PrimaryKeyType>, FilterClause>, &'b UpdateSchema>: AsQuery + ExecuteDsl<Source::Connection> + Send,
+    Update<Find<SchemaTable, PrimaryKeyType>, VersionBump<SchemaTable>>: AsQuery + ExecuteDsl<Source::Connection> + Send,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, Source::Connection, Schema> + Send,
+{
+    async fn update_item(&self, id: PrimaryKeyType, item: UpdateSchema) -> Result<Schema, CrudError> {
+        let mut connection = self.connection.connection().await?;
+        let table = self.table;
+        let Some(version_column) = self.version_column else {
+            return Ok(diesel::update(table.find(id))
+                .set(item)
+                .get_result(&mut *connection).await?);
+        };
+
+        let version = required_version(serde_json::to_value(&item)?.get(version_column), version_column)?;
+        connection.transaction::<_, CrudError, _>(|connection| async move {
+            if !update_at_version(connection, table, id.clone(), &item, version_column, version).await? {
+                return Err(CrudError::NotFound);
+            }
+            Ok(table.find(id).limit(1).get_result(connection).await?)
+        }.scope_boxed()).await
+    }
+
+    async fn update_items(&self, ids: Vec<PrimaryKeyType>, item: UpdateSchema) -> Result<usize, CrudError> {
+        let mut connection = self.connection.connection().await?;
+        let table = self.table;
+        let Some(version_column) = self.version_column else {
+            return Ok(connection.transaction::<_, diesel::result::Error, _>(|connection| async move {
+                let mut updated = 0;
+                for id in ids {
+                    updated += diesel::update(table.find(id)).set(&item).execute(connection).await?;
+                }
+                Ok(updated)
+            }.scope_boxed()).await?);
+        };
+
+        // all items have to be at the version of the update
+        let version = required_version(serde_json::to_value(&item)?.get(version_column), version_column)?;
+        connection.transaction::<_, CrudError, _>(|connection| async move {
+            let mut updated = 0;
+            for id in ids {
+                if update_at_version(connection, table, id, &item, version_column, version).await? {
+                    updated += 1;
+                }
+            }
+            Ok(updated)
+        }.scope_boxed()).await
+    }
+}
+
+/// Updates the item if it is still at `version` and bumps its version. Returns false for a missing
+/// item and fails with [CrudError::Conflict] if it has been modified.
+async fn update_at_version<DBConnection, SchemaTable, Schema, PrimaryKeyType, UpdateSchema>(connection: &mut DBConnection, table: SchemaTable, id: PrimaryKeyType, item: &UpdateSchema, version_column: &'static str, version: i64) -> Result<bool, CrudError>
+where
+    DBConnection: AsyncConnection,
+    SchemaTable: AsQuery<Query=SelectStatement<FromClause<SchemaTable>>> + QueryFragment<DBConnection::Backend> + StaticQueryFragment + Table + QueryId + Copy + Send,
+    SchemaTable: FindDsl<PrimaryKeyType>,
+    PrimaryKeyType: Clone,
+    Schema: Send,
+    Find<SchemaTable, PrimaryKeyType>: HasTable<Table=SchemaTable> + IntoUpdateTarget + FilterDsl<FilterClause> + LimitDsl,
+    FilterBy<Find<SchemaTable, PrimaryKeyType>, FilterClause>: HasTable<Table=SchemaTable> + IntoUpdateTarget,
+    for<'b> &'b UpdateSchema: AsChangeset<Target=SchemaTable>,
+    for<'b> Update<FilterBy<Find<SchemaTable, PrimaryKeyType>, FilterClause>, &'b UpdateSchema>: AsQuery + ExecuteDsl<DBConnection> + Send,
+    Update<Find<SchemaTable, PrimaryKeyType>, VersionBump<SchemaTable>>: AsQuery + ExecuteDsl<DBConnection> + Send,
+    for<'a> Limit<Find<SchemaTable, PrimaryKeyType>>: LoadQuery<'a, DBConnection, Schema> + Send,
+{
+    let updated = diesel::update(table.find(id.clone()).filter(version_guard(version_column, version)))
+        .set(item)
+        .execute(connection).await?;
+    if updated == 0 {
+        // a missing item is not found rather than modified
+        return match table.find(id).limit(1).get_result::<Schema>(connection).await.optional()? {
+            Some(_) => Err(CrudError::Conflict("the item has been modified".to_string())),
+            None => Ok(false),
+        };
+    }
+
+    diesel::update(table.find(id))
+        .set(VersionBump::new(version_column))
+        .execute(connection).await?;
+    Ok(true)
+}
+
+#[cfg(all(test, feature = "diesel-sqlite"))]
+mod tests {
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use diesel_async::SimpleAsyncConnection;
+    use diesel_async::sync_connection_wrapper::SyncConnectionWrapper;
+    use serde::Deserialize;
+    use super::*;
+
+    diesel::table! {
+        notes (id) {
+            id -> Integer,
+            body -> Text,
+            version -> Integer,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Queryable)]
+    #[diesel(table_name = notes)]
+    struct Note {
+        id: i32,
+        body: String,
+        version: i32,
+    }
+
+    #[derive(Serialize, Deserialize, AsChangeset)]
+    #[diesel(table_name = notes)]
+    struct NoteForm {
+        body: Option<String>,
+        version: Option<i32>,
+    }
+
+    #[tokio::test]
+    async fn test_version_column() {
+        let mut connection = SyncConnectionWrapper::<SqliteConnection>::establish(":memory:").await.unwrap();
+        connection.batch_execute("
+            CREATE TABLE notes (id INTEGER PRIMARY KEY NOT NULL, body TEXT NOT NULL, version INTEGER NOT NULL);
+            INSERT INTO notes (id, body, version) VALUES (1, 'First', 0), (2, 'Second', 1), (3, 'Third', 0);
+        ").await.unwrap();
+        let repository = AsyncDieselRepository::new(connection, notes::table)
+            .version_column("version");
+        let form = |version| NoteForm { body: Some("Updated".to_string()), version };
+
+        let result = UpdateRepository::<Note, i32, NoteForm>::update_item(&repository, 1, form(None)).await;
+        assert!(matches!(result, Err(CrudError::PreconditionRequired(_))));
+        let note = UpdateRepository::<Note, i32, NoteForm>::update_item(&repository, 1, form(Some(0))).await.unwrap();
+        assert_eq!(note, Note { id: 1, body: "Updated".to_string(), version: 1 });
+        let result = UpdateRepository::<Note, i32, NoteForm>::update_item(&repository, 1, form(Some(0))).await;
+        assert!(matches!(result, Err(CrudError::Conflict(_))));
+
+        // one modified note fails the whole batch, missing notes are skipped
+        let result = UpdateRepository::<Note, i32, NoteForm>::update_items(&repository, vec![2, 3], form(Some(0))).await;
+        assert!(matches!(result, Err(CrudError::Conflict(_))));
+        assert_eq!(UpdateRepository::<Note, i32, NoteForm>::update_items(&repository, vec![3, 4], form(Some(0))).await, Ok(1));
+
+        let result = ReadDeleteRepository::<Note, i32>::delete_item_at_version(&repository, 2, 0).await;
+        assert!(matches!(result, Err(CrudError::Conflict(_))));
+        ReadDeleteRepository::<Note, i32>::delete_item_at_version(&repository, 2, 1).await.unwrap();
+        assert_eq!(ReadDeleteRepository::<Note, i32>::delete_item_at_version(&repository, 2, 1).await, Err(CrudError::NotFound));
+    }
+}
@@ -0,0 +1,383 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{PoisonError, RwLock};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::{Cursor, Filter, FilterCondition, FilterValue, Pagination, Sort};
+use crate::repositories::{CRUDRepository, CreateRepository, CrudError, ReadDeleteRepository, UpdateRepository};
+
+/// Generates the primary keys of items created in an [InMemoryRepository].
+pub trait IdGenerator<PrimaryKeyType>: Send + Sync {
+    fn next_id(&self) -> Result<PrimaryKeyType, CrudError>;
+}
+
+impl<PrimaryKeyType, F> IdGenerator<PrimaryKeyType> for F
+where
+    F: Fn() -> PrimaryKeyType + Send + Sync,
+{
+    fn next_id(&self) -> Result<PrimaryKeyType, CrudError> {
+        Ok(self())
+    }
+}
+
+/// Counts up from 1, like an auto increment column.
+#[derive(Debug, Default)]
+pub struct SequentialIdGenerator {
+    last_id: AtomicU64
+}
+
+impl<PrimaryKeyType: TryFrom<u64>> IdGenerator<PrimaryKeyType> for SequentialIdGenerator {
+    fn next_id(&self) -> Result<PrimaryKeyType, CrudError> {
+        let id = self.last_id.fetch_add(1, Ordering::Relaxed) + 1;
+        PrimaryKeyType::try_from(id)
+            .map_err(|_| CrudError::Backend(format!("id {} is out of range", id)))
+    }
+}
+
+/// Keeps the items in an ordered map, for prototyping and tests.
+///
+/// Create and update bodies are merged into `Schema` through their JSON representation,
+/// and the generated id is written to the `primary_key` field (`id` by default) unless the
+/// create body already has one.
+pub struct InMemoryRepository<Schema, PrimaryKeyType> {
+    items: RwLock<BTreeMap<PrimaryKeyType, Schema>>,
+    id_generator: Box<dyn IdGenerator<PrimaryKeyType>>,
+    primary_key: &'static str
+}
+
+impl<Schema, PrimaryKeyType> InMemoryRepository<Schema, PrimaryKeyType>
+where
+    PrimaryKeyType: TryFrom<u64>,
+{
+    pub fn new() -> Self{
+        Self::with_id_generator(SequentialIdGenerator::default())
+    }
+}
+
+impl<Schema, PrimaryKeyType> Default for InMemoryRepository<Schema, PrimaryKeyType>
+where
+    PrimaryKeyType: TryFrom<u64>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Schema, PrimaryKeyType> InMemoryRepository<Schema, PrimaryKeyType> {
+    pub fn with_id_generator(id_generator: impl IdGenerator<PrimaryKeyType> + 'static) -> Self{
+        Self{
+            items: RwLock::new(BTreeMap::new()),
+            id_generator: Box::new(id_generator),
+            primary_key: "id"
+        }
+    }
+
+    pub fn primary_key(self, primary_key: &'static str) -> Self{
+        Self{
+            primary_key,
+            ..self
+        }
+    }
+
+    /// The primary key of a new item, a key in its fields is kept like a natural key of a table
+    /// and a missing one is generated.
+    fn assign_id(&self, fields: &mut Map<String, Value>) -> Result<PrimaryKeyType, CrudError>
+    where
+        PrimaryKeyType: Serialize + DeserializeOwned,
+    {
+        match fields.get(self.primary_key) {
+            Some(id) if !id.is_null() => Ok(serde_json::from_value(id.clone())?),
+            _ => {
+                let id = self.id_generator.next_id()?;
+                fields.insert(self.primary_key.to_string(), serde_json::to_value(&id)?);
+                Ok(id)
+            }
+        }
+    }
+}
+
+/// Compares a JSON field with a filter value, `None` when their types don't match.
+fn compare(field: &Value, value: &FilterValue) -> Option<CmpOrdering> {
+    match (field, value) {
+        (Value::Bool(field), FilterValue::Bool(value)) => Some(field.cmp(value)),
+        (Value::Number(field), FilterValue::Integer(value)) => match field.as_i64() {
+            Some(field) => Some(field.cmp(value)),
+            None => field.as_f64()?.partial_cmp(&(*value as f64)),
+        },
+        (Value::Number(field), FilterValue::Float(value)) => field.as_f64()?.partial_cmp(value),
+        (Value::String(field), FilterValue::String(value)) => Some(field.as_str().cmp(value)),
+        _ => None,
+    }
+}
+
+/// Orders JSON fields for sorting, nulls come first.
+fn compare_fields(a: &Value, b: &Value) -> CmpOrdering {
+    match (a, b) {
+        (Value::Null, Value::Null) => CmpOrdering::Equal,
+        (Value::Null, _) => CmpOrdering::Less,
+        (_, Value::Null) => CmpOrdering::Greater,
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(CmpOrdering::Equal),
+        },
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => CmpOrdering::Equal,
+    }
+}
+
+/// Evaluates the filter like a database would, so null fields only match `is_null`.
+fn matches(item: &Value, filter: &Filter) -> bool {
+    let field = item.get(&filter.field).unwrap_or(&Value::Null);
+
+    match &filter.condition {
+        FilterCondition::Eq(value) => compare(field, value) == Some(CmpOrdering::Equal),
+        FilterCondition::Ne(value) => compare(field, value).is_some_and(|o| o != CmpOrdering::Equal),
+        FilterCondition::Lt(value) => compare(field, value) == Some(CmpOrdering::Less),
+        FilterCondition::Gt(value) => compare(field, value) == Some(CmpOrdering::Greater),
+        FilterCondition::In(values) => values.iter().any(|value| compare(field, value) == Some(CmpOrdering::Equal)),
+        FilterCondition::Contains(text) => field.as_str().is_some_and(|field| field.contains(text.as_str())),
+        FilterCondition::IsNull(is_null) => field.is_null() == *is_null,
+    }
+}
+
+impl<Schema, PrimaryKeyType> InMemoryRepository<Schema, PrimaryKeyType>
+where
+    Schema: Serialize + Clone,
+{
+    /// The items matching all filters and one alternative of the keyset, in list order.
+    fn select(&self, filters: &[Filter], keyset: &[Vec<Filter>], sort: &[Sort]) -> Result<Vec<Schema>, CrudError> {
+        let items = self.items.read().unwrap_or_else(PoisonError::into_inner);
+
+        let mut matching = Vec::new();
+        for item in items.values() {
+            let json = if filters.is_empty() && keyset.is_empty() && sort.is_empty() {
+                Value::Null
+            } else {
+                serde_json::to_value(item)?
+            };
+            if filters.iter().all(|filter| matches(&json, filter))
+                && (keyset.is_empty() || keyset.iter().any(|filters| filters.iter().all(|filter| matches(&json, filter)))) {
+                matching.push((json, item));
+            }
+        }
+
+        // the map iterates in primary key order, which the stable sort keeps for ties
+        matching.sort_by(|(a, _), (b, _)| {
+            sort.iter()
+                .map(|sort| {
+                    let ordering = compare_fields(a.get(&sort.field).unwrap_or(&Value::Null), b.get(&sort.field).unwrap_or(&Value::Null));
+                    if sort.descending { ordering.reverse() } else { ordering }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(CmpOrdering::Equal)
+        });
+
+        Ok(matching.into_iter()
+            .map(|(_, item)| item.clone())
+            .collect())
+    }
+
+    /// Writes the serialized fields of an update over the fields of `existing`, `null` included.
+    /// The primary key is never changed.
+    fn apply_changes(&self, existing: &Schema, changes: &Map<String, Value>) -> Result<Schema, CrudError>
+    where
+        Schema: DeserializeOwned,
+    {
+        let Value::Object(mut fields) = serde_json::to_value(existing)? else {
+            return Err(CrudError::Backend("stored item is not a JSON object".to_string()));
+        };
+        for (field, value) in changes {
+            if field != self.primary_key {
+                fields.insert(field.clone(), value.clone());
+            }
+        }
+
+        Ok(serde_json::from_value(Value::Object(fields))?)
+    }
+}
+
+impl<Schema, PrimaryKeyType> CRUDRepository for InMemoryRepository<Schema, PrimaryKeyType> {}
+
+impl<Schema, PrimaryKeyType> ReadDeleteRepository<Schema, PrimaryKeyType> for InMemoryRepository<Schema, PrimaryKeyType>
+where
+    Schema: Serialize + Clone + Send + Sync,
+    PrimaryKeyType: Ord + Send + Sync,
+{
+    fn get_table_name() -> String {
+        let name = std::any::type_name::<Schema>();
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().unwrap_or(name).to_lowercase()
+    }
+
+    fn get_primary_key_name(&self) -> String {
+        self.primary_key.to_string()
+    }
+
+    async fn list_items(&self, pagination: Pagination, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        Ok(self.select(&filters, &[], &sort)?
+            .into_iter()
+            .skip(pagination.skip.unwrap_or(0) as usize)
+            .take(pagination.limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect())
+    }
+
+    async fn list_items_after(&self, cursor: Option<Cursor>, limit: Option<u64>, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let keyset = match cursor {
+            Some(cursor) => cursor.keyset(&sort, self.primary_key),
+            None => Vec::new(),
+        };
+
+        Ok(self.select(&filters, &keyset, &sort)?
+            .into_iter()
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect())
+    }
+
+    async fn count_items(&self, filters: Vec<Filter>) -> Result<u64, CrudError> {
+        let items = self.items.read().unwrap_or_else(PoisonError::into_inner);
+        if filters.is_empty() {
+            return Ok(items.len() as u64);
+        }
+
+        let mut count = 0;
+        for item in items.values() {
+            let json = serde_json::to_value(item)?;
+            if filters.iter().all(|filter| matches(&json, filter)) {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    async fn get_item(&self, id: PrimaryKeyType) -> Result<Option<Schema>, CrudError> {
+        let items = self.items.read().unwrap_or_else(PoisonError::into_inner);
+        Ok(items.get(&id).cloned())
+    }
+
+    async fn delete_item(&self, id: PrimaryKeyType) -> Result<(), CrudError> {
+        let mut items = self.items.write().unwrap_or_else(PoisonError::into_inner);
+        items.remove(&id)
+            .map(|_| ())
+            .ok_or(CrudError::NotFound)
+    }
+
+    async fn delete_items(&self, ids: Vec<PrimaryKeyType>) -> Result<usize, CrudError> {
+        let mut items = self.items.write().unwrap_or_else(PoisonError::into_inner);
+        Ok(ids.iter()
+            .filter(|id| items.remove(id).is_some())
+            .count())
+    }
+
+    async fn delete_all_items(&self, filters: Vec<Filter>) -> Result<usize, CrudError> {
+        let mut items = self.items.write().unwrap_or_else(PoisonError::into_inner);
+        let count = items.len();
+        if filters.is_empty() {
+            items.clear();
+            return Ok(count);
+        }
+
+        // retain visits the items in the same order as values
+        let mut matching = Vec::with_capacity(count);
+        for item in items.values() {
+            let json = serde_json::to_value(item)?;
+            matching.push(filters.iter().all(|filter| matches(&json, filter)));
+        }
+        let mut matching = matching.into_iter();
+        items.retain(|_, _| !matching.next().unwrap_or(false));
+        Ok(count - items.len())
+    }
+}
+
+impl<Schema, PrimaryKeyType, CreateSchema> CreateRepository<Schema, CreateSchema> for InMemoryRepository<Schema, PrimaryKeyType>
+where
+    Schema: DeserializeOwned + Clone + Send + Sync,
+    PrimaryKeyType: Serialize + DeserializeOwned + Ord + Send + Sync,
+    CreateSchema: Serialize + Send,
+{
+    async fn create_item(&self, new_item: CreateSchema) -> Result<Schema, CrudError> {
+        let Value::Object(mut fields) = serde_json::to_value(new_item)? else {
+            return Err(CrudError::Validation("expected a JSON object".to_string()));
+        };
+
+        let id = self.assign_id(&mut fields)?;
+        let item: Schema = serde_json::from_value(Value::Object(fields))?;
+
+        let mut items = self.items.write().unwrap_or_else(PoisonError::into_inner);
+        if items.contains_key(&id) {
+            return Err(CrudError::Conflict(format!("{} already exists", self.primary_key)));
+        }
+        items.insert(id, item.clone());
+
+        Ok(item)
+    }
+
+    async fn create_items(&self, new_items: Vec<CreateSchema>) -> Result<Vec<Schema>, CrudError> {
+        let mut created = Vec::with_capacity(new_items.len());
+        for new_item in new_items {
+            let Value::Object(mut fields) = serde_json::to_value(new_item)? else {
+                return Err(CrudError::Validation("expected a JSON object".to_string()));
+            };
+
+            let id = self.assign_id(&mut fields)?;
+            created.push((id, serde_json::from_value::<Schema>(Value::Object(fields))?));
+        }
+
+        // all items are checked before any of them is inserted
+        let mut items = self.items.write().unwrap_or_else(PoisonError::into_inner);
+        let mut ids = BTreeSet::new();
+        for (id, _) in &created {
+            if items.contains_key(id) || !ids.insert(id) {
+                return Err(CrudError::Conflict(format!("{} already exists", self.primary_key)));
+            }
+        }
+
+        Ok(created.into_iter()
+            .map(|(id, item)| {
+                items.insert(id, item.clone());
+                item
+            })
+            .collect())
+    }
+}
+
+impl<Schema, PrimaryKeyType, UpdateSchema> UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> for InMemoryRepository<Schema, PrimaryKeyType>
+where
+    Schema: Serialize + DeserializeOwned + Clone + Send + Sync,
+    PrimaryKeyType: Ord + Send + Sync,
+    UpdateSchema: Serialize + Send,
+{
+    async fn update_item(&self, id: PrimaryKeyType, item: UpdateSchema) -> Result<Schema, CrudError> {
+        let Value::Object(changes) = serde_json::to_value(item)? else {
+            return Err(CrudError::Validation("expected a JSON object".to_string()));
+        };
+
+        let mut items = self.items.write().unwrap_or_else(PoisonError::into_inner);
+        let existing = items.get_mut(&id).ok_or(CrudError::NotFound)?;
+
+        *existing = self.apply_changes(existing, &changes)?;
+        Ok(existing.clone())
+    }
+
+    async fn update_items(&self, ids: Vec<PrimaryKeyType>, item: UpdateSchema) -> Result<usize, CrudError> {
+        let Value::Object(changes) = serde_json::to_value(item)? else {
+            return Err(CrudError::Validation("expected a JSON object".to_string()));
+        };
+
+        // all items are updated before any of them is written back
+        let mut items = self.items.write().unwrap_or_else(PoisonError::into_inner);
+        let mut updated = BTreeMap::new();
+        for id in ids {
+            if let Some(existing) = items.get(&id) {
+                let item = self.apply_changes(existing, &changes)?;
+                updated.insert(id, item);
+            }
+        }
+
+        let count = updated.len();
+        items.extend(updated);
+        Ok(count)
+    }
+}
@@ -1,31 +1,136 @@
-
+mod in_memory;
 #[cfg(feature = "diesel")]
 mod diesel;
+#[cfg(feature = "diesel-async")]
+mod diesel_async;
 #[cfg(feature = "sea-orm")]
 mod sea_orm;
+#[cfg(feature = "sqlx")]
+mod sqlx;
 
+pub use in_memory::{IdGenerator, InMemoryRepository, SequentialIdGenerator};
 #[cfg(feature = "sea-orm")]
 pub use sea_orm::SeaOrmRepository;
+#[cfg(feature = "sqlx")]
+pub use sqlx::{SqlxRepository, SqlxTable};
 #[cfg(feature = "diesel")]
-pub use diesel::DieselRepository;
+pub use diesel::{ConnectionSource, DieselRepository, InsertReturning, UpdateReturning};
+#[cfg(feature = "diesel-async")]
+pub use diesel_async::{AsyncConnectionSource, AsyncDieselRepository};
+
+use std::fmt;
+use crate::{Cursor, Filter, Pagination, Sort};
 
-use crate::Pagination;
+/// Error returned by the repository traits.
+///
+/// Repositories map their native errors into one of these variants so that
+/// servers can handle every backend in the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrudError {
+    /// The requested item does not exist.
+    NotFound,
+    /// The operation violates a unique or foreign key constraint.
+    Conflict(String),
+    /// The given item is not valid for the backend.
+    Validation(String),
+    /// The update lacks the version a repository with a version column needs to check it.
+    PreconditionRequired(String),
+    /// The backend could not be reached.
+    Connection(String),
+    /// Any other backend failure.
+    Backend(String),
+}
 
-pub trait CRUDRepository{}
+impl fmt::Display for CrudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrudError::NotFound => write!(f, "item not found"),
+            CrudError::Conflict(message) => write!(f, "conflict: {}", message),
+            CrudError::Validation(message) => write!(f, "validation error: {}", message),
+            CrudError::PreconditionRequired(message) => write!(f, "precondition required: {}", message),
+            CrudError::Connection(message) => write!(f, "connection error: {}", message),
+            CrudError::Backend(message) => write!(f, "backend error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CrudError {}
+
+impl From<serde_json::Error> for CrudError {
+    fn from(error: serde_json::Error) -> Self {
+        CrudError::Validation(error.to_string())
+    }
+}
+
+/// Converts a limit or offset for databases that take signed 64 bit integers.
+#[cfg(any(feature = "diesel", feature = "sqlx"))]
+pub(crate) fn signed(value: u64) -> Result<i64, CrudError> {
+    i64::try_from(value)
+        .map_err(|_| CrudError::Validation(format!("{} is out of range", value)))
+}
+
+/// The version an update carries in the version column, repositories with one don't write without it.
+pub(crate) fn required_version(version: Option<&serde_json::Value>, version_column: &str) -> Result<i64, CrudError> {
+    version.and_then(serde_json::Value::as_i64)
+        .ok_or_else(|| CrudError::PreconditionRequired(format!("the update needs the `{}` it is based on", version_column)))
+}
+
+pub trait CRUDRepository{
+    /// The integer column the repository uses for optimistic locking, if any.
+    fn get_version_column(&self) -> Option<&'static str> {
+        None
+    }
+}
 
 pub trait ReadDeleteRepository<Schema, PrimaryKeyType>: CRUDRepository {
     fn get_table_name() -> String;
-    fn list_items(&mut self, pagination: Pagination) -> impl std::future::Future<Output = Vec<Schema>> + Send;
-    fn get_item(&mut self, id: PrimaryKeyType) -> impl std::future::Future<Output = Option<Schema>> + Send;
-    fn delete_item(&mut self, id: PrimaryKeyType) -> impl std::future::Future<Output = ()> + Send;
-    fn delete_all_items(&mut self) -> impl std::future::Future<Output = usize> + Send;
+    fn get_primary_key_name(&self) -> String;
+    fn list_items(&self, pagination: Pagination, filters: Vec<Filter>, sort: Vec<Sort>) -> impl std::future::Future<Output = Result<Vec<Schema>, CrudError>> + Send;
+    /// Lists the items after the cursor in keyset order, for [crate::PaginationMode::Cursor].
+    fn list_items_after(&self, cursor: Option<Cursor>, limit: Option<u64>, filters: Vec<Filter>, sort: Vec<Sort>) -> impl std::future::Future<Output = Result<Vec<Schema>, CrudError>> + Send;
+    /// Counts the items matching the filters, regardless of pagination.
+    fn count_items(&self, filters: Vec<Filter>) -> impl std::future::Future<Output = Result<u64, CrudError>> + Send;
+    fn get_item(&self, id: PrimaryKeyType) -> impl std::future::Future<Output = Result<Option<Schema>, CrudError>> + Send;
+    /// Whether the item exists. The default loads it, repositories override it with a cheaper query.
+    fn exists(&self, id: PrimaryKeyType) -> impl std::future::Future<Output = Result<bool, CrudError>> + Send
+    where
+        Self: Sync,
+        PrimaryKeyType: Send,
+    {
+        async move { Ok(self.get_item(id).await?.is_some()) }
+    }
+    fn delete_item(&self, id: PrimaryKeyType) -> impl std::future::Future<Output = Result<(), CrudError>> + Send;
+    /// Deletes the item only if its version column still has `version`, and fails with
+    /// [CrudError::Conflict] otherwise. The default ignores the version, repositories with a
+    /// [version column](CRUDRepository::get_version_column) override it.
+    fn delete_item_at_version(&self, id: PrimaryKeyType, version: i64) -> impl std::future::Future<Output = Result<(), CrudError>> + Send
+    where
+        Self: Sync,
+        PrimaryKeyType: Send,
+    {
+        let _ = version;
+        self.delete_item(id)
+    }
+    /// Deletes the items with the given ids in one transaction and returns how many were deleted,
+    /// missing ids are skipped.
+    fn delete_items(&self, ids: Vec<PrimaryKeyType>) -> impl std::future::Future<Output = Result<usize, CrudError>> + Send;
+    /// Deletes the items matching the filters, all of them without filters, and returns how many were deleted.
+    fn delete_all_items(&self, filters: Vec<Filter>) -> impl std::future::Future<Output = Result<usize, CrudError>> + Send;
 }
 
 pub trait CreateRepository<Schema, CreateSchema>: CRUDRepository {
-    fn create_item(&mut self, new_item: CreateSchema) -> impl std::future::Future<Output = Schema> + Send;
+    fn create_item(&self, new_item: CreateSchema) -> impl std::future::Future<Output = Result<Schema, CrudError>> + Send;
+    /// Creates all items in one statement or transaction, if one of them fails none are created.
+    fn create_items(&self, new_items: Vec<CreateSchema>) -> impl std::future::Future<Output = Result<Vec<Schema>, CrudError>> + Send;
 }
 
 
 pub trait UpdateRepository<Schema, PrimaryKeyType, UpdateSchema>: CRUDRepository {
-    fn update_item(&mut self, id: PrimaryKeyType, item: UpdateSchema) -> impl std::future::Future<Output = Schema> + Send;
+    /// Updates the item, with a [version column](CRUDRepository::get_version_column) only if it still
+    /// has the version of the update, which fails with [CrudError::PreconditionRequired] without one.
+    fn update_item(&self, id: PrimaryKeyType, item: UpdateSchema) -> impl std::future::Future<Output = Result<Schema, CrudError>> + Send;
+    /// Applies the same changes to the items with the given ids in one transaction and returns
+    /// how many were updated, missing ids are skipped. With a version column every item has to
+    /// have the version of the update.
+    fn update_items(&self, ids: Vec<PrimaryKeyType>, item: UpdateSchema) -> impl std::future::Future<Output = Result<usize, CrudError>> + Send;
 }
@@ -1,27 +1,154 @@
-use crate::repositories::{CRUDRepository, ReadDeleteRepository, CreateRepository, UpdateRepository};
-use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityName, EntityTrait, FromQueryResult, IntoActiveModel, ModelTrait, PrimaryKeyTrait, QuerySelect, TryIntoModel};
+use crate::repositories::{required_version, CRUDRepository, ReadDeleteRepository, CreateRepository, UpdateRepository, CrudError};
+use std::str::FromStr;
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbErr, EntityName, EntityTrait, FromQueryResult, IdenStatic, IntoActiveModel, Iterable, ModelTrait, Order, PaginatorTrait, PrimaryKeyToColumn, PrimaryKeyTrait, QueryFilter, QueryOrder, QuerySelect, Select, SqlErr, TransactionTrait, TryIntoModel, Value};
+use sea_orm::sea_query::{Expr, LikeExpr, NullOrdering};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use crate::Pagination;
+use crate::{Cursor, Filter, FilterCondition, FilterValue, Pagination, Sort};
+use crate::filter::{contains_pattern, LIKE_ESCAPE};
 
 pub struct SeaOrmRepository {
-    connection: DatabaseConnection
+    connection: DatabaseConnection,
+    version_column: Option<&'static str>
 }
 
 impl SeaOrmRepository {
     pub fn new(connection: DatabaseConnection) -> Self{
         Self{
-            connection
+            connection,
+            version_column: None
         }
     }
+
+    /// Enables optimistic locking on an integer column: `update_item` and `update_items` only update
+    /// the rows if their version equals the one of the update, bump it, and fail with
+    /// [CrudError::Conflict] otherwise and with [CrudError::PreconditionRequired] without a version.
+    /// Every entity of the repository needs the column, writes to others fail with [CrudError::Backend].
+    pub fn version_column(self, version_column: &'static str) -> Self{
+        Self{
+            version_column: Some(version_column),
+            ..self
+        }
+    }
+}
+
+impl From<DbErr> for CrudError {
+    fn from(error: DbErr) -> Self {
+        match error.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(message)) => return CrudError::Conflict(message),
+            Some(SqlErr::ForeignKeyConstraintViolation(message)) => return CrudError::Conflict(message),
+            _ => {}
+        }
+
+        match error {
+            DbErr::RecordNotFound(_) | DbErr::RecordNotUpdated => CrudError::NotFound,
+            DbErr::Conn(_) | DbErr::ConnectionAcquire(_) => CrudError::Connection(error.to_string()),
+            DbErr::Json(_) | DbErr::AttrNotSet(_) | DbErr::Type(_) | DbErr::TryIntoErr { .. } => CrudError::Validation(error.to_string()),
+            error => CrudError::Backend(error.to_string()),
+        }
+    }
+}
+
+fn value(value: FilterValue) -> Value {
+    match value {
+        FilterValue::Bool(value) => value.into(),
+        FilterValue::Integer(value) => value.into(),
+        FilterValue::Float(value) => value.into(),
+        FilterValue::String(value) => value.into(),
+    }
+}
+
+/// Combines the filters into one condition, a filter field must be a column of `Entity`.
+fn condition<Entity: EntityTrait>(filters: Vec<Filter>) -> Result<Condition, CrudError> {
+    let mut condition = Condition::all();
+
+    for filter in filters {
+        let column = Entity::Column::from_str(&filter.field)
+            .map_err(|_| CrudError::Validation(format!("unknown column `{}`", filter.field)))?;
+
+        condition = condition.add(match filter.condition {
+            FilterCondition::Eq(v) => column.eq(value(v)),
+            FilterCondition::Ne(v) => column.ne(value(v)),
+            FilterCondition::Lt(v) => column.lt(value(v)),
+            FilterCondition::Gt(v) => column.gt(value(v)),
+            FilterCondition::In(values) => column.is_in(values.into_iter().map(value)),
+            FilterCondition::Contains(text) => column.like(LikeExpr::new(contains_pattern(&text)).escape(LIKE_ESCAPE)),
+            FilterCondition::IsNull(true) => column.is_null(),
+            FilterCondition::IsNull(false) => column.is_not_null(),
+        });
+    }
+
+    Ok(condition)
+}
+
+/// Orders the query by the sort keys with nulls as the smallest value, ties are ordered by the primary key.
+fn order<Entity: EntityTrait>(mut query: Select<Entity>, sort: Vec<Sort>) -> Result<Select<Entity>, CrudError> {
+    for sort in sort {
+        let column = Entity::Column::from_str(&sort.field)
+            .map_err(|_| CrudError::Validation(format!("unknown column `{}`", sort.field)))?;
+        query = if sort.descending {
+            query.order_by_with_nulls(column, Order::Desc, NullOrdering::Last)
+        } else {
+            query.order_by_with_nulls(column, Order::Asc, NullOrdering::First)
+        };
+    }
+    for primary_key in Entity::PrimaryKey::iter() {
+        query = query.order_by_asc(primary_key.into_column());
+    }
+    Ok(query)
+}
+
+/// Name of the primary key column, cursors only use the first one of a composite key.
+fn primary_key_name<Entity: EntityTrait>() -> String {
+    Entity::PrimaryKey::iter()
+        .next()
+        .map(|primary_key| primary_key.into_column().as_str().to_string())
+        .unwrap_or_default()
+}
+
+/// The fields of an update, see [changed_model].
+fn changes(item: impl Serialize) -> Result<serde_json::Map<String, serde_json::Value>, CrudError> {
+    let serde_json::Value::Object(changes) = serde_json::to_value(item)? else {
+        return Err(CrudError::Validation("expected a JSON object".to_string()));
+    };
+    Ok(changes)
+}
+
+/// The active model of `item` with the columns of the serialized update set. Fields the update
+/// skips keep their value and `null` clears the column.
+fn changed_model<Schema>(item: Schema, changes: &serde_json::Map<String, serde_json::Value>) -> Result<<Schema::Entity as EntityTrait>::ActiveModel, CrudError>
+where
+    Schema::Entity: EntityTrait<Model=Schema>,
+    Schema: ModelTrait + IntoActiveModel<<Schema::Entity as EntityTrait>::ActiveModel> + Serialize + DeserializeOwned,
+{
+    let mut item_json = serde_json::to_value(&item)?;
+    if let serde_json::Value::Object(fields) = &mut item_json {
+        fields.extend(changes.clone());
+    }
+    let mut active_model = item.into_active_model();
+    active_model.set_from_json(item_json)?;
+
+    let primary_key: Vec<_> = <Schema::Entity as EntityTrait>::PrimaryKey::iter()
+        .map(|primary_key| primary_key.into_column().as_str().to_string())
+        .collect();
+    for column in <Schema::Entity as EntityTrait>::Column::iter() {
+        if !changes.contains_key(column.as_str()) && !primary_key.iter().any(|primary_key| primary_key == column.as_str()) {
+            active_model.not_set(column);
+        }
+    }
+    Ok(active_model)
 }
 
-impl CRUDRepository for SeaOrmRepository {}
+impl CRUDRepository for SeaOrmRepository {
+    fn get_version_column(&self) -> Option<&'static str> {
+        self.version_column
+    }
+}
 
 impl<Schema> ReadDeleteRepository<Schema, <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType> for SeaOrmRepository
 where
     Schema::Entity: EntityTrait<Model=Schema>,
-    Schema: ModelTrait + FromQueryResult + IntoActiveModel<<Schema::Entity as EntityTrait>::ActiveModel> + TryFrom<<Schema::Entity as EntityTrait>::ActiveModel> + DeserializeOwned + Send,
+    Schema: ModelTrait + FromQueryResult + IntoActiveModel<<Schema::Entity as EntityTrait>::ActiveModel> + TryFrom<<Schema::Entity as EntityTrait>::ActiveModel> + DeserializeOwned + Send + Sync,
     <Schema::Entity as EntityTrait>::ActiveModel: ActiveModelTrait<Entity=Schema::Entity> + From<Schema> + TryIntoModel<Schema> + Send,
     <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType: DeserializeOwned + Clone
 {
@@ -30,57 +157,244 @@ where
         entity.table_name().to_string()
     }
 
-    async fn list_items(&mut self, pagination: Pagination) -> Vec<Schema> {
-        Schema::Entity::find().offset(pagination.skip).limit(pagination.limit).all(&self.connection).await.unwrap()
+    fn get_primary_key_name(&self) -> String {
+        primary_key_name::<Schema::Entity>()
+    }
+
+    async fn list_items(&self, pagination: Pagination, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let query = Schema::Entity::find()
+            .filter(condition::<Schema::Entity>(filters)?);
+
+        Ok(order(query, sort)?
+            .offset(pagination.skip)
+            .limit(pagination.limit)
+            .all(&self.connection).await?)
+    }
+
+    async fn list_items_after(&self, cursor: Option<Cursor>, limit: Option<u64>, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let mut query = Schema::Entity::find()
+            .filter(condition::<Schema::Entity>(filters)?);
+
+        if let Some(cursor) = cursor {
+            let mut keyset = Condition::any();
+            for filters in cursor.keyset(&sort, &primary_key_name::<Schema::Entity>()) {
+                keyset = keyset.add(condition::<Schema::Entity>(filters)?);
+            }
+            query = query.filter(keyset);
+        }
+
+        Ok(order(query, sort)?
+            .limit(limit)
+            .all(&self.connection).await?)
+    }
+
+    async fn count_items(&self, filters: Vec<Filter>) -> Result<u64, CrudError> {
+        Ok(Schema::Entity::find()
+            .filter(condition::<Schema::Entity>(filters)?)
+            .count(&self.connection).await?)
+    }
+
+    async fn get_item(&self, id: <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType) -> Result<Option<Schema>, CrudError> {
+        Ok(Schema::Entity::find_by_id(id).one(&self.connection).await?)
+    }
+
+    async fn exists(&self, id: <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType) -> Result<bool, CrudError> {
+        let found = Schema::Entity::find_by_id(id)
+            .select_only()
+            .expr(Expr::val(1))
+            .into_tuple::<i32>()
+            .one(&self.connection).await?;
+        Ok(found.is_some())
+    }
+
+    async fn delete_item(&self, id: <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType) -> Result<(), CrudError> {
+        let result = Schema::Entity::delete_by_id(id).exec(&self.connection).await?;
+
+        if result.rows_affected == 0 {
+            return Err(CrudError::NotFound);
+        }
+        Ok(())
     }
 
-    async fn get_item(&mut self, id: <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType) -> Option<Schema> {
-        Schema::Entity::find_by_id(id).one(&self.connection).await.unwrap()
+    async fn delete_item_at_version(&self, id: <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType, version: i64) -> Result<(), CrudError> {
+        let Some(version_column) = version_column::<Schema::Entity>(self.version_column)? else {
+            return ReadDeleteRepository::<Schema, _>::delete_item(self, id).await;
+        };
+
+        let result = Schema::Entity::delete_by_id(id.clone())
+            .filter(version_column.eq(version))
+            .exec(&self.connection).await?;
+        if result.rows_affected == 0 {
+            // a missing item is not found rather than modified
+            Schema::Entity::find_by_id(id).one(&self.connection).await?
+                .ok_or(CrudError::NotFound)?;
+            return Err(CrudError::Conflict("the item has been modified".to_string()));
+        }
+        Ok(())
     }
 
-    async fn delete_item(&mut self, id: <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType) {
-        Schema::Entity::delete_by_id(id).exec(&self.connection).await.unwrap();
+    async fn delete_items(&self, ids: Vec<<<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType>) -> Result<usize, CrudError> {
+        let transaction = self.connection.begin().await?;
+        let mut deleted = 0;
+        for id in ids {
+            deleted += Schema::Entity::delete_by_id(id).exec(&transaction).await?.rows_affected as usize;
+        }
+        transaction.commit().await?;
+
+        Ok(deleted)
     }
 
-    async fn delete_all_items(&mut self) -> usize {
-        Schema::Entity::delete_many().exec(&self.connection).await.unwrap().rows_affected as usize
+    async fn delete_all_items(&self, filters: Vec<Filter>) -> Result<usize, CrudError> {
+        Ok(Schema::Entity::delete_many()
+            .filter(condition::<Schema::Entity>(filters)?)
+            .exec(&self.connection).await?.rows_affected as usize)
     }
 }
 
 impl<Schema, CreateSchema> CreateRepository<Schema, CreateSchema> for SeaOrmRepository
 where
     Schema::Entity: EntityTrait<Model=Schema>,
-    Schema: ModelTrait + IntoActiveModel<<Schema::Entity as EntityTrait>::ActiveModel> + TryFrom<<Schema::Entity as EntityTrait>::ActiveModel> + DeserializeOwned + Send,
+    Schema: ModelTrait + FromQueryResult + IntoActiveModel<<Schema::Entity as EntityTrait>::ActiveModel> + TryFrom<<Schema::Entity as EntityTrait>::ActiveModel> + DeserializeOwned + Send,
     <Schema::Entity as EntityTrait>::ActiveModel: ActiveModelTrait<Entity=Schema::Entity> + From<Schema> + TryIntoModel<Schema> + Send,
     <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType: DeserializeOwned + Clone,
 
     CreateSchema: Serialize + Send
 {
-    async fn create_item(&mut self, new_item: CreateSchema) -> Schema {
-        let new_item_json = serde_json::to_value(new_item).unwrap();
+    async fn create_item(&self, new_item: CreateSchema) -> Result<Schema, CrudError> {
+        let new_item_json = serde_json::to_value(new_item)?;
+
+        let active_model = <Schema::Entity as EntityTrait>::ActiveModel::from_json(new_item_json)?;
+
+        Ok(active_model.insert(&self.connection).await?)
+    }
+
+    async fn create_items(&self, new_items: Vec<CreateSchema>) -> Result<Vec<Schema>, CrudError> {
+        let active_models = new_items.into_iter()
+            .map(|new_item| Ok(<Schema::Entity as EntityTrait>::ActiveModel::from_json(serde_json::to_value(new_item)?)?))
+            .collect::<Result<Vec<_>, CrudError>>()?;
+        if active_models.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if self.connection.support_returning() {
+            return Ok(Schema::Entity::insert_many(active_models).exec_with_returning_many(&self.connection).await?);
+        }
 
-        let active_model = <Schema::Entity as EntityTrait>::ActiveModel::from_json(new_item_json).unwrap();
+        // without `RETURNING` the rows are inserted one by one to load them back
+        let transaction = self.connection.begin().await?;
+        let mut items = Vec::with_capacity(active_models.len());
+        for active_model in active_models {
+            items.push(active_model.insert(&transaction).await?);
+        }
+        transaction.commit().await?;
 
-        active_model.insert(&self.connection).await.unwrap()
+        Ok(items)
     }
 }
 
 impl<Schema, UpdateSchema> UpdateRepository<Schema, <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType, UpdateSchema> for SeaOrmRepository
 where
     Schema::Entity: EntityTrait<Model=Schema>,
-    Schema: ModelTrait + FromQueryResult + IntoActiveModel<<Schema::Entity as EntityTrait>::ActiveModel> + TryFrom<<Schema::Entity as EntityTrait>::ActiveModel> + DeserializeOwned + Send,
+    Schema: ModelTrait + FromQueryResult + IntoActiveModel<<Schema::Entity as EntityTrait>::ActiveModel> + TryFrom<<Schema::Entity as EntityTrait>::ActiveModel> + Serialize + DeserializeOwned + Send,
     <Schema::Entity as EntityTrait>::ActiveModel: ActiveModelTrait<Entity=Schema::Entity> + From<Schema> + TryIntoModel<Schema> + Send,
     <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType: DeserializeOwned + Clone,
 
     UpdateSchema: Serialize + Send
 {
-    async fn update_item(&mut self, id: <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType, item: UpdateSchema) -> Schema {
-        let item_json = serde_json::to_value(item).unwrap();
+    async fn update_item(&self, id: <<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType, item: UpdateSchema) -> Result<Schema, CrudError> {
+        let changes = changes(item)?;
+        let version = update_version::<Schema::Entity>(self.version_column, &changes)?;
+
+        let transaction = self.connection.begin().await?;
+        let item = Schema::Entity::find_by_id(id.clone()).one(&transaction).await?
+            .ok_or(CrudError::NotFound)?;
+        let active_model = changed_model(item, &changes)?;
+        let item = match version {
+            Some((version_column, version)) => {
+                if !update_at_version(&transaction, active_model, version_column, version).await? {
+                    return Err(CrudError::NotFound);
+                }
+                Schema::Entity::find_by_id(id).one(&transaction).await?
+                    .ok_or(CrudError::NotFound)?
+            }
+            None => active_model.update(&transaction).await?,
+        };
+        transaction.commit().await?;
+
+        Ok(item)
+    }
+
+    async fn update_items(&self, ids: Vec<<<Schema::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType>, item: UpdateSchema) -> Result<usize, CrudError> {
+        let changes = changes(item)?;
+        // all items have to be at the version of the update
+        let version = update_version::<Schema::Entity>(self.version_column, &changes)?;
+
+        let transaction = self.connection.begin().await?;
+        let mut updated = 0;
+        for id in ids {
+            let Some(item) = Schema::Entity::find_by_id(id).one(&transaction).await? else {
+                continue;
+            };
+            let active_model = changed_model(item, &changes)?;
+            match version {
+                Some((version_column, version)) => if !update_at_version(&transaction, active_model, version_column, version).await? {
+                    continue;
+                },
+                None => {
+                    active_model.update(&transaction).await?;
+                }
+            }
+            updated += 1;
+        }
+        transaction.commit().await?;
 
-        let item = Schema::Entity::find_by_id(id.clone()).one(&self.connection).await.unwrap().unwrap();
-        let mut active_model = item.into_active_model();
-        active_model.set_from_json(item_json).unwrap();
+        Ok(updated)
+    }
+}
+
+/// The version column of the repository as a column of `Entity`.
+fn version_column<Entity: EntityTrait>(version_column: Option<&str>) -> Result<Option<Entity::Column>, CrudError> {
+    version_column
+        .map(|version_column| Entity::Column::from_str(version_column)
+            .map_err(|_| CrudError::Backend(format!("`{}` has no version column `{}`", Entity::default().table_name(), version_column))))
+        .transpose()
+}
+
+/// The version column of `Entity` and the version the update carries in it.
+fn update_version<Entity: EntityTrait>(version_column: Option<&str>, changes: &serde_json::Map<String, serde_json::Value>) -> Result<Option<(Entity::Column, i64)>, CrudError> {
+    let Some(version_column) = self::version_column::<Entity>(version_column)? else {
+        return Ok(None);
+    };
+    Ok(Some((version_column, required_version(changes.get(version_column.as_str()), version_column.as_str())?)))
+}
 
-        active_model.update(&self.connection).await.unwrap()
+/// Updates the item of `active_model` if it is still at `version` and bumps its version. Returns
+/// false for a missing item and fails with [CrudError::Conflict] if it has been modified.
+async fn update_at_version<ActiveModel>(connection: &impl ConnectionTrait, mut active_model: ActiveModel, version_column: <ActiveModel::Entity as EntityTrait>::Column, version: i64) -> Result<bool, CrudError>
+where
+    ActiveModel: ActiveModelTrait + Send,
+{
+    active_model.not_set(version_column);
+    let mut primary_key = Condition::all();
+    for column in <ActiveModel::Entity as EntityTrait>::PrimaryKey::iter().map(|primary_key| primary_key.into_column()) {
+        let Some(value) = active_model.get(column).into_value() else {
+            return Ok(false);
+        };
+        primary_key = primary_key.add(column.eq(value));
+    }
+
+    let updated = ActiveModel::Entity::update_many()
+        .set(active_model)
+        .col_expr(version_column, Expr::col(version_column).add(1))
+        .filter(primary_key.clone())
+        .filter(version_column.eq(version))
+        .exec(connection).await?;
+    if updated.rows_affected == 0 {
+        // a missing item is not found rather than modified
+        if ActiveModel::Entity::find().filter(primary_key).one(connection).await?.is_some() {
+            return Err(CrudError::Conflict("the item has been modified".to_string()));
+        }
+        return Ok(false);
     }
+    Ok(true)
 }
@@ -0,0 +1,520 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sqlx::error::ErrorKind;
+use sqlx::error::BoxDynError;
+use sqlx::{Arguments, Database, Encode, Executor, FromRow, IntoArguments, Pool, Type};
+use crate::{Cursor, Filter, FilterCondition, FilterValue, Pagination, Sort};
+use crate::filter::{contains_pattern, LIKE_ESCAPE};
+use crate::repositories::{signed, CRUDRepository, CreateRepository, CrudError, ReadDeleteRepository, UpdateRepository};
+
+/// Table and column metadata of a [FromRow] struct used by [SqlxRepository].
+///
+/// Create and update bodies may only contain fields listed in `COLUMNS`.
+pub trait SqlxTable {
+    const TABLE_NAME: &'static str;
+    const PRIMARY_KEY: &'static str;
+    const COLUMNS: &'static [&'static str];
+    /// SQL types of the columns whose values need a `CAST`, e.g. `("created_at", "timestamptz")`.
+    ///
+    /// JSON strings are bound as text, which Postgres doesn't assign to timestamp, uuid or jsonb
+    /// columns without a cast.
+    const COLUMN_TYPES: &'static [(&'static str, &'static str)] = &[];
+}
+
+pub struct SqlxRepository<DB: Database> {
+    pool: Pool<DB>
+}
+
+impl<DB: Database> SqlxRepository<DB> {
+    pub fn new(pool: Pool<DB>) -> Self{
+        Self{
+            pool
+        }
+    }
+}
+
+impl From<sqlx::Error> for CrudError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => CrudError::NotFound,
+            sqlx::Error::Database(error) => match error.kind() {
+                ErrorKind::UniqueViolation | ErrorKind::ForeignKeyViolation => CrudError::Conflict(error.message().to_string()),
+                ErrorKind::NotNullViolation | ErrorKind::CheckViolation => CrudError::Validation(error.message().to_string()),
+                _ => CrudError::Backend(error.message().to_string()),
+            },
+            sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed => CrudError::Connection(error.to_string()),
+            sqlx::Error::Encode(_) => CrudError::Validation(error.to_string()),
+            error => CrudError::Backend(error.to_string()),
+        }
+    }
+}
+
+/// A SQL statement built with the placeholder syntax of `DB`.
+struct Statement<'q, DB: Database> {
+    sql: String,
+    arguments: Result<DB::Arguments<'q>, BoxDynError>,
+    column_types: &'static [(&'static str, &'static str)]
+}
+
+impl<'q, DB: Database> Statement<'q, DB> {
+    fn new<Schema: SqlxTable>(sql: &str) -> Self {
+        Self {
+            sql: sql.to_string(),
+            arguments: Ok(Default::default()),
+            column_types: Schema::COLUMN_TYPES
+        }
+    }
+
+    /// The declared SQL type of `column`, see [SqlxTable::COLUMN_TYPES].
+    fn column_type(&self, column: &str) -> Option<&'static str> {
+        self.column_types.iter()
+            .find(|(name, _)| *name == column)
+            .map(|(_, column_type)| *column_type)
+    }
+
+    /// Runs `push` inside a `CAST` to the type of `column`, if it has one.
+    fn push_cast(&mut self, column: &str, push: impl FnOnce(&mut Self) -> &mut Self) -> &mut Self {
+        match self.column_type(column) {
+            Some(column_type) => {
+                self.push("CAST(");
+                push(self);
+                self.push(" AS ").push(column_type).push(")")
+            },
+            None => push(self),
+        }
+    }
+
+    fn push(&mut self, sql: &str) -> &mut Self {
+        self.sql.push_str(sql);
+        self
+    }
+
+    fn push_identifier(&mut self, identifier: &str) -> &mut Self {
+        self.sql.push('"');
+        self.sql.push_str(&identifier.replace('"', "\"\""));
+        self.sql.push('"');
+        self
+    }
+
+    fn push_bind(&mut self, value: impl Encode<'q, DB> + Type<DB> + 'q) -> &mut Self {
+        if let Ok(arguments) = &mut self.arguments {
+            if let Err(error) = arguments.add(value) {
+                self.arguments = Err(error);
+            } else if let Err(error) = arguments.format_placeholder(&mut self.sql) {
+                self.arguments = Err(error.into());
+            }
+        }
+        self
+    }
+
+    /// Binds the value of `column`.
+    fn push_value(&mut self, column: &str, value: Value) -> &mut Self
+    where
+        bool: Encode<'q, DB> + Type<DB>,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        String: Encode<'q, DB> + Type<DB>,
+    {
+        if value.is_null() {
+            return self.push("NULL");
+        }
+        self.push_cast(column, |query| match value {
+            Value::Bool(value) => query.push_bind(value),
+            Value::Number(value) => match value.as_i64() {
+                Some(value) => query.push_bind(value),
+                None => query.push_bind(value.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(value) => query.push_bind(value),
+            value => query.push_bind(value.to_string()),
+        })
+    }
+
+    /// `INSERT INTO table (columns) VALUES (values) RETURNING *`
+    fn insert<Schema: SqlxTable>(columns: Vec<(&str, Value)>) -> Self
+    where
+        bool: Encode<'q, DB> + Type<DB>,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        String: Encode<'q, DB> + Type<DB>,
+    {
+        let mut query = Statement::new::<Schema>("INSERT INTO ");
+        query.push_identifier(Schema::TABLE_NAME);
+
+        if columns.is_empty() {
+            query.push(" DEFAULT VALUES");
+        } else {
+            query.push(" (");
+            for (i, (column, _)) in columns.iter().enumerate() {
+                if i > 0 {
+                    query.push(", ");
+                }
+                query.push_identifier(column);
+            }
+            query.push(") VALUES (");
+            for (i, (column, value)) in columns.into_iter().enumerate() {
+                if i > 0 {
+                    query.push(", ");
+                }
+                query.push_value(column, value);
+            }
+            query.push(")");
+        }
+        query.push(" RETURNING *");
+        query
+    }
+
+    fn push_filter_value(&mut self, field: &str, value: FilterValue) -> &mut Self
+    where
+        bool: Encode<'q, DB> + Type<DB>,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        String: Encode<'q, DB> + Type<DB>,
+    {
+        self.push_cast(field, |query| match value {
+            FilterValue::Bool(value) => query.push_bind(value),
+            FilterValue::Integer(value) => query.push_bind(value),
+            FilterValue::Float(value) => query.push_bind(value),
+            FilterValue::String(value) => query.push_bind(value),
+        })
+    }
+
+    fn push_filter(&mut self, filter: Filter) -> &mut Self
+    where
+        bool: Encode<'q, DB> + Type<DB>,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        String: Encode<'q, DB> + Type<DB>,
+    {
+        let field = filter.field;
+        self.push_identifier(&field);
+        match filter.condition {
+            FilterCondition::Eq(value) => self.push(" = ").push_filter_value(&field, value),
+            FilterCondition::Ne(value) => self.push(" <> ").push_filter_value(&field, value),
+            FilterCondition::Lt(value) => self.push(" < ").push_filter_value(&field, value),
+            FilterCondition::Gt(value) => self.push(" > ").push_filter_value(&field, value),
+            FilterCondition::In(values) => {
+                self.push(" IN (");
+                for (i, value) in values.into_iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    self.push_filter_value(&field, value);
+                }
+                self.push(")")
+            },
+            FilterCondition::Contains(text) => self.push(" LIKE ")
+                .push_bind(contains_pattern(&text))
+                .push(&format!(" ESCAPE '{}'", LIKE_ESCAPE)),
+            FilterCondition::IsNull(true) => self.push(" IS NULL"),
+            FilterCondition::IsNull(false) => self.push(" IS NOT NULL"),
+        }
+    }
+
+    /// Appends the filters and one of the keyset alternatives as a `WHERE` clause,
+    /// nothing when there are none.
+    fn push_filters(&mut self, filters: Vec<Filter>, keyset: Vec<Vec<Filter>>) -> &mut Self
+    where
+        bool: Encode<'q, DB> + Type<DB>,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        String: Encode<'q, DB> + Type<DB>,
+    {
+        let has_filters = !filters.is_empty();
+        for (i, filter) in filters.into_iter().enumerate() {
+            self.push(if i == 0 { " WHERE " } else { " AND " });
+            self.push_filter(filter);
+        }
+
+        if !keyset.is_empty() {
+            self.push(if has_filters { " AND (" } else { " WHERE (" });
+            for (i, filters) in keyset.into_iter().enumerate() {
+                self.push(if i == 0 { "(" } else { " OR (" });
+                for (i, filter) in filters.into_iter().enumerate() {
+                    if i > 0 {
+                        self.push(" AND ");
+                    }
+                    self.push_filter(filter);
+                }
+                self.push(")");
+            }
+            self.push(")");
+        }
+        self
+    }
+
+    /// Appends `WHERE primary_key IN (ids)`, `ids` must not be empty.
+    fn push_ids(&mut self, primary_key: &str, ids: Vec<impl Encode<'q, DB> + Type<DB> + 'q>) -> &mut Self {
+        self.push(" WHERE ");
+        self.push_identifier(primary_key);
+        self.push(" IN (");
+        for (i, id) in ids.into_iter().enumerate() {
+            if i > 0 {
+                self.push(", ");
+            }
+            self.push_bind(id);
+        }
+        self.push(")")
+    }
+
+    /// Appends an `ORDER BY` of the sort keys, ties are ordered by `primary_key`.
+    /// Nulls come first in ascending order on every database, as the keyset of a cursor expects.
+    fn push_sort(&mut self, sort: Vec<Sort>, primary_key: &str) -> &mut Self {
+        self.push(" ORDER BY ");
+        for sort in sort {
+            self.push_identifier(&sort.field);
+            self.push(if sort.descending { " IS NULL, " } else { " IS NULL DESC, " });
+            self.push_identifier(&sort.field);
+            self.push(if sort.descending { " DESC, " } else { " ASC, " });
+        }
+        self.push_identifier(primary_key)
+    }
+
+    fn into_parts(self) -> Result<(String, DB::Arguments<'q>), CrudError> {
+        let arguments = self.arguments
+            .map_err(|e| CrudError::Validation(e.to_string()))?;
+        Ok((self.sql, arguments))
+    }
+}
+
+/// Turns a create or update body into `(column, value)` pairs of the table.
+fn columns<Schema: SqlxTable>(item: impl Serialize) -> Result<Vec<(&'static str, Value)>, CrudError> {
+    let Value::Object(fields) = serde_json::to_value(item)? else {
+        return Err(CrudError::Validation("expected a JSON object".to_string()));
+    };
+
+    fields.into_iter()
+        .map(|(field, value)| {
+            Schema::COLUMNS.iter()
+                .find(|column| **column == field)
+                .map(|column| (*column, value))
+                .ok_or_else(|| CrudError::Validation(format!("unknown column `{}`", field)))
+        })
+        .collect()
+}
+
+impl<DB: Database> CRUDRepository for SqlxRepository<DB> {}
+
+impl<DB, Schema, PrimaryKeyType> ReadDeleteRepository<Schema, PrimaryKeyType> for SqlxRepository<DB>
+where
+    DB: Database,
+    for<'c> &'c Pool<DB>: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'a> bool: Encode<'a, DB> + Type<DB>,
+    for<'a> i64: Encode<'a, DB> + Type<DB>,
+    for<'a> f64: Encode<'a, DB> + Type<DB>,
+    for<'a> String: Encode<'a, DB> + Type<DB>,
+
+    (i64,): for<'r> FromRow<'r, DB::Row>,
+
+    Schema: SqlxTable + for<'r> FromRow<'r, DB::Row> + Send + Unpin,
+    PrimaryKeyType: for<'a> Encode<'a, DB> + Type<DB> + DeserializeOwned + Send + 'static,
+{
+    fn get_table_name() -> String {
+        Schema::TABLE_NAME.to_string()
+    }
+
+    fn get_primary_key_name(&self) -> String {
+        Schema::PRIMARY_KEY.to_string()
+    }
+
+    async fn list_items(&self, pagination: Pagination, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let mut query = Statement::new::<Schema>("SELECT * FROM ");
+        query.push_identifier(Schema::TABLE_NAME);
+        query.push_filters(filters, Vec::new());
+        query.push_sort(sort, Schema::PRIMARY_KEY);
+
+        if pagination.limit.is_some() || pagination.skip.is_some() {
+            // OFFSET without LIMIT is not valid in every dialect
+            query.push(" LIMIT ").push_bind(pagination.limit.map_or(Ok(i64::MAX), signed)?);
+            query.push(" OFFSET ").push_bind(signed(pagination.skip.unwrap_or(0))?);
+        }
+
+        let (sql, arguments) = query.into_parts()?;
+        Ok(sqlx::query_as_with(&sql, arguments).fetch_all(&self.pool).await?)
+    }
+
+    async fn list_items_after(&self, cursor: Option<Cursor>, limit: Option<u64>, filters: Vec<Filter>, sort: Vec<Sort>) -> Result<Vec<Schema>, CrudError> {
+        let keyset = match cursor {
+            Some(cursor) => cursor.keyset(&sort, Schema::PRIMARY_KEY),
+            None => Vec::new(),
+        };
+
+        let mut query = Statement::new::<Schema>("SELECT * FROM ");
+        query.push_identifier(Schema::TABLE_NAME);
+        query.push_filters(filters, keyset);
+        query.push_sort(sort, Schema::PRIMARY_KEY);
+
+        if let Some(limit) = limit {
+            query.push(" LIMIT ").push_bind(signed(limit)?);
+        }
+
+        let (sql, arguments) = query.into_parts()?;
+        Ok(sqlx::query_as_with(&sql, arguments).fetch_all(&self.pool).await?)
+    }
+
+    async fn count_items(&self, filters: Vec<Filter>) -> Result<u64, CrudError> {
+        let mut query = Statement::new::<Schema>("SELECT COUNT(*) FROM ");
+        query.push_identifier(Schema::TABLE_NAME);
+        query.push_filters(filters, Vec::new());
+
+        let (sql, arguments) = query.into_parts()?;
+        let count: i64 = sqlx::query_scalar_with(&sql, arguments).fetch_one(&self.pool).await?;
+        Ok(count as u64)
+    }
+
+    async fn get_item(&self, id: PrimaryKeyType) -> Result<Option<Schema>, CrudError> {
+        let mut query = Statement::new::<Schema>("SELECT * FROM ");
+        query.push_identifier(Schema::TABLE_NAME);
+        query.push(" WHERE ");
+        query.push_identifier(Schema::PRIMARY_KEY);
+        query.push(" = ").push_bind(id);
+
+        let (sql, arguments) = query.into_parts()?;
+        Ok(sqlx::query_as_with(&sql, arguments).fetch_optional(&self.pool).await?)
+    }
+
+    async fn delete_item(&self, id: PrimaryKeyType) -> Result<(), CrudError> {
+        let mut query = Statement::new::<Schema>("DELETE FROM ");
+        query.push_identifier(Schema::TABLE_NAME);
+        query.push(" WHERE ");
+        query.push_identifier(Schema::PRIMARY_KEY);
+        query.push(" = ").push_bind(id);
+        query.push(" RETURNING 1");
+
+        let (sql, arguments) = query.into_parts()?;
+        if sqlx::query_with(&sql, arguments).fetch_optional(&self.pool).await?.is_none() {
+            return Err(CrudError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete_items(&self, ids: Vec<PrimaryKeyType>) -> Result<usize, CrudError> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query = Statement::new::<Schema>("DELETE FROM ");
+        query.push_identifier(Schema::TABLE_NAME);
+        query.push_ids(Schema::PRIMARY_KEY, ids);
+        query.push(" RETURNING 1");
+
+        let (sql, arguments) = query.into_parts()?;
+        Ok(sqlx::query_with(&sql, arguments).fetch_all(&self.pool).await?.len())
+    }
+
+    async fn delete_all_items(&self, filters: Vec<Filter>) -> Result<usize, CrudError> {
+        let mut query = Statement::<DB>::new::<Schema>("DELETE FROM ");
+        query.push_identifier(Schema::TABLE_NAME);
+        query.push_filters(filters, Vec::new());
+        // the affected row count is not exposed generically by sqlx
+        query.push(" RETURNING 1");
+
+        let (sql, arguments) = query.into_parts()?;
+        Ok(sqlx::query_with(&sql, arguments).fetch_all(&self.pool).await?.len())
+    }
+}
+
+impl<DB, Schema, CreateSchema> CreateRepository<Schema, CreateSchema> for SqlxRepository<DB>
+where
+    DB: Database,
+    for<'c> &'c Pool<DB>: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'a> bool: Encode<'a, DB> + Type<DB>,
+    for<'a> i64: Encode<'a, DB> + Type<DB>,
+    for<'a> f64: Encode<'a, DB> + Type<DB>,
+    for<'a> String: Encode<'a, DB> + Type<DB>,
+
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+
+    Schema: SqlxTable + for<'r> FromRow<'r, DB::Row> + Send + Unpin,
+    CreateSchema: Serialize + Send,
+{
+    async fn create_item(&self, new_item: CreateSchema) -> Result<Schema, CrudError> {
+        let (sql, arguments) = Statement::insert::<Schema>(columns::<Schema>(new_item)?).into_parts()?;
+        Ok(sqlx::query_as_with(&sql, arguments).fetch_one(&self.pool).await?)
+    }
+
+    async fn create_items(&self, new_items: Vec<CreateSchema>) -> Result<Vec<Schema>, CrudError> {
+        let mut transaction = self.pool.begin().await?;
+        let mut items = Vec::with_capacity(new_items.len());
+        for new_item in new_items {
+            let (sql, arguments) = Statement::insert::<Schema>(columns::<Schema>(new_item)?).into_parts()?;
+            items.push(sqlx::query_as_with(&sql, arguments).fetch_one(&mut *transaction).await?);
+        }
+        transaction.commit().await?;
+
+        Ok(items)
+    }
+}
+
+impl<DB, Schema, PrimaryKeyType, UpdateSchema> UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> for SqlxRepository<DB>
+where
+    DB: Database,
+    for<'c> &'c Pool<DB>: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'a> bool: Encode<'a, DB> + Type<DB>,
+    for<'a> i64: Encode<'a, DB> + Type<DB>,
+    for<'a> f64: Encode<'a, DB> + Type<DB>,
+    for<'a> String: Encode<'a, DB> + Type<DB>,
+    (i64,): for<'r> FromRow<'r, DB::Row>,
+
+    Schema: SqlxTable + for<'r> FromRow<'r, DB::Row> + Send + Unpin,
+    PrimaryKeyType: for<'a> Encode<'a, DB> + Type<DB> + DeserializeOwned + Send + 'static,
+    UpdateSchema: Serialize + Send,
+{
+    async fn update_item(&self, id: PrimaryKeyType, item: UpdateSchema) -> Result<Schema, CrudError> {
+        // only the serialized fields are written, a null one sets the column to NULL
+        let columns = columns::<Schema>(item)?;
+
+        if columns.is_empty() {
+            return ReadDeleteRepository::<Schema, PrimaryKeyType>::get_item(self, id).await?
+                .ok_or(CrudError::NotFound);
+        }
+
+        let mut query = Statement::new::<Schema>("UPDATE ");
+        query.push_identifier(Schema::TABLE_NAME);
+        query.push(" SET ");
+        for (i, (column, value)) in columns.into_iter().enumerate() {
+            if i > 0 {
+                query.push(", ");
+            }
+            query.push_identifier(column);
+            query.push(" = ");
+            query.push_value(column, value);
+        }
+        query.push(" WHERE ");
+        query.push_identifier(Schema::PRIMARY_KEY);
+        query.push(" = ").push_bind(id);
+        query.push(" RETURNING *");
+
+        let (sql, arguments) = query.into_parts()?;
+        sqlx::query_as_with(&sql, arguments).fetch_optional(&self.pool).await?
+            .ok_or(CrudError::NotFound)
+    }
+
+    async fn update_items(&self, ids: Vec<PrimaryKeyType>, item: UpdateSchema) -> Result<usize, CrudError> {
+        let columns = columns::<Schema>(item)?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        // without changes the matching items are only counted
+        let mut query = Statement::new::<Schema>(if columns.is_empty() { "SELECT 1 FROM " } else { "UPDATE " });
+        query.push_identifier(Schema::TABLE_NAME);
+        let returning = !columns.is_empty();
+        for (i, (column, value)) in columns.into_iter().enumerate() {
+            query.push(if i == 0 { " SET " } else { ", " });
+            query.push_identifier(column);
+            query.push(" = ");
+            query.push_value(column, value);
+        }
+        query.push_ids(Schema::PRIMARY_KEY, ids);
+        if returning {
+            query.push(" RETURNING 1");
+        }
+
+        let (sql, arguments) = query.into_parts()?;
+        Ok(sqlx::query_with(&sql, arguments).fetch_all(&self.pool).await?.len())
+    }
+}
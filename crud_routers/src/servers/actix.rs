@@ -1,132 +1,372 @@
-use actix_web::{web, HttpResponse, Scope};
-use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{web, Error, HttpRequest, HttpResponse, ResponseError, Scope};
+use actix_web::error::JsonPayloadError;
+use actix_web::http::{header, StatusCode};
+use actix_web::web::{Data, Json, JsonConfig, Path, PathConfig, Query, QueryConfig};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use tokio::sync::Mutex;
+use serde_json::Value;
 use crate::servers::ApiServer;
-use crate::{CrudRouterBuilder, Assigned, Empty, Assignable, Pagination};
-use crate::repositories::{CreateRepository, ReadDeleteRepository, UpdateRepository};
+use crate::servers::error::ErrorResponse;
+use crate::{CrudRouterBuilder, Assigned, Empty, Assignable, Cursor, CursorPage, CursorPagination, ListFormat, OffsetPage, Pagination, PaginationMode, RouterConfig, StatusCodes, IdempotentResponse, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER, TOTAL_COUNT_HEADER};
+use crate::repositories::{CreateRepository, CrudError, ReadDeleteRepository, UpdateRepository};
+use crate::patch::{json_patch, merge_patch, patch_item, replacement, PatchFormat};
+use crate::batch::{parse_ids, DeleteTarget};
+use crate::status::location;
+use crate::etag::{none_match, pin_version};
+use crate::idempotency::Reservation;
 
 pub struct ActixServer {}
 
+impl ResponseError for ErrorResponse {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        HttpResponse::build(status)
+            .content_type(self.format.content_type())
+            .body(self.body(status.canonical_reason().unwrap_or_default()))
+    }
+}
+
 impl ApiServer for ActixServer {
     fn get_id_path(prefix: &str) -> String {
         format!("/{}/{{id}}", prefix)
     }
 }
 
+/// Responds with the item and its ETag, or with 304 if it matches `If-None-Match`.
+fn item_response(config: &RouterConfig, version_field: Option<&str>, item: impl Serialize, if_none_match: Option<&str>) -> HttpResponse {
+    let Some(etag) = config.etag(version_field, &item) else {
+        return HttpResponse::Ok().json(item);
+    };
+    if if_none_match.is_some_and(|if_none_match| none_match(if_none_match, &etag)) {
+        return HttpResponse::NotModified().insert_header((header::ETAG, etag)).finish();
+    }
+    HttpResponse::Ok().insert_header((header::ETAG, etag)).json(item)
+}
+
+/// Responds with a create item response that is stored under the `Idempotency-Key` of the request.
+fn replayed_response(response: IdempotentResponse) -> HttpResponse {
+    let mut replayed = HttpResponse::build(StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK));
+    replayed.content_type("application/json");
+    replayed.insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"));
+    if let Some(location) = response.location {
+        replayed.insert_header((header::LOCATION, location));
+    }
+    replayed.body(response.body)
+}
+
+fn header_value(request: &HttpRequest, name: impl header::AsHeaderName) -> Option<&str> {
+    request.headers().get(name).and_then(|value| value.to_str().ok())
+}
+
+impl ActixServer {
+    /// Scoped to the prefix, so that routers with other prefixes can be registered next to it.
+    fn scope(prefix: &str, config: RouterConfig) -> Scope {
+        let json_config = config.clone();
+        let path_config = config.clone();
+        let query_config = config.clone();
+
+        web::scope(&ActixServer::get_path(prefix))
+            .app_data(JsonConfig::default().error_handler(move |error, _| {
+                let status = match &error {
+                    JsonPayloadError::ContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    JsonPayloadError::Deserialize(e) if e.is_data() => StatusCode::UNPROCESSABLE_ENTITY,
+                    error => error.status_code(),
+                };
+                json_config.error_with_status(status.as_u16(), error.to_string()).into()
+            }))
+            .app_data(PathConfig::default().error_handler(move |error, _| {
+                path_config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), error.to_string()).into()
+            }))
+            .app_data(QueryConfig::default().error_handler(move |error, _| {
+                query_config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), error.to_string()).into()
+            }))
+            .app_data(Data::new(config))
+    }
+}
+
 impl<R, Schema, PrimaryKeyType, CreateSchema, UpdateSchema> CrudRouterBuilder<'_, Assigned<ActixServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, UpdateSchema>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
     CreateSchema: Assignable + 'static,
     UpdateSchema: Assignable + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
 
     async fn list_items_route(
-        state: Data<Mutex<R>>,
-        pagination: Query<Pagination>
-    ) -> Json<Vec<Schema>>{
-        let mut state = state.lock().await;
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        pagination: Result<Query<Pagination>, Error>,
+        cursor_pagination: Result<Query<CursorPagination>, Error>,
+        query: Query<Vec<(String, String)>>
+    ) -> Result<HttpResponse, ErrorResponse>{
+        let filters = config.parse_filters(&query).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+        let sort = config.parse_sort(&query).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+
+        match config.pagination_mode {
+            PaginationMode::Offset => {
+                let Query(pagination) = pagination.map_err(|e| config.error_with_status(e.as_response_error().status_code().as_u16(), e.to_string()))?;
+                let pagination = config.pagination(pagination).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+                if config.list_format == ListFormat::Array {
+                    let items = state.list_items(pagination, filters, sort).await.map_err(|e| config.error(e))?;
+                    return Ok(HttpResponse::Ok().json(items));
+                }
 
-        Json(R::list_items(&mut state, pagination.into_inner()).await)
+                let page = OffsetPage::load(state.get_ref(), pagination, filters, sort).await.map_err(|e| config.error(e))?;
+                if config.list_format == ListFormat::TotalCountHeader {
+                    Ok(HttpResponse::Ok()
+                        .insert_header((TOTAL_COUNT_HEADER, page.total.to_string()))
+                        .json(page.items))
+                } else {
+                    Ok(HttpResponse::Ok().json(page))
+                }
+            }
+            PaginationMode::Cursor => {
+                let Query(CursorPagination { cursor, limit }) = cursor_pagination.map_err(|e| config.error_with_status(e.as_response_error().status_code().as_u16(), e.to_string()))?;
+                let cursor = cursor.map(|cursor| Cursor::decode::<PrimaryKeyType>(&cursor, &config.sort_types(&sort)))
+                    .transpose()
+                    .map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+                let limit = config.limit(limit).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+                let page = CursorPage::load(&config, state.get_ref(), cursor, limit, filters, sort).await?;
+                Ok(HttpResponse::Ok().json(page))
+            }
+        }
     }
     async fn get_item_route(
-        state: Data<Mutex<R>>,
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        request: HttpRequest,
         id: Path<PrimaryKeyType>
-    ) -> Json<Option<Schema>> {
-        let mut state = state.lock().await;
-
-        Json(state.get_item(id.into_inner()).await)
+    ) -> Result<HttpResponse, ErrorResponse> {
+        let version_field = config.version_field(state.get_ref())?;
+        let item = state.get_item(id.into_inner()).await
+            .and_then(|item| item.ok_or(CrudError::NotFound))
+            .map_err(|e| config.error(e))?;
+        Ok(item_response(&config, version_field, item, header_value(&request, header::IF_NONE_MATCH)))
+    }
+    async fn item_exists_route(
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        id: Path<PrimaryKeyType>
+    ) -> Result<HttpResponse, ErrorResponse> {
+        match state.exists(id.into_inner()).await.map_err(|e| config.error(e))? {
+            true => Ok(HttpResponse::Ok().finish()),
+            false => Err(config.error(CrudError::NotFound)),
+        }
+    }
+    async fn count_items_route(
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        query: Query<Vec<(String, String)>>
+    ) -> Result<HttpResponse, ErrorResponse> {
+        let filters = config.parse_filters(&query).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+        let count = state.count_items(filters).await.map_err(|e| config.error(e))?;
+        Ok(HttpResponse::Ok()
+            .insert_header((TOTAL_COUNT_HEADER, count.to_string()))
+            .finish())
     }
     async fn delete_item_route(
-        state: Data<Mutex<R>>,
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        request: HttpRequest,
         id: Path<PrimaryKeyType>
-    ) -> HttpResponse {
-        let mut state = state.lock().await;
-
-        state.delete_item(id.into_inner()).await;
-        HttpResponse::Ok().finish()
+    ) -> Result<HttpResponse, ErrorResponse> {
+        let id = id.into_inner();
+        let deleted = match config.if_match(state.get_ref(), id.clone(), header_value(&request, header::IF_MATCH)).await? {
+            Some(version) => state.delete_item_at_version(id, version).await,
+            None => state.delete_item(id).await,
+        };
+        deleted.map_err(|e| config.error(e))?;
+        match config.status_codes {
+            StatusCodes::Rest => Ok(HttpResponse::NoContent().finish()),
+            StatusCodes::Legacy => Ok(HttpResponse::Ok().finish()),
+        }
     }
 
 
-    async fn delete_all_items_route(
-        state: Data<Mutex<R>>
-    ) -> Json<usize>{
-        let mut state = state.lock().await;
+    async fn delete_items_route(
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        query: Query<Vec<(String, String)>>
+    ) -> Result<Json<usize>, ErrorResponse>{
+        let target = config.delete_target(&query).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
 
-        Json(state.delete_all_items().await)
+        let deleted = match target {
+            DeleteTarget::Ids(ids) => state.delete_items(ids).await,
+            DeleteTarget::Matching(filters) => state.delete_all_items(filters).await,
+        };
+        Ok(Json(deleted.map_err(|e| config.error(e))?))
     }
 }
 
 impl<R, Schema, PrimaryKeyType, CreateSchema, UpdateSchema: Assignable> CrudRouterBuilder<'_, Assigned<ActixServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, UpdateSchema>
 where
-    R: CreateRepository<Schema, CreateSchema>,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema>,
     Schema: Serialize + Send,
     CreateSchema: DeserializeOwned + Send,
 {
     async fn create_item_route(
-        state: Data<Mutex<R>>,
-        Json(new_item): Json<CreateSchema>
-    ) -> Json<Schema>{
-        let mut state = state.lock().await;
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        request: HttpRequest,
+        Json(new_item): Json<Value>
+    ) -> Result<HttpResponse, ErrorResponse>{
+        let key = config.idempotency_key(request.path(), header_value(&request, IDEMPOTENCY_KEY_HEADER));
+        let reservation = match config.reserve(key, &new_item).await.map_err(|e| config.error(e))? {
+            Reservation::Replay(response) => return Ok(replayed_response(response)),
+            reservation => reservation,
+        };
+
+        let created = match serde_json::from_value::<CreateSchema>(new_item) {
+            Ok(new_item) => state.create_item(new_item).await,
+            Err(e) => Err(e.into()),
+        };
+        let item = match created {
+            Ok(item) => item,
+            Err(e) => {
+                config.release(reservation).await;
+                return Err(config.error(e));
+            }
+        };
+        let (status, location) = match config.status_codes {
+            StatusCodes::Rest => (StatusCode::CREATED, location(request.path(), &item, &state.get_primary_key_name())),
+            StatusCodes::Legacy => (StatusCode::OK, None),
+        };
+        config.remember(reservation, status.as_u16(), location.as_deref(), &item).await;
 
-        Json(state.create_item(new_item).await)
+        let mut response = HttpResponse::build(status);
+        if let Some(location) = location {
+            response.insert_header((header::LOCATION, location));
+        }
+        Ok(response.json(item))
+    }
+
+    async fn create_items_route(
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        Json(new_items): Json<Vec<CreateSchema>>
+    ) -> Result<HttpResponse, ErrorResponse>{
+        let items = state.create_items(new_items).await.map_err(|e| config.error(e))?;
+        let status = match config.status_codes {
+            StatusCodes::Rest => StatusCode::CREATED,
+            StatusCodes::Legacy => StatusCode::OK,
+        };
+        Ok(HttpResponse::build(status).json(items))
     }
 
 }
 
 impl<R, Schema, PrimaryKeyType, CreateSchema: Assignable, UpdateSchema> CrudRouterBuilder<'_, Assigned<ActixServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, Assigned<UpdateSchema>>
 where
-    R: UpdateRepository<Schema, PrimaryKeyType, UpdateSchema>,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema>,
     Schema: Serialize + Send,
-    UpdateSchema: DeserializeOwned + Send,
+    UpdateSchema: Serialize + DeserializeOwned + Send,
+    PrimaryKeyType: DeserializeOwned + Clone + Send,
 {
     async fn update_item_route(
-        state: Data<Mutex<R>>,
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        request: HttpRequest,
         id: Path<PrimaryKeyType>,
-        Json(item): Json<UpdateSchema>
-    ) -> Json<Schema>{
-        let mut state = state.lock().await;
+        Json(mut item): Json<Value>
+    ) -> Result<HttpResponse, ErrorResponse>{
+        let id = id.into_inner();
+        let version_field = config.version_field(state.get_ref())?;
+        let version = config.if_match(state.get_ref(), id.clone(), header_value(&request, header::IF_MATCH)).await?;
+        pin_version(&mut item, state.get_version_column(), version);
+        let item = replacement(item, state.get_version_column()).map_err(|e| config.error(e))?;
+        let item = state.update_item(id, item).await.map_err(|e| config.error(e))?;
+        Ok(item_response(&config, version_field, item, None))
+    }
 
-        Json(state.update_item(id.into_inner(), item).await)
+    async fn patch_item_route(
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        request: HttpRequest,
+        id: Path<PrimaryKeyType>,
+        Json(patch): Json<Value>
+    ) -> Result<HttpResponse, ErrorResponse>{
+        let id = id.into_inner();
+        let Some(patch_format) = config.patch_format(header_value(&request, header::CONTENT_TYPE)) else {
+            return Err(config.error_with_status(StatusCode::UNSUPPORTED_MEDIA_TYPE.as_u16(), "this patch format is not supported"));
+        };
+        let version_field = config.version_field(state.get_ref())?;
+        let version = config.if_match(state.get_ref(), id.clone(), header_value(&request, header::IF_MATCH)).await?;
+
+        let item = match patch_format {
+            PatchFormat::MergePatch => patch_item::<_, _, _, UpdateSchema>(state.get_ref(), id, version, |item| {
+                merge_patch(item, patch);
+                Ok(())
+            }).await,
+            PatchFormat::JsonPatch => {
+                let patch = serde_json::from_value(patch).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e.to_string()))?;
+                patch_item::<_, _, _, UpdateSchema>(state.get_ref(), id, version, |item| json_patch(item, &patch)).await
+            }
+        };
+        Ok(item_response(&config, version_field, item.map_err(|e| config.error(e))?, None))
     }
 
+    async fn update_items_route(
+        state: Data<R>,
+        config: Data<RouterConfig>,
+        query: Query<Vec<(String, String)>>,
+        Json(item): Json<UpdateSchema>
+    ) -> Result<Json<usize>, ErrorResponse>{
+        let ids = parse_ids(&query)
+            .and_then(|ids| ids.ok_or_else(|| String::from("`ids` is required")))
+            .map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+        Ok(Json(state.update_items(ids, item).await.map_err(|e| config.error(e))?))
+    }
 }
 
 
 impl<R, Schema, PrimaryKeyType, CreateSchema, UpdateSchema> CrudRouterBuilder<'_, Assigned<ActixServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, Assigned<UpdateSchema>>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
     CreateSchema: DeserializeOwned + Send + 'static,
-    UpdateSchema: DeserializeOwned + Send + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    UpdateSchema: Serialize + DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
     pub fn build_router(self) -> Scope {
-        let mut s = web::scope("");
-        let prefix = self.get_prefix();
-        let path = ActixServer::get_path(&prefix);
-        let id_path = ActixServer::get_id_path(&prefix);
+        let mut s = ActixServer::scope(self.get_prefix(), self.router_config());
 
         if !self.list_items_route_disabled {
-            s = s.route(&path, web::get().to(Self::list_items_route))
+            s = s.route("", web::get().to(Self::list_items_route))
         }
         if !self.create_item_route_disabled {
-            s = s.route(&path, web::post().to(Self::create_item_route))
+            s = s.route("", web::post().to(Self::create_item_route))
+        }
+        if !self.create_items_route_disabled {
+            s = s.route("/bulk", web::post().to(Self::create_items_route))
         }
-        if !self.delete_all_items_route_disabled {
-            s = s.route(&path, web::delete().to(Self::delete_all_items_route))
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            s = s.route("", web::delete().to(Self::delete_items_route))
         }
         if !self.get_item_route_disabled {
-            s = s.route(&id_path, web::get().to(Self::get_item_route))
+            s = s.route("/{id}", web::get().to(Self::get_item_route))
+        }
+        if !self.item_exists_route_disabled {
+            s = s.route("/{id}", web::head().to(Self::item_exists_route))
+        }
+        if !self.count_items_route_disabled {
+            s = s.route("", web::head().to(Self::count_items_route))
         }
         if !self.update_item_route_disabled {
-            s = s.route(&id_path, web::put().to(Self::update_item_route))
+            s = s.route("/{id}", web::put().to(Self::update_item_route))
+        }
+        if !self.update_items_route_disabled {
+            s = s.route("", web::patch().to(Self::update_items_route))
+        }
+        if !self.patch_item_route_disabled || self.json_patch_enabled {
+            s = s.route("/{id}", web::patch().to(Self::patch_item_route))
         }
         if !self.delete_item_route_disabled {
-            s = s.route(&id_path, web::delete().to(Self::delete_item_route))
+            s = s.route("/{id}", web::delete().to(Self::delete_item_route))
         }
 
         s
@@ -135,31 +375,37 @@ where
 
 impl<R, Schema, PrimaryKeyType, CreateSchema> CrudRouterBuilder<'_, Assigned<ActixServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, Empty>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
     CreateSchema: DeserializeOwned + Send + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
     pub fn build_router(self) -> Scope {
-        let mut s = web::scope("");
-        let prefix = self.get_prefix();
-        let path = ActixServer::get_path(&prefix);
-        let id_path = ActixServer::get_id_path(&prefix);
+        let mut s = ActixServer::scope(self.get_prefix(), self.router_config());
 
         if !self.list_items_route_disabled {
-            s = s.route(&path, web::get().to(Self::list_items_route))
+            s = s.route("", web::get().to(Self::list_items_route))
         }
         if !self.create_item_route_disabled {
-            s = s.route(&path, web::post().to(Self::create_item_route))
+            s = s.route("", web::post().to(Self::create_item_route))
         }
-        if !self.delete_all_items_route_disabled {
-            s = s.route(&path, web::delete().to(Self::delete_all_items_route))
+        if !self.create_items_route_disabled {
+            s = s.route("/bulk", web::post().to(Self::create_items_route))
+        }
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            s = s.route("", web::delete().to(Self::delete_items_route))
         }
         if !self.get_item_route_disabled {
-            s = s.route(&id_path, web::get().to(Self::get_item_route))
+            s = s.route("/{id}", web::get().to(Self::get_item_route))
+        }
+        if !self.item_exists_route_disabled {
+            s = s.route("/{id}", web::head().to(Self::item_exists_route))
+        }
+        if !self.count_items_route_disabled {
+            s = s.route("", web::head().to(Self::count_items_route))
         }
         if !self.delete_item_route_disabled {
-            s = s.route(&id_path, web::delete().to(Self::delete_item_route))
+            s = s.route("/{id}", web::delete().to(Self::delete_item_route))
         }
 
         s
@@ -168,31 +414,40 @@ where
 
 impl<R, Schema, PrimaryKeyType, UpdateSchema> CrudRouterBuilder<'_, Assigned<ActixServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Empty, Assigned<UpdateSchema>>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
-    UpdateSchema: DeserializeOwned + Send + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    UpdateSchema: Serialize + DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
     pub fn build_router(self) -> Scope {
-        let mut s = web::scope("");
-        let prefix = self.get_prefix();
-        let path = ActixServer::get_path(&prefix);
-        let id_path = ActixServer::get_id_path(&prefix);
+        let mut s = ActixServer::scope(self.get_prefix(), self.router_config());
 
         if !self.list_items_route_disabled {
-            s = s.route(&path, web::get().to(Self::list_items_route))
+            s = s.route("", web::get().to(Self::list_items_route))
         }
-        if !self.delete_all_items_route_disabled {
-            s = s.route(&path, web::delete().to(Self::delete_all_items_route))
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            s = s.route("", web::delete().to(Self::delete_items_route))
         }
         if !self.get_item_route_disabled {
-            s = s.route(&id_path, web::get().to(Self::get_item_route))
+            s = s.route("/{id}", web::get().to(Self::get_item_route))
+        }
+        if !self.item_exists_route_disabled {
+            s = s.route("/{id}", web::head().to(Self::item_exists_route))
+        }
+        if !self.count_items_route_disabled {
+            s = s.route("", web::head().to(Self::count_items_route))
         }
         if !self.update_item_route_disabled {
-            s = s.route(&id_path, web::put().to(Self::update_item_route))
+            s = s.route("/{id}", web::put().to(Self::update_item_route))
+        }
+        if !self.update_items_route_disabled {
+            s = s.route("", web::patch().to(Self::update_items_route))
+        }
+        if !self.patch_item_route_disabled || self.json_patch_enabled {
+            s = s.route("/{id}", web::patch().to(Self::patch_item_route))
         }
         if !self.delete_item_route_disabled {
-            s = s.route(&id_path, web::delete().to(Self::delete_item_route))
+            s = s.route("/{id}", web::delete().to(Self::delete_item_route))
         }
 
         s
@@ -202,27 +457,30 @@ where
 
 impl<R, Schema, PrimaryKeyType> CrudRouterBuilder<'_, Assigned<ActixServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Empty, Empty>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
     pub fn build_router(self) -> Scope {
-        let mut s = web::scope("");
-        let prefix = self.get_prefix();
-        let path = ActixServer::get_path(&prefix);
-        let id_path = ActixServer::get_id_path(&prefix);
+        let mut s = ActixServer::scope(self.get_prefix(), self.router_config());
 
         if !self.list_items_route_disabled {
-            s = s.route(&path, web::get().to(Self::list_items_route))
+            s = s.route("", web::get().to(Self::list_items_route))
         }
-        if !self.delete_all_items_route_disabled {
-            s = s.route(&path, web::delete().to(Self::delete_all_items_route))
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            s = s.route("", web::delete().to(Self::delete_items_route))
         }
         if !self.get_item_route_disabled {
-            s = s.route(&id_path, web::get().to(Self::get_item_route))
+            s = s.route("/{id}", web::get().to(Self::get_item_route))
+        }
+        if !self.item_exists_route_disabled {
+            s = s.route("/{id}", web::head().to(Self::item_exists_route))
+        }
+        if !self.count_items_route_disabled {
+            s = s.route("", web::head().to(Self::count_items_route))
         }
         if !self.delete_item_route_disabled {
-            s = s.route(&id_path, web::delete().to(Self::delete_item_route))
+            s = s.route("/{id}", web::delete().to(Self::delete_item_route))
         }
 
         s
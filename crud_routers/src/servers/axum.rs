@@ -1,114 +1,322 @@
 use std::sync::Arc;
-use axum::extract::{Path, Query, State};
-use axum::{routing, Json, Router};
+use axum::extract::{OriginalUri, Path, Query, State};
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{routing, Extension, Json, Router};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use tokio::sync::Mutex;
+use serde_json::Value;
 use crate::servers::ApiServer;
-use crate::{CrudRouterBuilder, Assigned, Empty, Assignable, Pagination};
-use crate::repositories::{CreateRepository, ReadDeleteRepository, UpdateRepository};
+use crate::servers::error::ErrorResponse;
+use crate::{CrudRouterBuilder, Assigned, Empty, Assignable, Cursor, CursorPage, CursorPagination, ListFormat, OffsetPage, Pagination, PaginationMode, RouterConfig, StatusCodes, IdempotentResponse, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER, TOTAL_COUNT_HEADER};
+use crate::repositories::{CreateRepository, CrudError, ReadDeleteRepository, UpdateRepository};
+use crate::patch::{json_patch, merge_patch, patch_item, replacement, PatchFormat};
+use crate::batch::{parse_ids, DeleteTarget};
+use crate::status::location;
+use crate::etag::{none_match, pin_version};
+use crate::idempotency::Reservation;
 
 pub struct AxumServer;
 
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = self.body(status.canonical_reason().unwrap_or_default());
+        (status, [(header::CONTENT_TYPE, self.format.content_type())], body).into_response()
+    }
+}
+
 impl ApiServer for AxumServer {
     fn get_id_path(prefix: &str) -> String {
         format!("/{}/{{id}}", prefix)
     }
 }
 
+/// Responds with the item and its ETag, or with 304 if it matches `If-None-Match`.
+fn item_response(config: &RouterConfig, version_field: Option<&str>, item: impl Serialize, if_none_match: Option<&str>) -> Response {
+    let Some(etag) = config.etag(version_field, &item) else {
+        return Json(item).into_response();
+    };
+    if if_none_match.is_some_and(|if_none_match| none_match(if_none_match, &etag)) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    ([(header::ETAG, etag)], Json(item)).into_response()
+}
+
+/// Responds with a create item response that is stored under the `Idempotency-Key` of the request.
+fn replayed_response(response: IdempotentResponse) -> Response {
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK);
+    let headers = [(header::CONTENT_TYPE.as_str(), "application/json"), (IDEMPOTENT_REPLAYED_HEADER, "true")];
+    let mut replayed = (status, headers, response.body).into_response();
+    if let Some(location) = response.location.and_then(|location| HeaderValue::from_str(&location).ok()) {
+        replayed.headers_mut().insert(header::LOCATION, location);
+    }
+    replayed
+}
+
+fn header_value(headers: &HeaderMap, name: impl header::AsHeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
 impl<R, Schema, PrimaryKeyType, CreateSchema, UpdateSchema> CrudRouterBuilder<'_, Assigned<AxumServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, UpdateSchema>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
     CreateSchema: Assignable + 'static,
     UpdateSchema: Assignable + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
 
     async fn list_items_route(
-        state: State<Arc<Mutex<R>>>,
-        Query(pagination): Query<Pagination>
-    ) -> Json<Vec<Schema>>{
-        let mut state = state.lock().await;
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        pagination: Result<Query<Pagination>, QueryRejection>,
+        cursor_pagination: Result<Query<CursorPagination>, QueryRejection>,
+        query: Result<Query<Vec<(String, String)>>, QueryRejection>
+    ) -> Result<Response, ErrorResponse>{
+        let Query(query) = query.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let filters = config.parse_filters(&query).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+        let sort = config.parse_sort(&query).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+
+        match config.pagination_mode {
+            PaginationMode::Offset => {
+                let Query(pagination) = pagination.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+                let pagination = config.pagination(pagination).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+                if config.list_format == ListFormat::Array {
+                    let items = state.list_items(pagination, filters, sort).await.map_err(|e| config.error(e))?;
+                    return Ok(Json(items).into_response());
+                }
 
-        R::list_items(&mut state, pagination).await.into()
+                let page = OffsetPage::load(state.0.as_ref(), pagination, filters, sort).await.map_err(|e| config.error(e))?;
+                if config.list_format == ListFormat::TotalCountHeader {
+                    Ok(([(TOTAL_COUNT_HEADER, page.total.to_string())], Json(page.items)).into_response())
+                } else {
+                    Ok(Json(page).into_response())
+                }
+            }
+            PaginationMode::Cursor => {
+                let Query(CursorPagination { cursor, limit }) = cursor_pagination.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+                let cursor = cursor.map(|cursor| Cursor::decode::<PrimaryKeyType>(&cursor, &config.sort_types(&sort)))
+                    .transpose()
+                    .map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+                let limit = config.limit(limit).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+                let page = CursorPage::load(&config, state.0.as_ref(), cursor, limit, filters, sort).await?;
+                Ok(Json(page).into_response())
+            }
+        }
     }
     async fn get_item_route(
-        state: State<Arc<Mutex<R>>>,
-        Path(id): Path<PrimaryKeyType>
-    ) -> Json<Option<Schema>> {
-        let mut state = state.lock().await;
-
-        state.get_item(id).await.into()
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        headers: HeaderMap,
+        id: Result<Path<PrimaryKeyType>, PathRejection>
+    ) -> Result<Response, ErrorResponse> {
+        let Path(id) = id.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let version_field = config.version_field(state.0.as_ref())?;
+        let item = state.get_item(id).await
+            .and_then(|item| item.ok_or(CrudError::NotFound))
+            .map_err(|e| config.error(e))?;
+        Ok(item_response(&config, version_field, item, header_value(&headers, header::IF_NONE_MATCH)))
+    }
+    async fn item_exists_route(
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        id: Result<Path<PrimaryKeyType>, PathRejection>
+    ) -> Result<StatusCode, ErrorResponse> {
+        let Path(id) = id.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        match state.exists(id).await.map_err(|e| config.error(e))? {
+            true => Ok(StatusCode::OK),
+            false => Err(config.error(CrudError::NotFound)),
+        }
+    }
+    async fn count_items_route(
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        query: Result<Query<Vec<(String, String)>>, QueryRejection>
+    ) -> Result<Response, ErrorResponse> {
+        let Query(query) = query.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let filters = config.parse_filters(&query).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+        let count = state.count_items(filters).await.map_err(|e| config.error(e))?;
+        Ok([(TOTAL_COUNT_HEADER, count.to_string())].into_response())
     }
     async fn delete_item_route(
-        state: State<Arc<Mutex<R>>>,
-        Path(id): Path<PrimaryKeyType>
-    ) {
-        let mut state = state.lock().await;
-
-        state.delete_item(id).await;
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        headers: HeaderMap,
+        id: Result<Path<PrimaryKeyType>, PathRejection>
+    ) -> Result<StatusCode, ErrorResponse> {
+        let Path(id) = id.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let deleted = match config.if_match(state.0.as_ref(), id.clone(), header_value(&headers, header::IF_MATCH)).await? {
+            Some(version) => state.delete_item_at_version(id, version).await,
+            None => state.delete_item(id).await,
+        };
+        deleted.map_err(|e| config.error(e))?;
+        match config.status_codes {
+            StatusCodes::Rest => Ok(StatusCode::NO_CONTENT),
+            StatusCodes::Legacy => Ok(StatusCode::OK),
+        }
     }
 
 
-    async fn delete_all_items_route(
-        state: State<Arc<Mutex<R>>>
-    ) -> Json<usize>{
-        let mut state = state.lock().await;
+    async fn delete_items_route(
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        query: Result<Query<Vec<(String, String)>>, QueryRejection>
+    ) -> Result<Json<usize>, ErrorResponse>{
+        let Query(query) = query.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let target = config.delete_target(&query).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
 
-        state.delete_all_items().await.into()
+        let deleted = match target {
+            DeleteTarget::Ids(ids) => state.delete_items(ids).await,
+            DeleteTarget::Matching(filters) => state.delete_all_items(filters).await,
+        };
+        Ok(deleted.map_err(|e| config.error(e))?.into())
     }
 }
 
 impl<R, Schema, PrimaryKeyType, CreateSchema, UpdateSchema: Assignable> CrudRouterBuilder<'_, Assigned<AxumServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, UpdateSchema>
 where
-    R: CreateRepository<Schema, CreateSchema>,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema>,
     Schema: Serialize + Send,
     CreateSchema: DeserializeOwned + Send,
 {
     async fn create_item_route(
-        state: State<Arc<Mutex<R>>>,
-        Json(new_item): Json<CreateSchema>
-    ) -> Json<Schema>{
-        let mut state = state.lock().await;
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        OriginalUri(uri): OriginalUri,
+        headers: HeaderMap,
+        new_item: Result<Json<Value>, JsonRejection>
+    ) -> Result<Response, ErrorResponse>{
+        let Json(new_item) = new_item.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let key = config.idempotency_key(uri.path(), header_value(&headers, IDEMPOTENCY_KEY_HEADER));
+        let reservation = match config.reserve(key, &new_item).await.map_err(|e| config.error(e))? {
+            Reservation::Replay(response) => return Ok(replayed_response(response)),
+            reservation => reservation,
+        };
+
+        let created = match serde_json::from_value::<CreateSchema>(new_item) {
+            Ok(new_item) => state.create_item(new_item).await,
+            Err(e) => Err(e.into()),
+        };
+        let item = match created {
+            Ok(item) => item,
+            Err(e) => {
+                config.release(reservation).await;
+                return Err(config.error(e));
+            }
+        };
+        let (status, location) = match config.status_codes {
+            StatusCodes::Rest => (StatusCode::CREATED, location(uri.path(), &item, &state.get_primary_key_name())),
+            StatusCodes::Legacy => (StatusCode::OK, None),
+        };
+        config.remember(reservation, status.as_u16(), location.as_deref(), &item).await;
 
-        state.create_item(new_item).await.into()
+        match location {
+            Some(location) => Ok((status, [(header::LOCATION, location)], Json(item)).into_response()),
+            None => Ok((status, Json(item)).into_response()),
+        }
+    }
+
+    async fn create_items_route(
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        new_items: Result<Json<Vec<CreateSchema>>, JsonRejection>
+    ) -> Result<Response, ErrorResponse>{
+        let Json(new_items) = new_items.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let items = state.create_items(new_items).await.map_err(|e| config.error(e))?;
+        let status = match config.status_codes {
+            StatusCodes::Rest => StatusCode::CREATED,
+            StatusCodes::Legacy => StatusCode::OK,
+        };
+        Ok((status, Json(items)).into_response())
     }
 
 }
 
 impl<R, Schema, PrimaryKeyType, CreateSchema: Assignable, UpdateSchema> CrudRouterBuilder<'_, Assigned<AxumServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, CreateSchema, Assigned<UpdateSchema>>
 where
-    R: UpdateRepository<Schema, PrimaryKeyType, UpdateSchema>,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema>,
     Schema: Serialize + Send,
-    UpdateSchema: DeserializeOwned + Send,
+    UpdateSchema: Serialize + DeserializeOwned + Send,
+    PrimaryKeyType: DeserializeOwned + Clone + Send,
 {
     async fn update_item_route(
-        state: State<Arc<Mutex<R>>>,
-        Path(id): Path<PrimaryKeyType>,
-        Json(item): Json<UpdateSchema>
-    ) -> Json<Schema>{
-        let mut state = state.lock().await;
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        headers: HeaderMap,
+        id: Result<Path<PrimaryKeyType>, PathRejection>,
+        item: Result<Json<Value>, JsonRejection>
+    ) -> Result<Response, ErrorResponse>{
+        let Path(id) = id.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let Json(mut item) = item.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let version_field = config.version_field(state.0.as_ref())?;
+        let version = config.if_match(state.0.as_ref(), id.clone(), header_value(&headers, header::IF_MATCH)).await?;
+        pin_version(&mut item, state.get_version_column(), version);
+        let item = replacement(item, state.get_version_column()).map_err(|e| config.error(e))?;
+        let item = state.update_item(id, item).await.map_err(|e| config.error(e))?;
+        Ok(item_response(&config, version_field, item, None))
+    }
+
+    async fn patch_item_route(
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        headers: HeaderMap,
+        id: Result<Path<PrimaryKeyType>, PathRejection>,
+        patch: Result<Json<Value>, JsonRejection>
+    ) -> Result<Response, ErrorResponse>{
+        let Path(id) = id.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let content_type = header_value(&headers, header::CONTENT_TYPE);
+        let Some(patch_format) = config.patch_format(content_type) else {
+            return Err(config.error_with_status(StatusCode::UNSUPPORTED_MEDIA_TYPE.as_u16(), "this patch format is not supported"));
+        };
+        let Json(patch) = patch.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let version_field = config.version_field(state.0.as_ref())?;
+        let version = config.if_match(state.0.as_ref(), id.clone(), header_value(&headers, header::IF_MATCH)).await?;
 
-        state.update_item(id, item).await.into()
+        let item = match patch_format {
+            PatchFormat::MergePatch => patch_item::<_, _, _, UpdateSchema>(state.0.as_ref(), id, version, |item| {
+                merge_patch(item, patch);
+                Ok(())
+            }).await,
+            PatchFormat::JsonPatch => {
+                let patch = serde_json::from_value(patch).map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e.to_string()))?;
+                patch_item::<_, _, _, UpdateSchema>(state.0.as_ref(), id, version, |item| json_patch(item, &patch)).await
+            }
+        };
+        Ok(item_response(&config, version_field, item.map_err(|e| config.error(e))?, None))
     }
 
+    async fn update_items_route(
+        state: State<Arc<R>>,
+        Extension(config): Extension<Arc<RouterConfig>>,
+        query: Result<Query<Vec<(String, String)>>, QueryRejection>,
+        item: Result<Json<UpdateSchema>, JsonRejection>
+    ) -> Result<Json<usize>, ErrorResponse>{
+        let Query(query) = query.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        let ids = parse_ids(&query)
+            .and_then(|ids| ids.ok_or_else(|| String::from("`ids` is required")))
+            .map_err(|e| config.error_with_status(StatusCode::BAD_REQUEST.as_u16(), e))?;
+        let Json(item) = item.map_err(|e| config.error_with_status(e.status().as_u16(), e.body_text()))?;
+        Ok(state.update_items(ids, item).await.map_err(|e| config.error(e))?.into())
+    }
 }
 
 
 impl<R, Schema, PrimaryKeyType, CreateSchema, UpdateSchema> CrudRouterBuilder<'_, Assigned<AxumServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, Assigned<UpdateSchema>>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
     CreateSchema: DeserializeOwned + Send + 'static,
-    UpdateSchema: DeserializeOwned + Send + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    UpdateSchema: Serialize + DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
-    pub fn build_router(self) -> Router<Arc<Mutex<R>>> {
+    pub fn build_router(self) -> Router<Arc<R>> {
         let mut r = Router::new();
         let prefix = self.get_prefix();
-        let path = AxumServer::get_path(&prefix);
-        let id_path = AxumServer::get_id_path(&prefix);
+        let path = AxumServer::get_path(prefix);
+        let id_path = AxumServer::get_id_path(prefix);
+        let bulk_path = AxumServer::get_bulk_path(prefix);
 
         if !self.list_items_route_disabled {
             r = r.route(&path, routing::get(Self::list_items_route))
@@ -116,35 +324,51 @@ where
         if !self.create_item_route_disabled {
             r = r.route(&path, routing::post(Self::create_item_route))
         }
-        if !self.delete_all_items_route_disabled {
-            r = r.route(&path, routing::delete(Self::delete_all_items_route))
+        if !self.create_items_route_disabled {
+            r = r.route(&bulk_path, routing::post(Self::create_items_route))
+        }
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            r = r.route(&path, routing::delete(Self::delete_items_route))
         }
         if !self.get_item_route_disabled {
             r = r.route(&id_path, routing::get(Self::get_item_route))
         }
+        if !self.item_exists_route_disabled {
+            r = r.route(&id_path, routing::head(Self::item_exists_route))
+        }
+        if !self.count_items_route_disabled {
+            r = r.route(&path, routing::head(Self::count_items_route))
+        }
         if !self.update_item_route_disabled {
             r = r.route(&id_path, routing::put(Self::update_item_route))
         }
+        if !self.update_items_route_disabled {
+            r = r.route(&path, routing::patch(Self::update_items_route))
+        }
+        if !self.patch_item_route_disabled || self.json_patch_enabled {
+            r = r.route(&id_path, routing::patch(Self::patch_item_route))
+        }
         if !self.delete_item_route_disabled {
             r = r.route(&id_path, routing::delete(Self::delete_item_route))
         }
 
-        r
+        r.layer(Extension(Arc::new(self.router_config())))
     }
 }
 
 impl<R, Schema, PrimaryKeyType, CreateSchema> CrudRouterBuilder<'_, Assigned<AxumServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Assigned<CreateSchema>, Empty>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + CreateRepository<Schema, CreateSchema> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
     CreateSchema: DeserializeOwned + Send + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
-    pub fn build_router(self) -> Router<Arc<Mutex<R>>> {
+    pub fn build_router(self) -> Router<Arc<R>> {
         let mut r = Router::new();
         let prefix = self.get_prefix();
-        let path = AxumServer::get_path(&prefix);
-        let id_path = AxumServer::get_id_path(&prefix);
+        let path = AxumServer::get_path(prefix);
+        let id_path = AxumServer::get_id_path(prefix);
+        let bulk_path = AxumServer::get_bulk_path(prefix);
 
         if !self.list_items_route_disabled {
             r = r.route(&path, routing::get(Self::list_items_route))
@@ -152,79 +376,106 @@ where
         if !self.create_item_route_disabled {
             r = r.route(&path, routing::post(Self::create_item_route))
         }
-        if !self.delete_all_items_route_disabled {
-            r = r.route(&path, routing::delete(Self::delete_all_items_route))
+        if !self.create_items_route_disabled {
+            r = r.route(&bulk_path, routing::post(Self::create_items_route))
+        }
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            r = r.route(&path, routing::delete(Self::delete_items_route))
         }
         if !self.get_item_route_disabled {
             r = r.route(&id_path, routing::get(Self::get_item_route))
         }
+        if !self.item_exists_route_disabled {
+            r = r.route(&id_path, routing::head(Self::item_exists_route))
+        }
+        if !self.count_items_route_disabled {
+            r = r.route(&path, routing::head(Self::count_items_route))
+        }
         if !self.delete_item_route_disabled {
             r = r.route(&id_path, routing::delete(Self::delete_item_route))
         }
 
-        r
+        r.layer(Extension(Arc::new(self.router_config())))
     }
 }
 
 impl<R, Schema, PrimaryKeyType, UpdateSchema> CrudRouterBuilder<'_, Assigned<AxumServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Empty, Assigned<UpdateSchema>>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + UpdateRepository<Schema, PrimaryKeyType, UpdateSchema> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
-    UpdateSchema: DeserializeOwned + Send + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    UpdateSchema: Serialize + DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
-    pub fn build_router(self) -> Router<Arc<Mutex<R>>> {
+    pub fn build_router(self) -> Router<Arc<R>> {
         let mut r = Router::new();
         let prefix = self.get_prefix();
-        let path = AxumServer::get_path(&prefix);
-        let id_path = AxumServer::get_id_path(&prefix);
+        let path = AxumServer::get_path(prefix);
+        let id_path = AxumServer::get_id_path(prefix);
 
         if !self.list_items_route_disabled {
             r = r.route(&path, routing::get(Self::list_items_route))
         }
-        if !self.delete_all_items_route_disabled {
-            r = r.route(&path, routing::delete(Self::delete_all_items_route))
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            r = r.route(&path, routing::delete(Self::delete_items_route))
         }
         if !self.get_item_route_disabled {
             r = r.route(&id_path, routing::get(Self::get_item_route))
         }
+        if !self.item_exists_route_disabled {
+            r = r.route(&id_path, routing::head(Self::item_exists_route))
+        }
+        if !self.count_items_route_disabled {
+            r = r.route(&path, routing::head(Self::count_items_route))
+        }
         if !self.update_item_route_disabled {
             r = r.route(&id_path, routing::put(Self::update_item_route))
         }
+        if !self.update_items_route_disabled {
+            r = r.route(&path, routing::patch(Self::update_items_route))
+        }
+        if !self.patch_item_route_disabled || self.json_patch_enabled {
+            r = r.route(&id_path, routing::patch(Self::patch_item_route))
+        }
         if !self.delete_item_route_disabled {
             r = r.route(&id_path, routing::delete(Self::delete_item_route))
         }
 
-        r
+        r.layer(Extension(Arc::new(self.router_config())))
     }
 }
 
 
 impl<R, Schema, PrimaryKeyType> CrudRouterBuilder<'_, Assigned<AxumServer>, R, Assigned<Schema>, Assigned<PrimaryKeyType>, Empty, Empty>
 where
-    R: ReadDeleteRepository<Schema, PrimaryKeyType> + Send + 'static,
+    R: ReadDeleteRepository<Schema, PrimaryKeyType> + Send + Sync + 'static,
     Schema: Serialize + Send + 'static,
-    PrimaryKeyType: DeserializeOwned + Send + 'static,
+    PrimaryKeyType: DeserializeOwned + Clone + Send + 'static,
 {
-    pub fn build_router(self) -> Router<Arc<Mutex<R>>> {
+    pub fn build_router(self) -> Router<Arc<R>> {
         let mut r = Router::new();
         let prefix = self.get_prefix();
-        let path = AxumServer::get_path(&prefix);
-        let id_path = AxumServer::get_id_path(&prefix);
+        let path = AxumServer::get_path(prefix);
+        let id_path = AxumServer::get_id_path(prefix);
 
         if !self.list_items_route_disabled {
             r = r.route(&path, routing::get(Self::list_items_route))
         }
-        if !self.delete_all_items_route_disabled {
-            r = r.route(&path, routing::delete(Self::delete_all_items_route))
+        if !self.delete_items_route_disabled || !self.delete_all_items_route_disabled {
+            r = r.route(&path, routing::delete(Self::delete_items_route))
         }
         if !self.get_item_route_disabled {
             r = r.route(&id_path, routing::get(Self::get_item_route))
         }
+        if !self.item_exists_route_disabled {
+            r = r.route(&id_path, routing::head(Self::item_exists_route))
+        }
+        if !self.count_items_route_disabled {
+            r = r.route(&path, routing::head(Self::count_items_route))
+        }
         if !self.delete_item_route_disabled {
             r = r.route(&id_path, routing::delete(Self::delete_item_route))
         }
 
-        r
+        r.layer(Extension(Arc::new(self.router_config())))
     }
 }
@@ -0,0 +1,103 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::{CrudError, RouterConfig};
+
+impl CrudError {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            CrudError::NotFound => 404,
+            CrudError::Conflict(_) => 409,
+            CrudError::Validation(_) => 422,
+            CrudError::PreconditionRequired(_) => 428,
+            CrudError::Connection(_) => 503,
+            CrudError::Backend(_) => 500,
+        }
+    }
+}
+
+/// Body format of the error responses of generated routes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// The error message as `text/plain`.
+    #[default]
+    PlainText,
+    /// [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) problem details as `application/problem+json`.
+    ProblemJson,
+}
+
+impl ErrorFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ErrorFormat::PlainText => "text/plain; charset=utf-8",
+            ErrorFormat::ProblemJson => "application/problem+json",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+}
+
+/// An error of a generated route, rendered with the router's [ErrorFormat].
+#[derive(Debug)]
+pub(crate) struct ErrorResponse {
+    pub(crate) status: u16,
+    pub(crate) detail: String,
+    pub(crate) format: ErrorFormat,
+}
+
+impl ErrorResponse {
+    pub(crate) fn new(status: u16, detail: impl Into<String>, format: ErrorFormat) -> Self {
+        Self {
+            status,
+            detail: detail.into(),
+            format,
+        }
+    }
+
+    /// Returns the body of the response, `title` is the reason phrase of the status code.
+    pub(crate) fn body(&self, title: &str) -> String {
+        match self.format {
+            ErrorFormat::PlainText => self.detail.clone(),
+            ErrorFormat::ProblemJson => {
+                let problem = ProblemDetails {
+                    problem_type: String::from("about:blank"),
+                    title: title.to_string(),
+                    status: self.status,
+                    detail: self.detail.clone(),
+                };
+                serde_json::to_string(&problem).unwrap_or_default()
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.detail)
+    }
+}
+
+impl RouterConfig {
+    /// The response of a failed request. Backend and connection errors may contain SQL or table
+    /// names, so they are logged and answered with a generic detail.
+    pub(crate) fn error(&self, error: CrudError) -> ErrorResponse {
+        let detail = match &error {
+            CrudError::Connection(_) => String::from("the database is unavailable"),
+            CrudError::Backend(_) => String::from("internal server error"),
+            error => return self.error_with_status(error.status_code(), error.to_string()),
+        };
+        log::error!("{}", error);
+        self.error_with_status(error.status_code(), detail)
+    }
+
+    pub(crate) fn error_with_status(&self, status: u16, detail: impl Into<String>) -> ErrorResponse {
+        ErrorResponse::new(status, detail, self.error_format)
+    }
+}
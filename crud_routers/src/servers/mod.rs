@@ -2,16 +2,24 @@
 mod axum;
 #[cfg(feature = "actix")]
 mod actix;
+mod error;
 
 #[cfg(feature = "axum")]
 pub use axum::AxumServer;
 #[cfg(feature = "actix")]
 pub use actix::ActixServer;
 
+pub use error::{ErrorFormat, ProblemDetails};
+pub(crate) use error::ErrorResponse;
+
 pub trait ApiServer {
     fn get_path(prefix: &str) -> String {
         format!("/{}", prefix)
     }
 
     fn get_id_path(prefix: &str) -> String;
+
+    fn get_bulk_path(prefix: &str) -> String {
+        format!("/{}/bulk", prefix)
+    }
 }
@@ -0,0 +1,65 @@
+use crate::RouterConfig;
+
+/// One key of the `sort` query parameter, `-field` sorts in descending order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sort {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl RouterConfig {
+    /// Parses the `sort` parameter of the query string, only sortable fields are accepted.
+    pub(crate) fn parse_sort(&self, query: &[(String, String)]) -> Result<Vec<Sort>, String> {
+        let mut sort = Vec::new();
+
+        for (_, value) in query.iter().filter(|(key, _)| key == "sort") {
+            for key in value.split(',').filter(|key| !key.is_empty()) {
+                let (field, descending) = match key.strip_prefix('-') {
+                    Some(field) => (field, true),
+                    None => (key, false),
+                };
+
+                if !self.sort_fields.iter().any(|(name, _)| name == field) {
+                    return Err(format!("`{}` is not sortable", field));
+                }
+                sort.push(Sort {
+                    field: field.to_string(),
+                    descending,
+                });
+            }
+        }
+
+        Ok(sort)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FieldType;
+
+    fn config() -> RouterConfig {
+        RouterConfig {
+            sort_fields: vec![(String::from("created_at"), FieldType::String), (String::from("title"), FieldType::String)],
+            ..RouterConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        let query = vec![(String::from("sort"), String::from("-created_at,title")), (String::from("limit"), String::from("5"))];
+
+        assert_eq!(config().parse_sort(&query).unwrap(), vec![
+            Sort { field: String::from("created_at"), descending: true },
+            Sort { field: String::from("title"), descending: false },
+        ]);
+        assert_eq!(config().parse_sort(&[]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_unsortable_field() {
+        let query = vec![(String::from("sort"), String::from("title,-body"))];
+
+        assert!(config().parse_sort(&query).is_err());
+    }
+}
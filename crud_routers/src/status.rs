@@ -0,0 +1,50 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Status codes of the create and delete item routes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatusCodes {
+    /// `201 Created` on create and bulk create, with a `Location` header for a single item,
+    /// `204 No Content` on delete.
+    #[default]
+    Rest,
+    /// `200 OK` on create, bulk create and delete, for clients of earlier versions.
+    Legacy,
+}
+
+/// The path of a created item below `path`, `None` if the item doesn't serialize its primary key.
+pub(crate) fn location(path: &str, item: &impl Serialize, primary_key: &str) -> Option<String> {
+    let id = match serde_json::to_value(item).ok()?.get(primary_key)? {
+        Value::String(id) => id.clone(),
+        Value::Null => return None,
+        id => id.to_string(),
+    };
+    Some(format!("{}/{}", path.trim_end_matches('/'), encode_segment(&id)))
+}
+
+/// Percent-encodes a path segment, all bytes but the unreserved characters of RFC 3986 are encoded.
+fn encode_segment(segment: &str) -> String {
+    segment.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_location() {
+        assert_eq!(location("/posts", &json!({"id": 1, "title": "Post"}), "id"), Some(String::from("/posts/1")));
+        assert_eq!(location("/base/api/", &json!({"id": 1}), "id"), Some(String::from("/base/api/1")));
+        assert_eq!(location("/posts", &json!({"slug": "first-post"}), "slug"), Some(String::from("/posts/first-post")));
+        assert_eq!(location("/posts", &json!({"slug": "a/b?c#d e"}), "slug"), Some(String::from("/posts/a%2Fb%3Fc%23d%20e")));
+        assert_eq!(location("/posts", &json!({"slug": "ü\n"}), "slug"), Some(String::from("/posts/%C3%BC%0A")));
+        assert_eq!(location("/posts", &json!({"id": null}), "id"), None);
+        assert_eq!(location("/posts", &json!({"title": "Post"}), "id"), None);
+    }
+}
@@ -0,0 +1,489 @@
+use serde::{Deserialize, Serialize};
+use crud_routers::InMemoryRepository;
+
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Post {
+    pub id: i64,
+    pub title: String,
+    pub body: String,
+    pub published: bool,
+}
+
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Serialize, Deserialize)]
+pub struct NewPost {
+    pub title: String,
+    pub body: String,
+    pub published: bool,
+}
+
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Serialize, Deserialize)]
+pub struct PostForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<bool>,
+}
+
+type Repository = InMemoryRepository<Post, i64>;
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+async fn e2e_axum() {
+    use std::sync::Arc;
+    use crud_routers::{AxumServer, CrudRouterBuilder, FieldType};
+    use test_utils::{e2e_test, TestApp};
+
+    let router = CrudRouterBuilder::new::<AxumServer>()
+        .repository::<Repository>()
+        .schema::<Post, i64>()
+        .create_schema::<NewPost>()
+        .update_schema::<PostForm>()
+        .enable_delete_all_items_route()
+        .filterable_field("id", FieldType::Integer)
+        .filterable_field("title", FieldType::String)
+        .filterable_field("published", FieldType::Bool)
+        .sortable_field("id", FieldType::Integer)
+        .sortable_field("title", FieldType::String)
+        .sortable_field("published", FieldType::Bool)
+        .prefix("posts")
+        .build_router()
+        .with_state(Arc::new(Repository::new()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    e2e_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "actix")]
+#[tokio::test]
+async fn e2e_actix() {
+    use actix_web::{App, HttpServer};
+    use actix_web::web::Data;
+    use crud_routers::{ActixServer, CrudRouterBuilder, FieldType, RoutePreset};
+    use test_utils::{e2e_test, TestApp};
+
+    let shared_state = Data::new(Repository::new());
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(shared_state.clone())
+            .service(
+                CrudRouterBuilder::new::<ActixServer>()
+                    .repository::<Repository>()
+                    .schema::<Post, i64>()
+                    .create_schema::<NewPost>()
+                    .update_schema::<PostForm>()
+                    .route_preset(RoutePreset::Full)
+                    .filterable_field("id", FieldType::Integer)
+                    .filterable_field("title", FieldType::String)
+                    .filterable_field("published", FieldType::Bool)
+                    .sortable_field("id", FieldType::Integer)
+                    .sortable_field("title", FieldType::String)
+                    .sortable_field("published", FieldType::Bool)
+                    .prefix("posts")
+                    .build_router()
+            )
+    })
+        .listen(listener).unwrap()
+        .run();
+    tokio::spawn(server);
+
+    e2e_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+async fn cursor_pagination_axum() {
+    use std::sync::Arc;
+    use crud_routers::{AxumServer, CrudRouterBuilder, FieldType, PaginationMode};
+    use test_utils::{cursor_pagination_test, TestApp};
+
+    let router = CrudRouterBuilder::new::<AxumServer>()
+        .repository::<Repository>()
+        .schema::<Post, i64>()
+        .create_schema::<NewPost>()
+        .enable_delete_all_items_route()
+        .filterable_field("title", FieldType::String)
+        .sortable_field("title", FieldType::String)
+        .pagination_mode(PaginationMode::Cursor)
+        .prefix("posts")
+        .build_router()
+        .with_state(Arc::new(Repository::new()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    cursor_pagination_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "actix")]
+#[tokio::test]
+async fn cursor_pagination_actix() {
+    use actix_web::{App, HttpServer};
+    use actix_web::web::Data;
+    use crud_routers::{ActixServer, CrudRouterBuilder, FieldType, PaginationMode};
+    use test_utils::{cursor_pagination_test, TestApp};
+
+    let shared_state = Data::new(Repository::new());
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(shared_state.clone())
+            .service(
+                CrudRouterBuilder::new::<ActixServer>()
+                    .repository::<Repository>()
+                    .schema::<Post, i64>()
+                    .create_schema::<NewPost>()
+                    .enable_delete_all_items_route()
+                    .filterable_field("title", FieldType::String)
+                    .sortable_field("title", FieldType::String)
+                    .pagination_mode(PaginationMode::Cursor)
+                    .prefix("posts")
+                    .build_router()
+            )
+    })
+        .listen(listener).unwrap()
+        .run();
+    tokio::spawn(server);
+
+    cursor_pagination_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+async fn total_count_axum() {
+    use std::sync::Arc;
+    use crud_routers::{AxumServer, CrudRouterBuilder, FieldType, ListFormat};
+    use test_utils::{total_count_test, TestApp};
+
+    for list_format in [ListFormat::Envelope, ListFormat::TotalCountHeader] {
+        let router = CrudRouterBuilder::new::<AxumServer>()
+            .repository::<Repository>()
+            .schema::<Post, i64>()
+            .create_schema::<NewPost>()
+            .enable_delete_all_items_route()
+            .filterable_field("published", FieldType::Bool)
+            .list_format(list_format)
+            .prefix("posts")
+            .build_router()
+            .with_state(Arc::new(Repository::new()));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        total_count_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+    }
+}
+
+#[cfg(feature = "actix")]
+#[tokio::test]
+async fn total_count_actix() {
+    use actix_web::{App, HttpServer};
+    use actix_web::web::Data;
+    use crud_routers::{ActixServer, CrudRouterBuilder, FieldType, ListFormat};
+    use test_utils::{total_count_test, TestApp};
+
+    for list_format in [ListFormat::Envelope, ListFormat::TotalCountHeader] {
+        let shared_state = Data::new(Repository::new());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(shared_state.clone())
+                .service(
+                    CrudRouterBuilder::new::<ActixServer>()
+                        .repository::<Repository>()
+                        .schema::<Post, i64>()
+                        .create_schema::<NewPost>()
+                        .enable_delete_all_items_route()
+                        .filterable_field("published", FieldType::Bool)
+                        .list_format(list_format)
+                        .prefix("posts")
+                        .build_router()
+                )
+        })
+            .listen(listener).unwrap()
+            .run();
+        tokio::spawn(server);
+
+        total_count_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+    }
+}
+
+#[tokio::test]
+async fn custom_id_generator() {
+    use crud_routers::{CreateRepository, CrudError, ReadDeleteRepository};
+
+    let repository = InMemoryRepository::<Post, i64>::with_id_generator(|| 42);
+
+    let new_post = || NewPost {
+        title: "Post".to_string(),
+        body: "Body".to_string(),
+        published: false,
+    };
+
+    let post: Post = repository.create_item(new_post()).await.unwrap();
+    assert_eq!(post.id, 42);
+    assert!(repository.get_item(42).await.unwrap().is_some());
+
+    let duplicate: Result<Post, CrudError> = repository.create_item(new_post()).await;
+    assert!(matches!(duplicate, Err(CrudError::Conflict(_))));
+
+    assert_eq!(<Repository as ReadDeleteRepository<Post, i64>>::get_table_name(), "post");
+}
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+async fn limit_axum() {
+    use std::sync::Arc;
+    use crud_routers::{AxumServer, CrudRouterBuilder};
+    use test_utils::{limit_test, TestApp};
+
+    let router = CrudRouterBuilder::new::<AxumServer>()
+        .repository::<Repository>()
+        .schema::<Post, i64>()
+        .create_schema::<NewPost>()
+        .enable_delete_all_items_route()
+        .default_limit(2)
+        .max_limit(3)
+        .prefix("posts")
+        .build_router()
+        .with_state(Arc::new(Repository::new()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    limit_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts"), 2, 3).await;
+}
+
+#[cfg(feature = "actix")]
+#[tokio::test]
+async fn limit_actix() {
+    use actix_web::{App, HttpServer};
+    use actix_web::web::Data;
+    use crud_routers::{ActixServer, CrudRouterBuilder};
+    use test_utils::{limit_test, TestApp};
+
+    let shared_state = Data::new(Repository::new());
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(shared_state.clone())
+            .service(
+                CrudRouterBuilder::new::<ActixServer>()
+                    .repository::<Repository>()
+                    .schema::<Post, i64>()
+                    .create_schema::<NewPost>()
+                    .enable_delete_all_items_route()
+                    .default_limit(2)
+                    .max_limit(3)
+                    .prefix("posts")
+                    .build_router()
+            )
+    })
+        .listen(listener).unwrap()
+        .run();
+    tokio::spawn(server);
+
+    limit_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts"), 2, 3).await;
+}
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+async fn json_patch_axum() {
+    use std::sync::Arc;
+    use crud_routers::{AxumServer, CrudRouterBuilder};
+    use test_utils::{json_patch_test, TestApp};
+
+    let router = CrudRouterBuilder::new::<AxumServer>()
+        .repository::<Repository>()
+        .schema::<Post, i64>()
+        .create_schema::<NewPost>()
+        .update_schema::<PostForm>()
+        .enable_delete_all_items_route()
+        .enable_json_patch()
+        .prefix("posts")
+        .build_router()
+        .with_state(Arc::new(Repository::new()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    json_patch_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "actix")]
+#[tokio::test]
+async fn json_patch_actix() {
+    use actix_web::{App, HttpServer};
+    use actix_web::web::Data;
+    use crud_routers::{ActixServer, CrudRouterBuilder};
+    use test_utils::{json_patch_test, TestApp};
+
+    let shared_state = Data::new(Repository::new());
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(shared_state.clone())
+            .service(
+                CrudRouterBuilder::new::<ActixServer>()
+                    .repository::<Repository>()
+                    .schema::<Post, i64>()
+                    .create_schema::<NewPost>()
+                    .update_schema::<PostForm>()
+                    .enable_delete_all_items_route()
+                    .enable_json_patch()
+                    .prefix("posts")
+                    .build_router()
+            )
+    })
+        .listen(listener).unwrap()
+        .run();
+    tokio::spawn(server);
+
+    json_patch_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+async fn etags_axum() {
+    use std::sync::Arc;
+    use crud_routers::{AxumServer, CrudRouterBuilder};
+    use test_utils::{etag_test, TestApp};
+
+    let router = CrudRouterBuilder::new::<AxumServer>()
+        .repository::<Repository>()
+        .schema::<Post, i64>()
+        .create_schema::<NewPost>()
+        .update_schema::<PostForm>()
+        .enable_etags()
+        .prefix("posts")
+        .build_router()
+        .with_state(Arc::new(Repository::new()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    etag_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "actix")]
+#[tokio::test]
+async fn etags_actix() {
+    use actix_web::{App, HttpServer};
+    use actix_web::web::Data;
+    use crud_routers::{ActixServer, CrudRouterBuilder};
+    use test_utils::{etag_test, TestApp};
+
+    let shared_state = Data::new(Repository::new());
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(shared_state.clone())
+            .service(
+                CrudRouterBuilder::new::<ActixServer>()
+                    .repository::<Repository>()
+                    .schema::<Post, i64>()
+                    .create_schema::<NewPost>()
+                    .update_schema::<PostForm>()
+                    .enable_etags()
+                    .prefix("posts")
+                    .build_router()
+            )
+    })
+        .listen(listener).unwrap()
+        .run();
+    tokio::spawn(server);
+
+    etag_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+async fn idempotency_keys_axum() {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crud_routers::{AxumServer, CrudRouterBuilder, InMemoryIdempotencyStore};
+    use test_utils::{idempotency_test, TestApp};
+
+    let router = CrudRouterBuilder::new::<AxumServer>()
+        .repository::<Repository>()
+        .schema::<Post, i64>()
+        .create_schema::<NewPost>()
+        .idempotency_store(InMemoryIdempotencyStore::new(), Duration::from_secs(60))
+        .prefix("posts")
+        .build_router()
+        .with_state(Arc::new(Repository::new()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    idempotency_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
+
+#[cfg(feature = "actix")]
+#[tokio::test]
+async fn idempotency_keys_actix() {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use actix_web::{App, HttpServer};
+    use actix_web::web::Data;
+    use crud_routers::{ActixServer, CrudRouterBuilder, InMemoryIdempotencyStore};
+    use test_utils::{idempotency_test, TestApp};
+
+    let shared_state = Data::new(Repository::new());
+    // shared by the router of every worker
+    let store = Arc::new(InMemoryIdempotencyStore::new());
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(shared_state.clone())
+            .service(
+                CrudRouterBuilder::new::<ActixServer>()
+                    .repository::<Repository>()
+                    .schema::<Post, i64>()
+                    .create_schema::<NewPost>()
+                    .idempotency_store(store.clone(), Duration::from_secs(60))
+                    .prefix("posts")
+                    .build_router()
+            )
+    })
+        .listen(listener).unwrap()
+        .run();
+    tokio::spawn(server);
+
+    idempotency_test(TestApp::new(format!("http://127.0.0.1:{}", port), "posts")).await;
+}
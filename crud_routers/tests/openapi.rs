@@ -1,7 +1,8 @@
 use std::fs;
+use std::time::Duration;
 use utoipa::openapi::{InfoBuilder, OpenApi, OpenApiBuilder};
 use utoipa::{ToSchema};
-use crud_routers::{ApiServer, CRUDRepository, CrudRouterBuilder, Pagination, ReadDeleteRepository};
+use crud_routers::{ApiServer, CRUDRepository, CrudError, CrudRouterBuilder, Cursor, ErrorFormat, FieldType, Filter, InMemoryIdempotencyStore, ListFormat, Pagination, PaginationMode, ReadDeleteRepository, RoutePreset, Sort};
 
 #[derive(ToSchema)]
 pub struct Post {
@@ -22,9 +23,9 @@ pub struct NewPost {
 
 #[derive(ToSchema)]
 pub struct PostForm {
-    title: Option<String>,
-    body: Option<String>,
-    published: Option<bool>,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub published: Option<bool>,
 }
 struct PrimaryKeyType;
 struct Repo;
@@ -35,26 +36,42 @@ impl ReadDeleteRepository<Post, PrimaryKeyType> for Repo {
         String::from("test_table_name")
     }
 
-    async fn list_items(&mut self, _pagination: Pagination) -> Vec<Post> {
+    fn get_primary_key_name(&self) -> String {
         unimplemented!()
     }
 
-    async fn get_item(&mut self, _id: PrimaryKeyType) -> Option<Post> {
+    async fn list_items(&self, _pagination: Pagination, _filters: Vec<Filter>, _sort: Vec<Sort>) -> Result<Vec<Post>, CrudError> {
         unimplemented!()
     }
 
-    async fn delete_item(&mut self, _id: PrimaryKeyType) {
+    async fn list_items_after(&self, _cursor: Option<Cursor>, _limit: Option<u64>, _filters: Vec<Filter>, _sort: Vec<Sort>) -> Result<Vec<Post>, CrudError> {
         unimplemented!()
     }
 
-    async fn delete_all_items(&mut self) -> usize {
+    async fn count_items(&self, _filters: Vec<Filter>) -> Result<u64, CrudError> {
+        unimplemented!()
+    }
+
+    async fn get_item(&self, _id: PrimaryKeyType) -> Result<Option<Post>, CrudError> {
+        unimplemented!()
+    }
+
+    async fn delete_item(&self, _id: PrimaryKeyType) -> Result<(), CrudError> {
+        unimplemented!()
+    }
+
+    async fn delete_items(&self, _ids: Vec<PrimaryKeyType>) -> Result<usize, CrudError> {
+        unimplemented!()
+    }
+
+    async fn delete_all_items(&self, _filters: Vec<Filter>) -> Result<usize, CrudError> {
         unimplemented!()
     }
 }
 
 struct TestServer;
 impl ApiServer for TestServer {
-    fn get_id_path(prefix: &str) -> String {
+    fn get_id_path(_prefix: &str) -> String {
         unimplemented!()
     }
 }
@@ -72,13 +89,14 @@ fn get_default_openapi() -> OpenApi {
 fn openapi_spec() {
     let mut api = get_default_openapi();
 
-    let b = CrudRouterBuilder::new::<TestServer>()
+    CrudRouterBuilder::new::<TestServer>()
         .repository::<Repo>()
         .prefix("base/api")
         .tag("table_name")
         .schema::<Post, PrimaryKeyType>()
         .create_schema::<NewPost>()
         .update_schema::<PostForm>()
+        .route_preset(RoutePreset::Full)
         .build_openapi(&mut api);
 
     let expected_api_spec = fs::read_to_string("tests/test_api_spec.json").unwrap();
@@ -90,7 +108,7 @@ fn openapi_spec() {
 fn openapi_spec_without_create_schema() {
     let mut api = get_default_openapi();
 
-    let b = CrudRouterBuilder::new::<TestServer>()
+    CrudRouterBuilder::new::<TestServer>()
         .repository::<Repo>()
         .prefix("base/api")
         .tag("table_name")
@@ -102,3 +120,191 @@ fn openapi_spec_without_create_schema() {
 
     assert_eq!(api.to_json().unwrap(), expected_api_spec);
 }
+
+#[test]
+fn openapi_spec_with_problem_details() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .error_format(ErrorFormat::ProblemJson)
+        .schema::<Post, PrimaryKeyType>()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_problem_details.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
+
+#[test]
+fn openapi_spec_with_filters_and_sort() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .filterable_field("title", FieldType::String)
+        .filterable_field("published", FieldType::Bool)
+        .sortable_field("title", FieldType::String)
+        .sortable_field("id", FieldType::Integer)
+        .schema::<Post, PrimaryKeyType>()
+        .disable_get_item_route()
+        .disable_delete_item_route()
+        .disable_delete_all_items_route()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_filters_and_sort.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
+
+#[test]
+fn openapi_spec_with_cursor_pagination() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .pagination_mode(PaginationMode::Cursor)
+        .schema::<Post, PrimaryKeyType>()
+        .disable_get_item_route()
+        .disable_delete_item_route()
+        .disable_delete_all_items_route()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_cursor_pagination.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
+
+#[test]
+fn openapi_spec_with_envelope() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .list_format(ListFormat::Envelope)
+        .schema::<Post, PrimaryKeyType>()
+        .disable_get_item_route()
+        .disable_delete_item_route()
+        .disable_delete_all_items_route()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_envelope.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
+
+#[test]
+fn openapi_spec_with_total_count_header() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .list_format(ListFormat::TotalCountHeader)
+        .schema::<Post, PrimaryKeyType>()
+        .disable_get_item_route()
+        .disable_delete_item_route()
+        .disable_delete_all_items_route()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_total_count_header.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
+
+#[test]
+fn openapi_spec_with_limits() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .default_limit(20)
+        .max_limit(100)
+        .schema::<Post, PrimaryKeyType>()
+        .disable_get_item_route()
+        .disable_delete_item_route()
+        .disable_delete_all_items_route()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_limits.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
+
+#[test]
+fn openapi_spec_with_json_patch() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .schema::<Post, PrimaryKeyType>()
+        .update_schema::<PostForm>()
+        .enable_json_patch()
+        .disable_list_items_route()
+        .disable_get_item_route()
+        .disable_delete_item_route()
+        .disable_delete_all_items_route()
+        .disable_update_item_route()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_json_patch.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
+
+#[test]
+fn openapi_spec_with_etags() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .schema::<Post, PrimaryKeyType>()
+        .update_schema::<PostForm>()
+        .enable_etags()
+        .disable_list_items_route()
+        .disable_delete_items_route()
+        .disable_update_items_route()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_etags.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
+
+#[test]
+fn openapi_spec_with_idempotency_keys() {
+    let mut api = get_default_openapi();
+
+    CrudRouterBuilder::new::<TestServer>()
+        .repository::<Repo>()
+        .prefix("base/api")
+        .tag("table_name")
+        .schema::<Post, PrimaryKeyType>()
+        .create_schema::<NewPost>()
+        .idempotency_store(InMemoryIdempotencyStore::new(), Duration::from_secs(60 * 60))
+        .disable_list_items_route()
+        .disable_get_item_route()
+        .disable_delete_item_route()
+        .disable_delete_items_route()
+        .disable_create_items_route()
+        .build_openapi(&mut api);
+
+    let expected_api_spec = fs::read_to_string("tests/test_api_spec_with_idempotency_keys.json").unwrap();
+
+    assert_eq!(api.to_json().unwrap(), expected_api_spec);
+}
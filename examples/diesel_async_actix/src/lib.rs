@@ -0,0 +1,57 @@
+pub mod models;
+pub mod schema;
+
+use std::env;
+use std::net::TcpListener;
+use actix_web::dev::Server;
+use actix_web::{App, HttpServer};
+use actix_web::web::Data;
+use diesel_async::AsyncPgConnection;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use diesel_async::pooled_connection::deadpool::Pool;
+use dotenvy::dotenv;
+use crud_routers::{ActixServer, AsyncDieselRepository, CrudRouterBuilder, FieldType};
+use crate::models::{NewPost, Post, PostForm};
+use crate::schema::posts;
+
+type Repository = AsyncDieselRepository<Pool<AsyncPgConnection>, posts::table>;
+
+pub fn run(listener: TcpListener) -> std::io::Result<Server> {
+    let pool = establish_connection_pool();
+    let shared_state = Data::new(
+        AsyncDieselRepository::from_pool(pool, posts::table)
+    );
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(shared_state.clone())
+            .service(
+                CrudRouterBuilder::new::<ActixServer>()
+                    .repository::<Repository>()
+                    .schema::<Post, i32>()
+                    .create_schema::<NewPost>()
+                    .update_schema::<PostForm>()
+                    .enable_delete_all_items_route()
+                    .filterable_field("id", FieldType::Integer)
+                    .filterable_field("title", FieldType::String)
+                    .filterable_field("published", FieldType::Bool)
+                    .sortable_field("id", FieldType::Integer)
+                    .sortable_field("title", FieldType::String)
+                    .sortable_field("published", FieldType::Bool)
+                    .build_router()
+            )
+    })
+        .listen(listener)?
+        .run();
+
+    Ok(server)
+}
+
+pub fn establish_connection_pool() -> Pool<AsyncPgConnection> {
+    dotenv().ok();
+
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    Pool::builder(AsyncDieselConnectionManager::new(&database_url))
+        .build()
+        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+}
@@ -2,16 +2,16 @@ pub mod models;
 pub mod schema;
 
 use std::env;
-use diesel::{Connection, PgConnection};
+use diesel::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool};
 use dotenvy::dotenv;
 use std::sync::Arc;
 use axum::Router;
 use axum::serve::Serve;
 use tokio::net::TcpListener;
-use tokio::sync::Mutex;
 use utoipa::openapi::{InfoBuilder, OpenApiBuilder};
 use utoipa_swagger_ui::SwaggerUi;
-use crud_routers::{AxumServer, CrudRouterBuilder, DieselRepository};
+use crud_routers::{AxumServer, CrudRouterBuilder, DieselRepository, FieldType, PaginationMode};
 use crate::models::{NewPost, Post, PostForm};
 use crate::schema::posts;
 
@@ -20,18 +20,37 @@ pub fn run(listener: TcpListener) -> Serve<TcpListener, Router, Router> {
         .info(InfoBuilder::new().title("Diesel Axum example").build())
         .build();
 
-    let connection = establish_connection();
-    let shared_state = Arc::new(Mutex::new(
-        DieselRepository::new(connection, posts::table)
-    ));
+    let pool = establish_connection_pool();
+    let shared_state = Arc::new(
+        DieselRepository::from_pool(pool, posts::table)
+    );
 
     let router = CrudRouterBuilder::new::<AxumServer>()
         .schema::<Post, i32>()
         .create_schema::<NewPost>()
         .update_schema::<PostForm>()
+        .enable_delete_all_items_route()
+        .filterable_field("id", FieldType::Integer)
+        .filterable_field("title", FieldType::String)
+        .filterable_field("published", FieldType::Bool)
+        .sortable_field("id", FieldType::Integer)
+        .sortable_field("title", FieldType::String)
+        .sortable_field("published", FieldType::Bool)
         .prefix("base/api")
         .build_openapi(&mut openapi)
         .build_router()
+        .merge(
+            // the same posts, paginated with a cursor
+            CrudRouterBuilder::new::<AxumServer>()
+                .schema::<Post, i32>()
+                .create_schema::<NewPost>()
+                .enable_delete_all_items_route()
+                .filterable_field("title", FieldType::String)
+                .sortable_field("title", FieldType::String)
+                .pagination_mode(PaginationMode::Cursor)
+                .prefix("base/feed")
+                .build_router()
+        )
         .with_state(shared_state)
         .merge(SwaggerUi::new("/docs/swagger/").url("/api-docs/openapi.json", openapi));
 
@@ -39,11 +58,12 @@ pub fn run(listener: TcpListener) -> Serve<TcpListener, Router, Router> {
 }
 
 
-pub fn establish_connection() -> PgConnection {
+pub fn establish_connection_pool() -> Pool<ConnectionManager<PgConnection>> {
     dotenv().ok();
 
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    PgConnection::establish(&database_url)
+    Pool::builder()
+        .build(ConnectionManager::new(&database_url))
         .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
 }
 
@@ -13,9 +13,9 @@ async fn main() -> io::Result<()> {
 mod tests {
     use crate::run;
     use tokio::net::TcpListener;
-    use test_utils::{TestApp, e2e_test};
+    use test_utils::{TestApp, cursor_pagination_test, e2e_test};
 
-    async fn spawn_app() -> TestApp{
+    async fn spawn_app() -> String{
         let listener = TcpListener::bind("127.0.0.1:0").await
             .expect("Could not bind TCP listener");
         let port = listener.local_addr().unwrap().port();
@@ -23,14 +23,15 @@ mod tests {
             run(listener).await.unwrap();
         });
 
-        TestApp::new(format!("http://127.0.0.1:{}", port), "base/api")
+        format!("http://127.0.0.1:{}", port)
     }
 
     #[tokio::test]
     async fn e2e(){
-        let app = spawn_app().await;
+        let address = spawn_app().await;
 
-        e2e_test(app).await;
+        e2e_test(TestApp::new(address.clone(), "base/api")).await;
+        cursor_pagination_test(TestApp::new(address, "base/feed")).await;
     }
 
 }
@@ -23,10 +23,13 @@ pub struct NewPost {
 }
 
 
-#[derive(Deserialize, AsChangeset, ToSchema)]
+#[derive(Serialize, Deserialize, AsChangeset, ToSchema)]
 #[diesel(table_name = posts)]
 pub struct PostForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
     title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     published: Option<bool>,
 }
\ No newline at end of file
@@ -0,0 +1,56 @@
+pub mod models;
+pub mod schema;
+
+use std::sync::Arc;
+use axum::Router;
+use axum::serve::Serve;
+use diesel::prelude::*;
+use diesel::connection::SimpleConnection;
+use tokio::net::TcpListener;
+use crud_routers::{AxumServer, CrudRouterBuilder, DieselRepository, FieldType};
+use crate::models::{NewPost, Post, PostForm};
+use crate::schema::posts;
+
+pub fn run(listener: TcpListener) -> Serve<TcpListener, Router, Router> {
+    let connection = establish_connection();
+    let shared_state = Arc::new(
+        DieselRepository::new(connection, posts::table)
+            .version_column("version")
+    );
+
+    let router = CrudRouterBuilder::new::<AxumServer>()
+        .schema::<Post, i32>()
+        .create_schema::<NewPost>()
+        .update_schema::<PostForm>()
+        .enable_delete_all_items_route()
+        .enable_etags()
+        .filterable_field("id", FieldType::Integer)
+        .filterable_field("title", FieldType::String)
+        .filterable_field("published", FieldType::Bool)
+        .sortable_field("id", FieldType::Integer)
+        .sortable_field("title", FieldType::String)
+        .sortable_field("published", FieldType::Bool)
+        .build_router()
+        .with_state(shared_state);
+
+    axum::serve(listener, router)
+}
+
+/// Opens a fresh in-memory database with the posts table.
+pub fn establish_connection() -> SqliteConnection {
+    let mut connection = SqliteConnection::establish(":memory:")
+        .expect("Error creating in-memory database");
+
+    connection.batch_execute(
+        "CREATE TABLE posts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            published BOOLEAN NOT NULL DEFAULT FALSE,
+            version INTEGER NOT NULL DEFAULT 0
+        )"
+    )
+        .expect("Error creating posts table");
+
+    connection
+}
@@ -0,0 +1,38 @@
+use diesel::prelude::*;
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+use crate::schema::posts;
+
+#[derive(Serialize, Queryable, Selectable, ToSchema)]
+#[diesel(table_name = crate::schema::posts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Post {
+    pub id: i32,
+    pub title: String,
+    pub body: String,
+    pub published: bool,
+    pub version: i32,
+}
+
+
+#[derive(Serialize, Deserialize, Insertable, ToSchema)]
+#[diesel(table_name = posts)]
+pub struct NewPost {
+    pub title: String,
+    pub body: String,
+    pub published: bool,
+}
+
+
+#[derive(Serialize, Deserialize, AsChangeset, ToSchema)]
+#[diesel(table_name = posts)]
+pub struct PostForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<i32>,
+}
\ No newline at end of file
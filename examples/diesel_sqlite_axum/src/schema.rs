@@ -0,0 +1,9 @@
+diesel::table! {
+    posts (id) {
+        id -> Integer,
+        title -> Text,
+        body -> Text,
+        published -> Bool,
+        version -> Integer,
+    }
+}
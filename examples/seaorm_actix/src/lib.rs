@@ -5,15 +5,14 @@ use actix_web::{App, HttpServer};
 use actix_web::web::Data;
 use dotenvy::dotenv;
 use sea_orm::{Database, DatabaseConnection};
-use tokio::sync::Mutex;
-use crud_routers::{ActixServer, CrudRouterBuilder, SeaOrmRepository};
+use crud_routers::{ActixServer, CrudRouterBuilder, ErrorFormat, FieldType, PaginationMode, SeaOrmRepository};
 
 pub mod post;
 
 pub async fn run(listener: TcpListener) -> std::io::Result<Server> {
     let connection = establish_connection().await;
 
-    let shared_state = Data::new(Mutex::new( SeaOrmRepository::new(connection)));
+    let shared_state = Data::new(SeaOrmRepository::new(connection));
 
     let server = HttpServer::new(move || {
         App::new()
@@ -24,6 +23,28 @@ pub async fn run(listener: TcpListener) -> std::io::Result<Server> {
                     .schema::<post::Model, i32>()
                     .create_schema::<post::NewPost>()
                     .update_schema::<post::PostForm>()
+                    .enable_delete_all_items_route()
+                    .filterable_field("id", FieldType::Integer)
+                    .filterable_field("title", FieldType::String)
+                    .filterable_field("published", FieldType::Bool)
+                    .sortable_field("id", FieldType::Integer)
+                    .sortable_field("title", FieldType::String)
+                    .sortable_field("published", FieldType::Bool)
+                    .error_format(ErrorFormat::ProblemJson)
+                    .build_router()
+            )
+            .service(
+                // the same posts, paginated with a cursor
+                CrudRouterBuilder::new::<ActixServer>()
+                    .repository::<SeaOrmRepository>()
+                    .schema::<post::Model, i32>()
+                    .create_schema::<post::NewPost>()
+                    .enable_delete_all_items_route()
+                    .filterable_field("title", FieldType::String)
+                    .sortable_field("title", FieldType::String)
+                    .pagination_mode(PaginationMode::Cursor)
+                    .error_format(ErrorFormat::ProblemJson)
+                    .prefix("feed")
                     .build_router()
             )
     })
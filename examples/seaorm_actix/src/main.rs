@@ -13,23 +13,24 @@ async fn main() -> io::Result<()> {
 mod tests {
     use crate::run;
     use std::net::TcpListener;
-    use test_utils::{TestApp, e2e_test};
+    use test_utils::{TestApp, cursor_pagination_test, e2e_test};
 
-    async fn spawn_app() -> TestApp{
+    async fn spawn_app() -> String{
         let listener = TcpListener::bind("127.0.0.1:0")
             .expect("Could not bind TCP listener");
         let port = listener.local_addr().unwrap().port();
         let server = run(listener).await.expect("Failed to bind address");
-        let _ = tokio::spawn(server);
+        tokio::spawn(server);
 
-        TestApp::new(format!("http://127.0.0.1:{}", port), "posts")
+        format!("http://127.0.0.1:{}", port)
     }
 
     #[tokio::test]
     async fn e2e(){
-        let app = spawn_app().await;
+        let address = spawn_app().await;
 
-        e2e_test(app).await;
+        e2e_test(TestApp::new(address.clone(), "posts")).await;
+        cursor_pagination_test(TestApp::new(address, "feed")).await;
     }
 
 }
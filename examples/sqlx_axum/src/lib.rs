@@ -0,0 +1,74 @@
+pub mod models;
+
+use std::sync::Arc;
+use std::time::Duration;
+use axum::Router;
+use axum::serve::Serve;
+use sqlx::{Sqlite, SqlitePool};
+use sqlx::sqlite::SqlitePoolOptions;
+use tokio::net::TcpListener;
+use crud_routers::{AxumServer, CrudRouterBuilder, FieldType, RepositoryIdempotencyStore, SqlxRepository};
+use crate::models::{IdempotencyKey, NewPost, Post, PostForm};
+
+pub async fn run(listener: TcpListener) -> Serve<TcpListener, Router, Router> {
+    let pool = establish_connection_pool().await;
+    let shared_state = Arc::new(SqlxRepository::new(pool));
+    let idempotency_store = RepositoryIdempotencyStore::<_, IdempotencyKey>::new(shared_state.clone());
+
+    let router = CrudRouterBuilder::new::<AxumServer>()
+        .repository::<SqlxRepository<Sqlite>>()
+        .schema::<Post, i64>()
+        .create_schema::<NewPost>()
+        .update_schema::<PostForm>()
+        .enable_delete_all_items_route()
+        .idempotency_store(idempotency_store, Duration::from_secs(24 * 60 * 60))
+        .filterable_field("id", FieldType::Integer)
+        .filterable_field("title", FieldType::String)
+        .filterable_field("published", FieldType::Bool)
+        .sortable_field("id", FieldType::Integer)
+        .sortable_field("title", FieldType::String)
+        .sortable_field("published", FieldType::Bool)
+        .build_router()
+        .with_state(shared_state);
+
+    axum::serve(listener, router)
+}
+
+/// Creates a fresh in-memory database, kept alive by a single pooled connection.
+pub async fn establish_connection_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Error creating in-memory database");
+
+    sqlx::query(
+        "CREATE TABLE posts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            published BOOLEAN NOT NULL DEFAULT FALSE
+        )"
+    )
+        .execute(&pool)
+        .await
+        .expect("Error creating posts table");
+
+    sqlx::query(
+        "CREATE TABLE idempotency_keys (
+            key TEXT PRIMARY KEY NOT NULL,
+            status INTEGER NOT NULL,
+            location TEXT,
+            body TEXT NOT NULL,
+            fingerprint TEXT NOT NULL,
+            expires_at INTEGER NOT NULL
+        )"
+    )
+        .execute(&pool)
+        .await
+        .expect("Error creating idempotency_keys table");
+
+    pool
+}
@@ -0,0 +1,44 @@
+use sqlx_axum::run;
+use std::io;
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:8009").await
+        .expect("Could not bind TCP listener");
+    run(listener).await.await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run;
+    use tokio::net::TcpListener;
+    use test_utils::{TestApp, e2e_test, idempotency_test};
+
+    async fn spawn_app() -> TestApp{
+        let listener = TcpListener::bind("127.0.0.1:0").await
+            .expect("Could not bind TCP listener");
+        let port = listener.local_addr().unwrap().port();
+        let server = run(listener).await;
+        tokio::spawn(async move {
+            server.await.unwrap();
+        });
+
+        TestApp::new(format!("http://127.0.0.1:{}", port), "posts")
+    }
+
+    #[tokio::test]
+    async fn e2e(){
+        let app = spawn_app().await;
+
+        e2e_test(app).await;
+    }
+
+    #[tokio::test]
+    async fn idempotency_keys(){
+        let app = spawn_app().await;
+
+        idempotency_test(app).await;
+    }
+
+}
@@ -0,0 +1,55 @@
+use serde::{Serialize, Deserialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use crud_routers::SqlxTable;
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct Post {
+    pub id: i64,
+    pub title: String,
+    pub body: String,
+    pub published: bool,
+}
+
+impl SqlxTable for Post {
+    const TABLE_NAME: &'static str = "posts";
+    const PRIMARY_KEY: &'static str = "id";
+    const COLUMNS: &'static [&'static str] = &["id", "title", "body", "published"];
+}
+
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct NewPost {
+    pub title: String,
+    pub body: String,
+    pub published: bool,
+}
+
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PostForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published: Option<bool>,
+}
+
+
+/// Responses of the create route, replayed to retries with the same `Idempotency-Key`.
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct IdempotencyKey {
+    pub key: String,
+    pub status: i64,
+    pub location: Option<String>,
+    pub body: String,
+    pub fingerprint: String,
+    pub expires_at: i64,
+}
+
+impl SqlxTable for IdempotencyKey {
+    const TABLE_NAME: &'static str = "idempotency_keys";
+    const PRIMARY_KEY: &'static str = "key";
+    const COLUMNS: &'static [&'static str] = &["key", "status", "location", "body", "fingerprint", "expires_at"];
+}
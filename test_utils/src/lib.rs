@@ -30,8 +30,27 @@ impl TestApp {
         .await
         .expect("Failed to execute request.")
     }
+    async fn list_filtered(&self, filters: &[(&str, String)]) -> reqwest::Response {
+        self.api_client.get(&self.address)
+            .query(filters)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
     async fn get(&self, id: i64) -> reqwest::Response {
-        self.api_client.get(&format!("{}/{}", &self.address, id))
+        self.api_client.get(format!("{}/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+    async fn exists(&self, id: i64) -> reqwest::Response {
+        self.api_client.head(format!("{}/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+    async fn count(&self) -> reqwest::Response {
+        self.api_client.head(&self.address)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -44,22 +63,62 @@ impl TestApp {
             .await
             .expect("Failed to execute request.")
     }
+    async fn create_many(&self, body: impl Serialize) -> reqwest::Response {
+        self.api_client.post(format!("{}/bulk", &self.address))
+            .header("Content-Type", mime::APPLICATION_JSON.as_ref())
+            .body(reqwest::Body::from(serde_json::to_vec(&body).unwrap()))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
     async fn update(&self, id: i64, body: impl Serialize) -> reqwest::Response {
-        self.api_client.put(&format!("{}/{}", &self.address, id))
+        self.api_client.put(format!("{}/{}", &self.address, id))
             .body(reqwest::Body::from(serde_json::to_vec(&body).unwrap()))
             .header("Content-Type", mime::APPLICATION_JSON.as_ref())
             .send()
             .await
             .expect("Failed to execute request.")
     }
+    async fn patch(&self, id: i64, body: impl Serialize) -> reqwest::Response {
+        self.api_client.patch(format!("{}/{}", &self.address, id))
+            .body(reqwest::Body::from(serde_json::to_vec(&body).unwrap()))
+            .header("Content-Type", "application/merge-patch+json")
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
     async fn delete(&self, id: i64) -> reqwest::Response {
-        self.api_client.delete(&format!("{}/{}", &self.address, id))
+        self.api_client.delete(format!("{}/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+    async fn delete_many(&self, ids: &str) -> reqwest::Response {
+        self.api_client.delete(&self.address)
+            .query(&[("ids", ids)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+    async fn update_many(&self, ids: &str, body: impl Serialize) -> reqwest::Response {
+        self.api_client.patch(&self.address)
+            .query(&[("ids", ids)])
+            .body(reqwest::Body::from(serde_json::to_vec(&body).unwrap()))
+            .header("Content-Type", mime::APPLICATION_JSON.as_ref())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+    async fn delete_filtered(&self, filters: &[(&str, String)]) -> reqwest::Response {
+        self.api_client.delete(&self.address)
+            .query(filters)
             .send()
             .await
             .expect("Failed to execute request.")
     }
     async fn delete_all(&self) -> reqwest::Response {
         self.api_client.delete(&self.address)
+            .query(&[("confirm", "true")])
             .send()
             .await
             .expect("Failed to execute request.")
@@ -78,10 +137,13 @@ pub async fn e2e_test(app: TestApp){
         &json!({"title": "Post", "body": "Body", "published": false})
     ).await;
 
-    assert!(response.status().is_success());
+    assert_eq!(response.status().as_u16(), 201);
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    let location = response.url().join(location).unwrap();
 
     let body = response.bytes().await.unwrap();
     let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(location.as_str(), format!("{}/{}", app.address, body.get("id").unwrap()));
     assert_eq!(*body.get("title").unwrap(), json!("Post"));
     assert_eq!(*body.get("body").unwrap(), json!("Body"));
     assert_eq!(*body.get("published").unwrap(), json!(false));
@@ -112,6 +174,15 @@ pub async fn e2e_test(app: TestApp){
 
     let post_ids = posts.iter_mut().map(|p| p.as_object_mut().unwrap().remove("id").unwrap().as_i64().unwrap()).collect::<Vec<i64>>();
 
+    // head checks an item and counts all of them without a body
+    let response = app.exists(post_ids[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.bytes().await.unwrap().is_empty());
+
+    let response = app.count().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("X-Total-Count").unwrap(), "3");
+
     // skip 1 and limit 1
     let response = app.list_all(Some(1), Some(1)).await;
 
@@ -142,17 +213,40 @@ pub async fn e2e_test(app: TestApp){
     assert_eq!(*body.get("body").unwrap(), json!("First Post Body"));
     assert_eq!(*body.get("published").unwrap(), json!(false));
 
+    // put replaces the whole item, so every field is required
+    let response = app.update(first_post_id, &json!({"title": "Replaced Post"})).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    // patch changes only the given fields
+    let response = app.patch(first_post_id, &json!({"title": "Patched Post"})).await;
+    assert!(response.status().is_success());
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("id").unwrap(), json!(first_post_id));
+    assert_eq!(*body.get("title").unwrap(), json!("Patched Post"));
+    assert_eq!(*body.get("body").unwrap(), json!("First Post Body"));
+    assert_eq!(*body.get("published").unwrap(), json!(false));
+
     // delete first one
     let response = app.delete(first_post_id).await;
 
-    assert!(response.status().is_success());
+    assert_eq!(response.status().as_u16(), 204);
+
+    // try to get, update and delete the deleted one
+    let response = app.exists(first_post_id).await;
+    assert_eq!(response.status().as_u16(), 404);
 
-    // try to get the deleted one
     let response = app.get(first_post_id).await;
+    assert_eq!(response.status().as_u16(), 404);
 
-    assert!(response.status().is_success());
-    let body = response.bytes().await.unwrap();
-    assert_eq!(&body[..], b"null");
+    let response = app.update(first_post_id, &posts[0]).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.patch(first_post_id, &json!({"title": "Patched Post"})).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.delete(first_post_id).await;
+    assert_eq!(response.status().as_u16(), 404);
 
     // get 2 of them
     let response = app.list_all(None, None).await;
@@ -169,6 +263,77 @@ pub async fn e2e_test(app: TestApp){
         assert_eq!(*body.get("published").unwrap(), json!(false));
     }
 
+    // filter the 2 remaining posts and a published one
+    let response = app.create(
+        &json!({"title": "Published 100%", "body": "Body", "published": true})
+    ).await;
+    assert!(response.status().is_success());
+
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let published_id = body.get("id").unwrap().as_i64().unwrap();
+
+    let filtered_ids = |response: reqwest::Response| async move {
+        assert!(response.status().is_success());
+        let body = response.bytes().await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        body.as_array().unwrap().iter().map(|p| p.get("id").unwrap().as_i64().unwrap()).collect::<Vec<i64>>()
+    };
+
+    let response = app.list_filtered(&[("published", "true".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, vec![published_id]);
+
+    let response = app.list_filtered(&[("published__ne", "true".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, vec![post_ids[1], post_ids[2]]);
+
+    let response = app.list_filtered(&[("title__contains", "100%".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, vec![published_id]);
+
+    let response = app.list_filtered(&[("title__contains", "0_".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, Vec::<i64>::new());
+
+    let response = app.list_filtered(&[("id__in", format!("{},{}", post_ids[1], published_id))]).await;
+    assert_eq!(filtered_ids(response).await, vec![post_ids[1], published_id]);
+
+    let response = app.list_filtered(&[("id__gt", post_ids[1].to_string()), ("id__lt", published_id.to_string())]).await;
+    assert_eq!(filtered_ids(response).await, vec![post_ids[2]]);
+
+    let response = app.list_filtered(&[("title__is_null", "true".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, Vec::<i64>::new());
+
+    let response = app.list_filtered(&[("title", "Post".to_string()), ("skip", "1".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, vec![post_ids[2]]);
+
+    let response = app.list_filtered(&[("published", "maybe".to_string())]).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // sort them, ties are ordered by id
+    let response = app.list_filtered(&[("sort", "-id".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, vec![published_id, post_ids[2], post_ids[1]]);
+
+    let response = app.list_filtered(&[("sort", "-title".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, vec![published_id, post_ids[1], post_ids[2]]);
+
+    let response = app.list_filtered(&[("sort", "published,-id".to_string()), ("limit", "2".to_string())]).await;
+    assert_eq!(filtered_ids(response).await, vec![post_ids[2], post_ids[1]]);
+
+    let response = app.list_filtered(&[("sort", "body".to_string())]).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // delete only the published post
+    let response = app.delete_filtered(&[("published", "true".to_string())]).await;
+    assert_eq!(response.text().await.unwrap(), "1");
+
+    let response = app.list_filtered(&[]).await;
+    assert_eq!(filtered_ids(response).await, vec![post_ids[1], post_ids[2]]);
+
+    // deleting everything has to be confirmed
+    let response = app.delete_filtered(&[]).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.list_filtered(&[]).await;
+    assert_eq!(filtered_ids(response).await, vec![post_ids[1], post_ids[2]]);
+
     // delete all
     let response = app.delete_all().await;
 
@@ -181,4 +346,440 @@ pub async fn e2e_test(app: TestApp){
 
     let body = response.bytes().await.unwrap();
     assert_eq!(&body[..], b"[]");
-}
\ No newline at end of file
+
+    // malformed ids and bodies are rejected
+    let response = app.api_client.get(format!("{}/not-an-id", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.create(&json!({"title": 1})).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    // create many posts at once
+    let response = app.create_many(&json!([
+        {"title": "First Bulk Post", "body": "Body", "published": false},
+        {"title": "Second Bulk Post", "body": "Body", "published": true},
+    ])).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let titles: Vec<_> = body.as_array().unwrap().iter()
+        .map(|post| post.get("title").unwrap().as_str().unwrap())
+        .collect();
+    assert_eq!(titles, vec!["First Bulk Post", "Second Bulk Post"]);
+    for post in body.as_array().unwrap() {
+        let response = app.get(post.get("id").unwrap().as_i64().unwrap()).await;
+        assert!(response.status().is_success());
+    }
+
+    let response = app.create_many(&json!([])).await;
+    assert!(response.status().is_success());
+    assert_eq!(response.text().await.unwrap(), "[]");
+
+    // nothing is created if one of the posts is malformed
+    let response = app.create_many(&json!([
+        {"title": "Third Bulk Post", "body": "Body", "published": false},
+        {"title": 1},
+    ])).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = app.list_all(None, None).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    let bulk_ids: Vec<i64> = body.as_array().unwrap().iter()
+        .map(|post| post.get("id").unwrap().as_i64().unwrap())
+        .collect();
+    let missing_id = bulk_ids[1] + 1000;
+
+    // update and delete many posts by their ids, missing ids are skipped,
+    // the version is only read by repositories with a version column
+    let response = app.update_many(&format!("{},{}", bulk_ids[0], missing_id), &json!({"published": true, "version": 0})).await;
+    assert!(response.status().is_success());
+    assert_eq!(response.text().await.unwrap(), "1");
+
+    let response = app.get(bulk_ids[0]).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("title").unwrap(), json!("First Bulk Post"));
+    assert_eq!(*body.get("published").unwrap(), json!(true));
+
+    let response = app.update_many("not-an-id", &json!({"published": true})).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.delete_many(&format!("{},{},{}", bulk_ids[0], bulk_ids[1], missing_id)).await;
+    assert!(response.status().is_success());
+    assert_eq!(response.text().await.unwrap(), "2");
+
+    let response = app.get(bulk_ids[1]).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.delete_all().await;
+    assert!(response.status().is_success());
+}
+
+/// Pages through a list route in cursor pagination mode, `app` has to be empty.
+pub async fn cursor_pagination_test(app: TestApp){
+    let mut ids = Vec::new();
+    for title in ["Post 1", "Post 2", "Post 2", "Post 3", "Post 2"] {
+        let response = app.create(
+            &json!({"title": title, "body": "Body", "published": false})
+        ).await;
+        assert!(response.status().is_success());
+
+        let body = response.bytes().await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        ids.push(body.get("id").unwrap().as_i64().unwrap());
+    }
+
+    let page = |response: reqwest::Response| async move {
+        assert!(response.status().is_success());
+        let body = response.bytes().await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let ids = body.get("items").unwrap().as_array().unwrap().iter().map(|p| p.get("id").unwrap().as_i64().unwrap()).collect::<Vec<i64>>();
+        let next_cursor = body.get("next_cursor").unwrap().as_str().map(|cursor| cursor.to_string());
+        (ids, next_cursor)
+    };
+
+    // ties of the sort key are ordered by id
+    let response = app.list_filtered(&[("sort", "-title".to_string()), ("limit", "2".to_string())]).await;
+    let (first_page, cursor) = page(response).await;
+    assert_eq!(first_page, vec![ids[3], ids[1]]);
+
+    // items inserted before the cursor don't shift the next pages
+    let response = app.create(
+        &json!({"title": "Post 4", "body": "Body", "published": false})
+    ).await;
+    assert!(response.status().is_success());
+
+    let response = app.list_filtered(&[("sort", "-title".to_string()), ("limit", "2".to_string()), ("cursor", cursor.unwrap())]).await;
+    let (second_page, cursor) = page(response).await;
+    assert_eq!(second_page, vec![ids[2], ids[4]]);
+
+    let response = app.list_filtered(&[("sort", "-title".to_string()), ("limit", "2".to_string()), ("cursor", cursor.unwrap())]).await;
+    assert_eq!(page(response).await, (vec![ids[0]], None));
+
+    // filters apply to every page
+    let response = app.list_filtered(&[("title", "Post 2".to_string()), ("limit", "1".to_string())]).await;
+    let (first_page, cursor) = page(response).await;
+    assert_eq!(first_page, vec![ids[1]]);
+
+    let response = app.list_filtered(&[("title", "Post 2".to_string()), ("cursor", cursor.unwrap())]).await;
+    assert_eq!(page(response).await, (vec![ids[2], ids[4]], None));
+
+    let response = app.list_filtered(&[("cursor", "not-a-cursor".to_string())]).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // offset parameters aren't read in cursor mode
+    let response = app.list_filtered(&[("skip", "abc".to_string())]).await;
+    assert!(response.status().is_success());
+
+    // cursor values have to match the types of the sort key and the id, `[1,7]` and `["Post","7"]`
+    for cursor in ["WzEsN10", "WyJQb3N0IiwiNyJd"] {
+        let response = app.list_filtered(&[("sort", "-title".to_string()), ("cursor", cursor.to_string())]).await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    let response = app.delete_all().await;
+    assert!(response.status().is_success());
+}
+
+
+/// Checks the total count of a list route with `ListFormat::Envelope` or
+/// `ListFormat::TotalCountHeader`, `app` has to be empty.
+pub async fn total_count_test(app: TestApp){
+    for published in [false, true, false] {
+        let response = app.create(
+            &json!({"title": "Post", "body": "Body", "published": published})
+        ).await;
+        assert!(response.status().is_success());
+    }
+
+    let page = |response: reqwest::Response| async move {
+        assert!(response.status().is_success());
+        let header = response.headers().get("X-Total-Count").map(|total| total.to_str().unwrap().parse::<u64>().unwrap());
+        let body = response.bytes().await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        match header {
+            Some(total) => (body.as_array().unwrap().len(), total),
+            None => {
+                assert_eq!(body.get("skip"), Some(&json!(1)));
+                assert_eq!(body.get("limit"), Some(&json!(1)));
+                (body.get("items").unwrap().as_array().unwrap().len(), body.get("total").unwrap().as_u64().unwrap())
+            }
+        }
+    };
+
+    let response = app.list_filtered(&[("skip", "1".to_string()), ("limit", "1".to_string())]).await;
+    assert_eq!(page(response).await, (1, 3));
+
+    // the total counts the filtered items
+    let response = app.list_filtered(&[("published", "false".to_string()), ("skip", "1".to_string()), ("limit", "1".to_string())]).await;
+    assert_eq!(page(response).await, (1, 2));
+
+    let response = app.delete_all().await;
+    assert!(response.status().is_success());
+}
+
+/// Checks the limits of a list route with `default_limit` and `max_limit` set, `app` has to be empty.
+pub async fn limit_test(app: TestApp, default_limit: usize, max_limit: usize){
+    for _ in 0..=max_limit {
+        let response = app.create(
+            &json!({"title": "Post", "body": "Body", "published": false})
+        ).await;
+        assert!(response.status().is_success());
+    }
+
+    let len = |response: reqwest::Response| async move {
+        assert!(response.status().is_success());
+        let body = response.bytes().await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        body.as_array().unwrap().len()
+    };
+
+    let response = app.list_all(None, None).await;
+    assert_eq!(len(response).await, default_limit);
+
+    let response = app.list_all(Some(1), Some(max_limit as u32)).await;
+    assert_eq!(len(response).await, max_limit);
+
+    let response = app.list_all(None, Some(max_limit as u32 + 1)).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.delete_all().await;
+    assert!(response.status().is_success());
+}
+
+/// Edits an item with JSON Patch documents, the router has to enable them and `app` has to be empty.
+pub async fn json_patch_test(app: TestApp){
+    let json_patch = |id: i64, body: Value| app.api_client.patch(format!("{}/{}", &app.address, id))
+        .body(reqwest::Body::from(serde_json::to_vec(&body).unwrap()))
+        .header("Content-Type", "application/json-patch+json")
+        .send();
+
+    let response = app.create(
+        &json!({"title": "Post", "body": "Body", "published": false})
+    ).await;
+    assert!(response.status().is_success());
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let id = body.get("id").unwrap().as_i64().unwrap();
+
+    let response = json_patch(id, json!([
+        {"op": "test", "path": "/title", "value": "Post"},
+        {"op": "replace", "path": "/title", "value": "Patched Post"},
+        {"op": "replace", "path": "/published", "value": true},
+    ])).await.expect("Failed to execute request.");
+    assert!(response.status().is_success());
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("title").unwrap(), json!("Patched Post"));
+    assert_eq!(*body.get("body").unwrap(), json!("Body"));
+    assert_eq!(*body.get("published").unwrap(), json!(true));
+
+    // a failed test leaves the item unchanged
+    let response = json_patch(id, json!([
+        {"op": "test", "path": "/title", "value": "Post"},
+        {"op": "replace", "path": "/body", "value": "Other Body"},
+    ])).await.expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.get(id).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("body").unwrap(), json!("Body"));
+
+    let response = json_patch(id, json!({"title": "Not a JSON Patch"})).await.expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = json_patch(id, json!([{"op": "replace", "path": "/missing/field", "value": 1}])).await.expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 422);
+
+    // merge patches are still accepted
+    let response = app.patch(id, &json!({"body": "Merged Body"})).await;
+    assert!(response.status().is_success());
+
+    let response = app.delete_all().await;
+    assert!(response.status().is_success());
+}
+
+pub async fn etag_test(app: TestApp){
+    let with_header = |request: reqwest::RequestBuilder, name: &'static str, etag: &str| request
+        .header(name, etag)
+        .send();
+
+    let response = app.create(
+        &json!({"title": "Post", "body": "Body", "published": false})
+    ).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let id = body.get("id").unwrap().as_i64().unwrap();
+    let item_url = format!("{}/{}", &app.address, id);
+
+    let response = app.get(id).await;
+    assert!(response.status().is_success());
+    let etag = response.headers().get("ETag").unwrap().to_str().unwrap().to_string();
+
+    // a cached item is not sent again
+    let response = with_header(app.api_client.get(&item_url), "If-None-Match", &etag).await.unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+    assert_eq!(response.headers().get("ETag").unwrap().to_str().unwrap(), etag);
+
+    let response = with_header(app.api_client.get(&item_url), "If-None-Match", "\"stale\"").await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // updates with the current ETag succeed and change it
+    let response = with_header(
+        app.api_client.patch(&item_url)
+            .body(reqwest::Body::from(serde_json::to_vec(&json!({"title": "Patched Post"})).unwrap()))
+            .header("Content-Type", "application/merge-patch+json"),
+        "If-Match",
+        &etag
+    ).await.unwrap();
+    assert!(response.status().is_success());
+    let new_etag = response.headers().get("ETag").unwrap().to_str().unwrap().to_string();
+    assert_ne!(new_etag, etag);
+
+    // the stale ETag is rejected and nothing changes
+    let response = with_header(
+        app.api_client.put(&item_url)
+            .body(reqwest::Body::from(serde_json::to_vec(&json!({"title": "Replaced Post", "body": "Body", "published": true})).unwrap()))
+            .header("Content-Type", "application/json"),
+        "If-Match",
+        &etag
+    ).await.unwrap();
+    assert_eq!(response.status().as_u16(), 412);
+
+    let response = with_header(app.api_client.delete(&item_url), "If-Match", &etag).await.unwrap();
+    assert_eq!(response.status().as_u16(), 412);
+
+    let response = app.get(id).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("title").unwrap(), json!("Patched Post"));
+
+    let response = with_header(app.api_client.delete(&item_url), "If-Match", &new_etag).await.unwrap();
+    assert!(response.status().is_success());
+
+    let response = app.get(id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+pub async fn version_test(app: TestApp){
+    let response = app.create(
+        &json!({"title": "Post", "body": "Body", "published": false})
+    ).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let id = body.get("id").unwrap().as_i64().unwrap();
+    let version = body.get("version").unwrap().as_i64().unwrap();
+
+    // an update with the current version bumps it
+    let response = app.update(id, &json!({"title": "Updated Post", "body": "Body", "published": false, "version": version})).await;
+    assert!(response.status().is_success());
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("version").unwrap(), json!(version + 1));
+
+    // the stale version is rejected and nothing changes
+    let response = app.update(id, &json!({"title": "Stale Post", "body": "Body", "published": false, "version": version})).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.get(id).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("title").unwrap(), json!("Updated Post"));
+    assert_eq!(*body.get("version").unwrap(), json!(version + 1));
+
+    // a patch is checked against the version it was applied to
+    let response = app.patch(id, &json!({"title": "Patched Post"})).await;
+    assert!(response.status().is_success());
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("title").unwrap(), json!("Patched Post"));
+    assert_eq!(*body.get("version").unwrap(), json!(version + 2));
+
+    let response = app.update(id + 1000, &json!({"title": "Missing Post", "body": "Body", "published": false, "version": version})).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    // an update without a version is rejected
+    let response = app.update(id, &json!({"title": "Unversioned Post", "body": "Body", "published": false})).await;
+    assert_eq!(response.status().as_u16(), 428);
+
+    // a batch update checks and bumps the version of every item
+    let response = app.update_many(&id.to_string(), &json!({"published": true})).await;
+    assert_eq!(response.status().as_u16(), 428);
+
+    let response = app.update_many(&id.to_string(), &json!({"published": true, "version": version + 1})).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.update_many(&id.to_string(), &json!({"published": true, "version": version + 2})).await;
+    assert!(response.status().is_success());
+    assert_eq!(response.text().await.unwrap(), "1");
+
+    let response = app.get(id).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(*body.get("title").unwrap(), json!("Patched Post"));
+    assert_eq!(*body.get("published").unwrap(), json!(true));
+    assert_eq!(*body.get("version").unwrap(), json!(version + 3));
+
+    let response = app.delete(id).await;
+    assert!(response.status().is_success());
+}
+
+pub async fn idempotency_test(app: TestApp){
+    let create = |key: &'static str, title: &'static str| app.api_client.post(&app.address)
+        .header("Idempotency-Key", key)
+        .header("Content-Type", mime::APPLICATION_JSON.as_ref())
+        .body(reqwest::Body::from(serde_json::to_vec(&json!({"title": title, "body": "Body", "published": false})).unwrap()))
+        .send();
+
+    let response = create("first", "Post").await.unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    assert!(response.headers().get("Idempotent-Replayed").is_none());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap().to_string();
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let id = body.get("id").unwrap().as_i64().unwrap();
+
+    // a retry gets the first response and doesn't create the item again
+    let response = create("first", "Post").await.unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    assert_eq!(response.headers().get("Idempotent-Replayed").unwrap(), "true");
+    assert_eq!(response.headers().get("Location").unwrap().to_str().unwrap(), location);
+    let replayed = response.bytes().await.unwrap();
+    let replayed: Value = serde_json::from_slice(&replayed).unwrap();
+    assert_eq!(replayed, body);
+
+    // the key can't be reused for another request
+    let response = create("first", "Other Post").await.unwrap();
+    assert_eq!(response.status().as_u16(), 422);
+
+    // a failed request doesn't take the key
+    let response = app.api_client.post(&app.address)
+        .header("Idempotency-Key", "second")
+        .header("Content-Type", mime::APPLICATION_JSON.as_ref())
+        .body(reqwest::Body::from(serde_json::to_vec(&json!({"title": "Other Post"})).unwrap()))
+        .send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = create("second", "Other Post").await.unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let other_id = body.get("id").unwrap().as_i64().unwrap();
+    assert_ne!(other_id, id);
+
+    let response = app.list_all(None, None).await;
+    let body = response.bytes().await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 2);
+
+    for id in [id, other_id] {
+        let response = app.delete(id).await;
+        assert!(response.status().is_success());
+    }
+}